@@ -0,0 +1,237 @@
+//! Incremental cache of read files (`--cache DIR`)
+//!
+//! Reading, decoding and transforming the files is most of the work of a run, so
+//! with a cache directory each included file's final content and token count are
+//! kept on disk and reused while the file's size and modification time stay the
+//! same. Entries belong to one set of options: the cache file is named after a
+//! hash of them, so runs with different flags never see each other's content.
+//!
+//! The file holds a version line, then one record per file: a tab-separated
+//! line with the size, modification time, token count, content length and
+//! path, followed by the content itself. Only files read during the last run
+//! are kept, and a cache that can't be parsed is simply rebuilt.
+
+use crate::utils::write_atomic;
+use crate::Result;
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// First line of every cache file; bump it when the record layout changes
+const CACHE_VERSION: &str = "fuse-cache 1";
+
+/// Files modified this close to the start of the run are not stored, since a
+/// second change within the same timestamp tick would go unnoticed
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// A file's content as it was last included
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedFile {
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub modified: u128,
+    pub tokens: usize,
+    pub content: String,
+}
+
+/// Included files from earlier runs with the same options
+#[derive(Debug)]
+pub struct FileCache {
+    path: PathBuf,
+    started: SystemTime,
+    /// Entries from the previous run not yet looked up in this one
+    previous: HashMap<PathBuf, CachedFile>,
+    /// Entries used or added in this run, written back by `save`
+    current: HashMap<PathBuf, CachedFile>,
+}
+
+impl FileCache {
+    /// Load the cache for `options_key` from `dir`, starting empty if there is none yet
+    pub fn open(dir: &Path, options_key: &str) -> Self {
+        let path = dir.join(format!("{}.cache", options_key));
+        let previous = fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| parse(&data))
+            .unwrap_or_default();
+        Self {
+            path,
+            started: SystemTime::now(),
+            previous,
+            current: HashMap::new(),
+        }
+    }
+
+    /// Where the cache is stored
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The cached entry for `path`, if the file is unchanged since it was stored
+    pub fn lookup(&mut self, path: &Path, metadata: &Metadata) -> Option<&CachedFile> {
+        let (size, modified) = stamp(metadata)?;
+        if let Some(entry) = self.previous.remove(path) {
+            if entry.size == size && entry.modified == modified {
+                self.current.insert(path.to_path_buf(), entry);
+            }
+        }
+        self.current
+            .get(path)
+            .filter(|entry| entry.size == size && entry.modified == modified)
+    }
+
+    /// Remember the content `path` was included with
+    pub fn insert(&mut self, path: &Path, metadata: &Metadata, content: &str, tokens: usize) {
+        let Some((size, modified)) = stamp(metadata) else {
+            return;
+        };
+        let settled = metadata
+            .modified()
+            .is_ok_and(|time| time + RACY_WINDOW <= self.started);
+        // The path has to fit on the record's header line
+        if !settled || path.to_str().is_none_or(|p| p.contains('\n')) {
+            return;
+        }
+        self.current.insert(
+            path.to_path_buf(),
+            CachedFile {
+                size,
+                modified,
+                tokens,
+                content: content.to_string(),
+            },
+        );
+    }
+
+    /// Token count stored for `path`, if its included content is `content`
+    pub fn tokens(&self, path: &Path, content: &str) -> Option<usize> {
+        self.current
+            .get(path)
+            .filter(|entry| entry.content == content)
+            .map(|entry| entry.tokens)
+    }
+
+    /// Write the entries of this run back to disk
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut paths: Vec<&PathBuf> = self.current.keys().collect();
+        paths.sort();
+
+        let mut data = format!("{}\n", CACHE_VERSION);
+        for path in paths {
+            let entry = &self.current[path];
+            data.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n{}\n",
+                entry.size,
+                entry.modified,
+                entry.tokens,
+                entry.content.len(),
+                path.display(),
+                entry.content
+            ));
+        }
+        write_atomic(&self.path, data.as_bytes(), false)?;
+        Ok(())
+    }
+}
+
+/// Size and modification time identifying a version of a file
+fn stamp(metadata: &Metadata) -> Option<(u64, u128)> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos()))
+}
+
+/// Records of a cache file, or `None` if it is from another version or damaged
+fn parse(data: &str) -> Option<HashMap<PathBuf, CachedFile>> {
+    let mut rest = data.strip_prefix(CACHE_VERSION)?.strip_prefix('\n')?;
+    let mut entries = HashMap::new();
+    while !rest.is_empty() {
+        let (header, after) = rest.split_once('\n')?;
+        let mut fields = header.splitn(5, '\t');
+        let size = fields.next()?.parse().ok()?;
+        let modified = fields.next()?.parse().ok()?;
+        let tokens = fields.next()?.parse().ok()?;
+        let length: usize = fields.next()?.parse().ok()?;
+        let path = PathBuf::from(fields.next()?);
+
+        let content = after.get(..length)?;
+        rest = after[length..].strip_prefix('\n')?;
+        entries.insert(
+            path,
+            CachedFile {
+                size,
+                modified,
+                tokens,
+                content: content.to_string(),
+            },
+        );
+    }
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    /// Write `content` to `path`, dated well before the run so it can be cached
+    fn write_settled(path: &Path, content: &str) -> Metadata {
+        fs::write(path, content).unwrap();
+        let earlier = SystemTime::now() - Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(earlier)
+            .unwrap();
+        fs::metadata(path).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_and_invalidation() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("a.rs");
+        let metadata = write_settled(&file, "fn a() {}\n");
+
+        let mut cache = FileCache::open(temp_dir.path(), "key");
+        assert!(cache.lookup(&file, &metadata).is_none());
+        cache.insert(&file, &metadata, "fn a() {}\n\tb\n", 4);
+        cache.save().unwrap();
+
+        let mut cache = FileCache::open(temp_dir.path(), "key");
+        let entry = cache.lookup(&file, &metadata).unwrap();
+        assert_eq!(entry.content, "fn a() {}\n\tb\n");
+        assert_eq!(cache.tokens(&file, "fn a() {}\n\tb\n"), Some(4));
+        assert_eq!(cache.tokens(&file, "other"), None);
+
+        // Other options get a cache of their own
+        let mut other = FileCache::open(temp_dir.path(), "other-key");
+        assert!(other.lookup(&file, &metadata).is_none());
+
+        // A changed file misses
+        let changed = write_settled(&file, "fn b() {}\n\n");
+        assert!(cache.lookup(&file, &changed).is_none());
+    }
+
+    #[test]
+    fn test_recent_files_are_not_stored() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("a.rs");
+        fs::write(&file, "fn a() {}\n").unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+
+        let mut cache = FileCache::open(temp_dir.path(), "key");
+        cache.insert(&file, &metadata, "fn a() {}\n", 3);
+        assert!(cache.lookup(&file, &metadata).is_none());
+    }
+
+    #[test]
+    fn test_damaged_cache_is_ignored() {
+        assert!(parse("fuse-cache 0\n").is_none());
+        assert!(parse("fuse-cache 1\n3\t1\t1\t99\ta.rs\nabc\n").is_none());
+        assert_eq!(parse("fuse-cache 1\n").unwrap().len(), 0);
+    }
+}
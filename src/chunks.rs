@@ -0,0 +1,201 @@
+//! Overlapping windows for embedding pipelines (`--chunks`)
+//!
+//! Each file is split at line boundaries into windows of at most SIZE tokens,
+//! counted with the `--tokenizer`; each window after the first repeats up to N
+//! tokens of the previous one's last lines (`--chunk-overlap`). A line larger
+//! than SIZE, such as a minified bundle, is cut into pieces that fit, so
+//! consecutive windows can share a line number. Every window becomes one JSON
+//! line with its path, index and 1-based inclusive line range.
+
+use crate::file_processor::FileEntry;
+use crate::utils::{json_string, Tokenizer};
+
+/// One window of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub index: usize,
+    /// First line, 1-based
+    pub start_line: usize,
+    /// Last line, inclusive
+    pub end_line: usize,
+    pub content: String,
+}
+
+/// Longest a single token is assumed to be, bounding the search for where to cut a long line
+const MAX_TOKEN_BYTES: usize = 64;
+
+/// A line, or a piece of one too large for a window
+struct Piece<'a> {
+    /// Line the piece comes from, 0-based
+    line: usize,
+    text: &'a str,
+    tokens: usize,
+}
+
+/// Split `content` into windows of at most `size` tokens overlapping by up to `overlap`
+pub fn chunk_content(
+    content: &str,
+    size: usize,
+    overlap: usize,
+    tokenizer: Tokenizer,
+) -> Vec<Chunk> {
+    let pieces = pieces(content, size, tokenizer);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < pieces.len() {
+        let mut end = start;
+        let mut tokens = 0;
+        while end < pieces.len() && (end == start || tokens + pieces[end].tokens <= size) {
+            tokens += pieces[end].tokens;
+            end += 1;
+        }
+        chunks.push(Chunk {
+            index: chunks.len(),
+            start_line: pieces[start].line + 1,
+            end_line: pieces[end - 1].line + 1,
+            content: pieces[start..end].iter().map(|piece| piece.text).collect(),
+        });
+        if end == pieces.len() {
+            break;
+        }
+
+        // Step back over trailing pieces that fit the overlap, always moving forward
+        let mut next = end;
+        let mut repeated = 0;
+        while next > start + 1 && repeated + pieces[next - 1].tokens <= overlap {
+            repeated += pieces[next - 1].tokens;
+            next -= 1;
+        }
+        start = next;
+    }
+    chunks
+}
+
+/// The lines of `content`, with each line over `size` tokens cut into pieces that fit
+fn pieces(content: &str, size: usize, tokenizer: Tokenizer) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    for (line, text) in content.split_inclusive('\n').enumerate() {
+        let tokens = tokenizer.count(text);
+        if tokens <= size {
+            pieces.push(Piece { line, text, tokens });
+            continue;
+        }
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (text, after) = rest.split_at(fitting_prefix(rest, size, tokenizer));
+            pieces.push(Piece {
+                line,
+                text,
+                tokens: tokenizer.count(text),
+            });
+            rest = after;
+        }
+    }
+    pieces
+}
+
+/// Length of the longest prefix of `text` within `size` tokens, and at least one character
+fn fitting_prefix(text: &str, size: usize, tokenizer: Tokenizer) -> usize {
+    let limit = size.saturating_mul(MAX_TOKEN_BYTES);
+    let ends: Vec<usize> = text
+        .char_indices()
+        .map(|(i, c)| i + c.len_utf8())
+        .take_while(|&end| end <= limit)
+        .collect();
+    let fits = ends.partition_point(|&end| tokenizer.count(&text[..end]) <= size);
+    match fits {
+        0 => text.chars().next().map_or(0, char::len_utf8),
+        n => ends[n - 1],
+    }
+}
+
+/// One JSON record per chunk of every file
+pub fn render_jsonl(
+    files: &[FileEntry],
+    size: usize,
+    overlap: usize,
+    tokenizer: Tokenizer,
+) -> String {
+    let mut jsonl = String::new();
+    for file in files {
+        let path = json_string(&file.path.display().to_string());
+        for chunk in chunk_content(&file.content, size, overlap, tokenizer) {
+            jsonl.push_str(&format!(
+                "{{\"path\":{},\"chunk_index\":{},\"start_line\":{},\"end_line\":{},\"content\":{}}}\n",
+                path,
+                chunk.index,
+                chunk.start_line,
+                chunk.end_line,
+                json_string(&chunk.content)
+            ));
+        }
+    }
+    jsonl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Line ranges of the chunks, which are easier to compare than contents
+    fn ranges(content: &str, size: usize, overlap: usize) -> Vec<(usize, usize)> {
+        chunk_content(content, size, overlap, Tokenizer::Approx)
+            .iter()
+            .map(|chunk| (chunk.start_line, chunk.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_windows() {
+        // Eight bytes per line, so two tokens each with the byte estimate
+        let content = "line 01\nline 02\nline 03\nline 04\nline 05\n";
+        assert_eq!(ranges(content, 4, 0), vec![(1, 2), (3, 4), (5, 5)]);
+        assert_eq!(ranges(content, 6, 2), vec![(1, 3), (3, 5)]);
+        assert_eq!(ranges(content, 100, 50), vec![(1, 5)]);
+        // Oversized lines are cut, and the overlap never stalls progress
+        assert_eq!(
+            ranges(content, 1, 10),
+            vec![
+                (1, 1),
+                (1, 1),
+                (2, 2),
+                (2, 2),
+                (3, 3),
+                (3, 3),
+                (4, 4),
+                (4, 4),
+                (5, 5),
+                (5, 5)
+            ]
+        );
+        assert!(ranges("", 10, 0).is_empty());
+
+        let chunks = chunk_content(content, 6, 2, Tokenizer::Approx);
+        assert_eq!(chunks[1].index, 1);
+        assert_eq!(chunks[1].content, "line 03\nline 04\nline 05\n");
+    }
+
+    #[test]
+    fn test_long_line_is_split() {
+        let minified = format!("{}\nshort\n", "x".repeat(100));
+        let chunks = chunk_content(&minified, 10, 0, Tokenizer::Approx);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks
+            .iter()
+            .all(|chunk| Tokenizer::Approx.count(&chunk.content) <= 10));
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 1));
+        assert_eq!((chunks[2].start_line, chunks[2].end_line), (1, 2));
+        let joined: String = chunks.iter().map(|chunk| chunk.content.as_str()).collect();
+        assert_eq!(joined, minified);
+    }
+
+    #[test]
+    fn test_render_jsonl() {
+        let files = vec![FileEntry::new("src/a.rs".into(), "fn a() {}\n".to_string())];
+        assert_eq!(
+            render_jsonl(&files, 100, 0, Tokenizer::Approx),
+            "{\"path\":\"src/a.rs\",\"chunk_index\":0,\"start_line\":1,\"end_line\":1,\"content\":\"fn a() {}\\n\"}\n"
+        );
+    }
+}
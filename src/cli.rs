@@ -1,13 +1,42 @@
 //! Command-line interface implementation using clap
 
-use clap::Parser;
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::output::{DefaultFormatter, MarkdownFormatter, XmlFormatter};
-use crate::utils::read_paths_from_stdin;
-use crate::{FileProcessor, Result};
+use crate::cache::FileCache;
+use crate::chunks;
+use crate::config;
+use crate::extensions::KNOWN_FILE_NAMES;
+use crate::file_processor::{BinaryMode, Decisions, FilterReason, FilterReport, HEX_DUMP_BYTES};
+use crate::git::{
+    current_branch, diff_hunks, files_last_committed, parse_remote, range_files, range_summary,
+    recent_commits, resolve_commit, staged_files, tracked_files, untracked_files, RemoteCheckout,
+};
+use crate::ignore::{CustomIgnore, Preset, TestFilter, OUTPUT_PLACEHOLDERS};
+use crate::integrity;
+use crate::llms_txt;
+use crate::models::Model;
+use crate::ordering::{Churn, Recency};
+use crate::output::{
+    DefaultFormatter, ManifestFormat, ManifestFormatter, MarkdownFormatter, OutputFormatter,
+    RawFormatter, XmlFormatter,
+};
+use crate::sample::SampleRows;
+use crate::schema;
+use crate::stats::{TextCounts, TokenBreakdown};
+use crate::transform::{
+    CollapseImports, Compact, Dedent, DiffHunks, FileLimit, HeadTail, MinifyMarkup, Normalize,
+    Redact, RegexReplace, SqueezeBlank, StripDocstrings, StripFrontMatter, StripLicenseHeader,
+    StripTrailingWhitespace, WrapLines,
+};
+use crate::usage::{self, HISTORY_ENV_VAR};
+use crate::utils::{
+    json_string, read_paths_from_stdin, sha256_hex, write_atomic, write_atomic_with, TimeZone,
+    Tokenizer,
+};
+use crate::{FileEntry, FileProcessor, Result};
 
 // ============================================================================
 // Shared documentation pieces (single source of truth)
@@ -18,35 +47,162 @@ const DESCRIPTION: &str = "Turn many files -> single file, useful for LLM prompt
 const USAGE: &str = "\
 Usage:
   fuse [path/to/file_or_directory] [options]
-  fuse [file1] [file2] [folder1] [folder2] [options]";
+  fuse [file1] [file2] [folder1] [folder2] [options]
+  fuse https://github.com/org/repo[@ref] [options]   # Shallow clone, fuse, then clean up
+  fuse usage                 # Local usage report (record runs by setting FUSE_HISTORY=<file>)
+  fuse schema <NAME>         # JSON Schema of --manifest rows, --trace or --chunks records (manifest, trace, chunk)";
 
 const EXAMPLES: &str = r#"Here's a few samples to get started:
   fuse src/                                      # All files in src/
   fuse src/ test/ -e ts                          # Only .ts files in src/ and test
   fuse src/ --toc-files --ignore "__tests__"     # Files in src/ except __tests__, with toc tree
   fuse . --ignore "*.log" --ignore "test_*"      # Skip logs and files that start with "test_"
-  fuse . -o output.txt                           # Save to file instead of printing or use >"#;
+  fuse . -o output.txt                           # Save to file instead of printing or use >
+  fuse src/ -o ctx.xml --cxml -o ctx.md -m       # Two formats from a single pass
+  fuse @core @web -e ts                          # Path aliases defined in .fuserc
+  fuse 'src/**.rs' docs/...                      # Recursive sugar: .rs files under src/, all of docs/"#;
 
 const OPTIONS_HELP: &str = "\
 OPTIONS
 Input Control:
-  -e, --extension <EXT>     Only include these extensions (e.g. -e py -e js)
+  -e, --extension <EXT>     Only include these extensions (e.g. -e py -e js -e d.ts)
+      --known-name <NAME>   With -e, also include files with this exact name (on top of Makefile, Dockerfile,
+                            LICENSE, ...)
+      --no-known-names      With -e, don't keep Makefile, Dockerfile, LICENSE and other well-known extensionless names
+      --sniff               With -e, also include extensionless files whose shebang or <?xml header matches
       --include-hidden      Include hidden files (starting with .)
       --ignore-files-only   Make --ignore patterns skip files only, not directories
       --ignore-gitignore    Don't use .gitignore rules
-      --ignore <PATTERN>    Skip files matching pattern (*.log, test_*, *foo*, __pycache__)
+      --ignore <PATTERN>    Skip files matching pattern (*.log, test_*, __pycache__; with a / it matches from the root: src/**/fixtures/*)
+      --ignore-from <FILE>  Read --ignore patterns from FILE, one per line (# comments, !negation)
+      --preset <NAME>       Skip the usual dependency, build and cache paths of an ecosystem (node, python, rust, java)
+      --include <PATTERN>   Only include files matching pattern (src/**/*.rs, /Cargo.toml, *.proto)
+      --force-include <PATTERN>
+                            Include files matching pattern even if gitignored, hidden or otherwise skipped
+                            (.env.example, gen/schema.json)
+      --contains <REGEX>    Only include files whose content matches REGEX (repeat: must match all)
+      --not-contains <REGEX>
+                            Skip files whose content matches REGEX
+      --binary <MODE>       What to do with binary files: skip, warn (default), placeholder, hex or error
+      --hex-bytes <N>       With --binary hex, dump the first N bytes of each binary file (default 4096)
+      --no-tests            Skip tests: tests/, __tests__/, *_test.go, test_*.py, *.spec.ts and similar
+      --tests-only          Only include tests (same conventions as --no-tests)
+      --transcode           Decode Latin-1, UTF-16 and other non-UTF-8 text instead of skipping it as binary
+      --skip-empty          Skip files that are empty or contain only whitespace
+      --filter-cmd <CMD>    Keep only files a shell command approves: with {} it runs per file and
+                            exit 0 keeps it, else it reads paths on stdin and prints the ones to keep
+      --max-depth <N>       Only descend N directory levels below each input directory (1 = direct children)
+      --no-default-ignores  Don't skip .git/, node_modules/, target/, dist/, .DS_Store and similar junk
+      --include-lockfiles   Keep lockfiles (Cargo.lock, package-lock.json, poetry.lock, ...), skipped by default
+      --include-vendored    Descend into vendor/ and third_party/ trees, skipped by default
+      --include-submodules  Descend into git submodules (skipped by default)
+      --include-generated   Keep generated files: linguist-generated or export-ignore in .gitattributes,
+                            minified (.min.js, very long lines) or with a DO NOT EDIT header
+      --allow-sensitive     Emit .env, private keys (id_rsa, *.pem) and credentials.json, skipped with a
+                            warning by default
+      --git-tracked         Only include files tracked by git (git ls-files) instead of walking the filesystem
+      --git-untracked       Only include files git doesn't track yet (new files not covered by .gitignore)
+      --staged              Only include files staged in the git index (defaults to the current directory)
+      --pr-range <RANGE>    Only include files touched in a git range (e.g. origin/main...HEAD)
+      --pr-summary          With --pr-range, end with the range's commits and diffstat
+      --git-since <DATE>    Only include files whose last commit is on or after DATE (e.g. 2024-01-01)
+      --git-until <DATE>    Only include files whose last commit is on or before DATE
+      --diff-only <REF>     Only include files changed since the git REF, showing their diff hunks
+
+Content Transforms:
+      --signatures          Code map: only declarations (functions, types, public methods, doc
+                            comments) with bodies elided, for Rust, Python, JS/TS, Go and Java
+      --normalize           Convert CRLF and CR line endings to LF
+      --tab-width <N>       With --normalize, also expand tabs to N-column tab stops
+      --redact              Replace secrets (AWS keys, GitHub tokens, private keys, JWTs) with
+                            [REDACTED:type], reporting counts to stderr
+      --replace <EXPR>      Rewrite content with a sed-style regex (e.g. 's/old/new/g')
+      --replace-from <FILE> Read --replace expressions from a file, one per line
+      --strip-license-headers  Drop the copyright/license comment banner at the top of each file
+      --transform-cmd <CMD> Pipe each file's content through a shell command (path in $FUSE_PATH),
+                            before the other transforms
+      --strip-frontmatter   Drop YAML/TOML front matter from Markdown (.md, .mdx) files
+      --strip-docstrings    Remove Python docstrings, /** */ doc blocks and /// doc comments, keeping code
+                            and ordinary comments
+      --collapse-imports    Replace each file's import/use/require block with one line (// 37 imports collapsed)
+      --strip-ansi          Remove terminal color/escape codes (e.g. from captured logs)
+      --pretty-json         Re-indent minified .json files, one member or element per line
+      --squeeze-blank       Collapse runs of blank lines to one
+      --strip-trailing-ws   Strip trailing spaces and tabs from every line
+      --dedent              Strip the indentation shared by all lines of a file and rescale 4- or
+                            8-space indents to 2 (Markdown and other prose left alone)
+      --compact             Save tokens: squeeze blank lines, strip trailing whitespace,
+                            dedent, drop lockfile comments
+      --minify-markup       Collapse insignificant whitespace in .html, .xml and .svg files
+                            (<pre>, <script>, <style> and CDATA kept as is)
+      --sample-rows <N>     Data files: the header and first N rows of CSV/TSV, the schema and
+                            first N rows of Parquet
+      --head <N>            Only the first N lines of each file, with a [... N more lines ...] marker
+      --tail <M>            Only the last M lines of each file; with --head, keep both ends
+      --wrap-cols <N>       Break lines longer than N characters, continuing them on lines starting with ↪
+      --max-lines-per-file <N>   Truncate files longer than N lines, with a marker
+      --max-tokens-per-file <N>  Truncate files larger than N tokens, with a marker
 
 Output Format:
+      --target <MODEL>     Use the recommended structure for claude, gpt, gemini or deepseek
   -c, --cxml               Output in Claude XML format
   -m, --markdown           Output as Markdown code blocks
+      --raw                File contents only, no path headers
+      --raw-separator <LINE>  Line placed between files in --raw mode
+      --llms-txt <DIR>     Write llms.txt (index) and llms-full.txt (contents) into DIR
+      --output-sqlite <DB> Write files into a SQLite `files` table instead of text
+      --chunks <SIZE>      Write JSONL for embedding: each file split at line boundaries into windows of
+                           up to SIZE tokens, with path, chunk_index, start_line and end_line
+      --chunk-overlap <N>  With --chunks, repeat up to N tokens of each window's last lines in the next
+      --manifest <FORMAT>  Output a csv/tsv inventory (path, size, lines, language, sha256) without contents
   -n, --line-numbers       Add line numbers
-  -o, --output <FILE>      Save to file instead of printing
+      --system-file <FILE> Put the contents of FILE at the very top (e.g. a system prompt)
+      --prefix <TEXT>      Text placed before the bundle
+      --suffix <TEXT>      Text placed after the bundle
+      --dir-preambles      Emit a directory's PROMPT.md/CONTEXT.md first as a context block
+      --collections        Wrap each input path's files in a named collection (e.g. <collection name=\"backend\">)
+      --dedupe             Emit files with identical content once; later copies become an
+                           \"[identical to <path>]\" stub
+      --peek               Skim mode: only the first 30 lines of each file, with a truncation note
+      --max-tokens <N>     Stop including files once the output reaches N tokens, truncating the last one
+      --budget-plan        With --max-tokens, list which files would be included, truncated or
+                           dropped instead of printing the bundle
+      --priority <GLOB:LEVEL>  Budget priority for --max-tokens: low files are dropped first,
+                           high files are never truncated (e.g. 'src/**:high' 'tests/**:low')
+      --model <NAME>       Warn when the output exceeds the model's context window (gpt-4o, gpt-4o-mini,
+                           gpt-4.1, gpt-4-turbo, o3, claude-opus, claude-sonnet, claude-haiku,
+                           gemini-pro, gemini-flash, llama3, deepseek)
+      --strict             Fail instead of warning when a sensitive file is skipped or, with --model,
+                           when the output does not fit
+      --tokens             Print the output's token count (o200k_base tokenizer) to stderr;
+                           with --toc, also annotate each tree entry with its token count
+      --tokenizer <NAME>   Tokenizer for --tokens, --max-tokens and --stats (cl100k, o200k, llama3, approx)
+      --front-matter       Put a YAML front-matter block (path, language, size, sha256) before each markdown file
+      --exact              Keep file bytes exactly (no trailing-newline folding) and add encoding columns to --manifest
+      --readme-first       Within each directory, emit README/docs before code files
+      --sort <KEY>         Reorder files (git-churn: most commits in the last 90 days first;
+                           git-recency: most recently committed first)
+      --header             Start with a header: fuse version, timestamp, command line, file and token counts
+      --local-time         Write --header and {date} timestamps in local time instead of UTC
+      --with-git-log <N>   End with the last N commits (short hash and subject) touching the input paths
+      --integrity          End with a footer (file count, bytes, SHA-256 of the content) to detect truncation
+      --reproducible       Omit timestamps and absolute paths from --header for byte-identical reruns
+  -o, --output <FILE>      Save to file instead of printing (repeat with per-output format flags;
+                           {date}, {branch}, {n} and {tokens} placeholders are expanded)
+  -p, --clipboard          Copy the output to the system clipboard instead of printing
+      --tee                Also print to stdout when writing with -o or -p
+      --backup             Keep the previous -o file as FILE.bak before replacing it
       --toc                Include table of contents tree (auto: files+dirs if <100 lines, dirs only if ≥100)
       --toc-dirs-only      Table of contents shows directories only
       --toc-files          Table of contents shows files and directories
 
 Other:
   -0, --null               Read null-separated paths from stdin
+  -v, --verbose            List every skipped file instead of summarizing them
+      --trace <FILE>       Write a JSONL trace of every visited path and the filter decision to FILE
+      --cache <DIR>        Reuse files unchanged since an earlier run with the same options, cached in DIR
+      --stats <KIND>       Print a report to stderr (tokens: per-file and per-directory counts, largest first;
+                           counts: files, lines, words, chars and bytes emitted)
   -h, --help               Print help
   -V, --version            Print version";
 
@@ -76,10 +232,30 @@ pub struct Cli {
     pub help: bool,
 
     // Input Control
-    /// Only include these extensions (e.g. -e py -e js)
+    /// Only include these extensions (e.g. -e py -e js -e d.ts)
     #[arg(short = 'e', long = "extension", action = clap::ArgAction::Append, value_name = "EXT", help_heading = "Input Control")]
     pub extensions: Vec<String>,
 
+    /// With -e, also include files with this exact name (on top of Makefile, Dockerfile, LICENSE, ...)
+    #[arg(long = "known-name", action = clap::ArgAction::Append, value_name = "NAME", requires = "extensions", help_heading = "Input Control")]
+    pub known_names: Vec<String>,
+
+    /// With -e, don't keep Makefile, Dockerfile, LICENSE and other well-known extensionless names
+    #[arg(
+        long = "no-known-names",
+        requires = "extensions",
+        help_heading = "Input Control"
+    )]
+    pub no_known_names: bool,
+
+    /// With -e, also include extensionless files whose shebang or <?xml header matches
+    #[arg(
+        long = "sniff",
+        requires = "extensions",
+        help_heading = "Input Control"
+    )]
+    pub sniff: bool,
+
     /// Include hidden files (starting with .)
     #[arg(long = "include-hidden", help_heading = "Input Control")]
     pub include_hidden: bool,
@@ -92,11 +268,362 @@ pub struct Cli {
     #[arg(long = "ignore-gitignore", help_heading = "Input Control")]
     pub ignore_gitignore: bool,
 
-    /// Skip files matching pattern (*.log, test_*, *foo*, __pycache__)
+    /// Skip files matching pattern (*.log, test_*, __pycache__; with a / it matches from the root: src/**/fixtures/*)
     #[arg(long = "ignore", action = clap::ArgAction::Append, value_name = "PATTERN", help_heading = "Input Control")]
     pub ignore_patterns: Vec<String>,
 
+    /// Skip the usual dependency, build and cache paths of an ecosystem (node, python, rust, java)
+    #[arg(long = "preset", action = clap::ArgAction::Append, value_name = "NAME", help_heading = "Input Control")]
+    pub presets: Vec<Preset>,
+
+    /// Read --ignore patterns from FILE, one per line (# comments, !negation)
+    #[arg(
+        long = "ignore-from",
+        value_name = "FILE",
+        help_heading = "Input Control"
+    )]
+    pub ignore_from: Option<PathBuf>,
+
+    /// Only include files matching pattern (src/**/*.rs, /Cargo.toml, *.proto)
+    #[arg(long = "include", action = clap::ArgAction::Append, value_name = "PATTERN", help_heading = "Input Control")]
+    pub include_patterns: Vec<String>,
+
+    /// Include files matching pattern even if gitignored, hidden or otherwise skipped (.env.example, gen/schema.json)
+    #[arg(long = "force-include", action = clap::ArgAction::Append, value_name = "PATTERN", help_heading = "Input Control")]
+    pub force_include: Vec<String>,
+
+    /// Only include files whose content matches REGEX (repeat: must match all)
+    #[arg(long = "contains", action = clap::ArgAction::Append, value_name = "REGEX", help_heading = "Input Control")]
+    pub contains: Vec<String>,
+
+    /// Skip files whose content matches REGEX
+    #[arg(long = "not-contains", action = clap::ArgAction::Append, value_name = "REGEX", help_heading = "Input Control")]
+    pub not_contains: Vec<String>,
+
+    /// What to do with binary files: skip, warn (default), placeholder, hex or error
+    #[arg(
+        long = "binary",
+        value_name = "MODE",
+        default_value = "warn",
+        help_heading = "Input Control"
+    )]
+    pub binary: BinaryMode,
+
+    /// With --binary hex, dump the first N bytes of each binary file (default 4096)
+    #[arg(long = "hex-bytes", value_name = "N", help_heading = "Input Control")]
+    pub hex_bytes: Option<usize>,
+
+    /// Skip tests: tests/, __tests__/, *_test.go, test_*.py, *.spec.ts and similar
+    #[arg(
+        long = "no-tests",
+        conflicts_with = "tests_only",
+        help_heading = "Input Control"
+    )]
+    pub no_tests: bool,
+
+    /// Only include tests (same conventions as --no-tests)
+    #[arg(long = "tests-only", help_heading = "Input Control")]
+    pub tests_only: bool,
+
+    /// Decode Latin-1, UTF-16 and other non-UTF-8 text instead of skipping it as binary
+    #[arg(long = "transcode", help_heading = "Input Control")]
+    pub transcode: bool,
+
+    /// Skip files that are empty or contain only whitespace
+    #[arg(long = "skip-empty", help_heading = "Input Control")]
+    pub skip_empty: bool,
+
+    /// Keep only files a shell command approves: with {} it runs per file and exit 0 keeps it,
+    /// else it reads paths on stdin and prints the ones to keep
+    #[arg(
+        long = "filter-cmd",
+        value_name = "CMD",
+        help_heading = "Input Control"
+    )]
+    pub filter_cmd: Option<String>,
+
+    /// Pipe each file's content through a shell command (path in $FUSE_PATH), before the other transforms
+    #[arg(
+        long = "transform-cmd",
+        value_name = "CMD",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub transform_cmd: Option<String>,
+
+    /// Only descend N directory levels below each input directory (1 = direct children)
+    #[arg(long = "max-depth", value_name = "N", help_heading = "Input Control")]
+    pub max_depth: Option<usize>,
+
+    /// Don't skip .git/, node_modules/, target/, dist/, .DS_Store and similar junk
+    #[arg(long = "no-default-ignores", help_heading = "Input Control")]
+    pub no_default_ignores: bool,
+
+    /// Keep lockfiles (Cargo.lock, package-lock.json, poetry.lock, ...), skipped by default
+    #[arg(long = "include-lockfiles", help_heading = "Input Control")]
+    pub include_lockfiles: bool,
+
+    /// Descend into vendor/ and third_party/ trees, skipped by default
+    #[arg(long = "include-vendored", help_heading = "Input Control")]
+    pub include_vendored: bool,
+
+    /// Descend into git submodules (skipped by default)
+    #[arg(long = "include-submodules", help_heading = "Input Control")]
+    pub include_submodules: bool,
+
+    /// Keep generated files: linguist-generated or export-ignore in .gitattributes, minified (.min.js, very long lines) or with a DO NOT EDIT header
+    #[arg(long = "include-generated", help_heading = "Input Control")]
+    pub include_generated: bool,
+
+    /// Emit .env, private keys (id_rsa, *.pem) and credentials.json, skipped with a warning by default
+    #[arg(long = "allow-sensitive", help_heading = "Input Control")]
+    pub allow_sensitive: bool,
+
+    /// Only include files tracked by git (git ls-files) instead of walking the filesystem
+    #[arg(long = "git-tracked", help_heading = "Input Control")]
+    pub git_tracked: bool,
+
+    /// Only include files git doesn't track yet (new files not covered by .gitignore)
+    #[arg(
+        long = "git-untracked",
+        conflicts_with = "git_tracked",
+        help_heading = "Input Control"
+    )]
+    pub git_untracked: bool,
+
+    /// Only include files staged in the git index (defaults to the current directory)
+    #[arg(long = "staged", help_heading = "Input Control")]
+    pub staged: bool,
+
+    /// Only include files touched in a git range (e.g. origin/main...HEAD)
+    #[arg(
+        long = "pr-range",
+        value_name = "RANGE",
+        help_heading = "Input Control"
+    )]
+    pub pr_range: Option<String>,
+
+    /// With --pr-range, end with the range's commits and diffstat
+    #[arg(
+        long = "pr-summary",
+        requires = "pr_range",
+        help_heading = "Input Control"
+    )]
+    pub pr_summary: bool,
+
+    /// Only include files whose last commit is on or after DATE (e.g. 2024-01-01)
+    #[arg(
+        long = "git-since",
+        value_name = "DATE",
+        help_heading = "Input Control"
+    )]
+    pub git_since: Option<String>,
+
+    /// Only include files whose last commit is on or before DATE
+    #[arg(
+        long = "git-until",
+        value_name = "DATE",
+        help_heading = "Input Control"
+    )]
+    pub git_until: Option<String>,
+
+    /// Only include files changed since the git REF, showing their diff hunks
+    #[arg(
+        long = "diff-only",
+        value_name = "REF",
+        conflicts_with = "exact",
+        help_heading = "Input Control"
+    )]
+    pub diff_only: Option<String>,
+
+    // Content Transforms
+    /// Code map: only declarations (functions, types, public methods, doc comments) with bodies elided, for Rust, Python, JS/TS, Go and Java
+    #[arg(
+        long = "signatures",
+        conflicts_with_all = ["exact", "diff_only"],
+        help_heading = "Content Transforms"
+    )]
+    pub signatures: bool,
+
+    /// Convert CRLF and CR line endings to LF
+    #[arg(
+        long = "normalize",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub normalize: bool,
+
+    /// With --normalize, also expand tabs to N-column tab stops
+    #[arg(
+        long = "tab-width",
+        value_name = "N",
+        requires = "normalize",
+        help_heading = "Content Transforms"
+    )]
+    pub tab_width: Option<usize>,
+
+    /// Replace secrets (AWS keys, GitHub tokens, private keys, JWTs) with [REDACTED:type], reporting counts to stderr
+    #[arg(long = "redact", help_heading = "Content Transforms")]
+    pub redact: bool,
+
+    /// Rewrite content with a sed-style regex (e.g. 's/old/new/g')
+    #[arg(long = "replace", action = clap::ArgAction::Append, value_name = "EXPR", help_heading = "Content Transforms")]
+    pub replace: Vec<String>,
+
+    /// Read --replace expressions from a file, one per line
+    #[arg(
+        long = "replace-from",
+        value_name = "FILE",
+        help_heading = "Content Transforms"
+    )]
+    pub replace_from: Option<PathBuf>,
+
+    /// Drop the copyright/license comment banner at the top of each file
+    #[arg(
+        long = "strip-license-headers",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub strip_license_headers: bool,
+
+    /// Drop YAML/TOML front matter from Markdown (.md, .mdx) files
+    #[arg(
+        long = "strip-frontmatter",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub strip_frontmatter: bool,
+
+    /// Remove Python docstrings, /** */ doc blocks and /// doc comments, keeping code and ordinary comments
+    #[arg(
+        long = "strip-docstrings",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub strip_docstrings: bool,
+
+    /// Replace each file's import/use/require block with one line (// 37 imports collapsed)
+    #[arg(
+        long = "collapse-imports",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub collapse_imports: bool,
+
+    /// Remove terminal color/escape codes (e.g. from captured logs)
+    #[arg(
+        long = "strip-ansi",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub strip_ansi: bool,
+
+    /// Re-indent minified .json files, one member or element per line
+    #[arg(
+        long = "pretty-json",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub pretty_json: bool,
+
+    /// Collapse runs of blank lines to one
+    #[arg(
+        long = "squeeze-blank",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub squeeze_blank: bool,
+
+    /// Strip trailing spaces and tabs from every line
+    #[arg(
+        long = "strip-trailing-ws",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub strip_trailing_ws: bool,
+
+    /// Strip the indentation shared by all lines of a file and rescale 4- or 8-space indents to 2 (Markdown and other prose left alone)
+    #[arg(
+        long = "dedent",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub dedent: bool,
+
+    /// Save tokens: squeeze blank lines, strip trailing whitespace, dedent, drop lockfile comments
+    #[arg(
+        long = "compact",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub compact: bool,
+
+    /// Collapse insignificant whitespace in .html, .xml and .svg files (<pre>, <script>, <style> and CDATA kept as is)
+    #[arg(
+        long = "minify-markup",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub minify_markup: bool,
+
+    /// Data files: the header and first N rows of CSV/TSV, the schema and first N rows of Parquet
+    #[arg(
+        long = "sample-rows",
+        value_name = "N",
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub sample_rows: Option<usize>,
+
+    /// Only the first N lines of each file, with a [... N more lines ...] marker
+    #[arg(
+        long = "head",
+        value_name = "N",
+        conflicts_with = "peek",
+        help_heading = "Content Transforms"
+    )]
+    pub head: Option<usize>,
+
+    /// Only the last M lines of each file; with --head, keep both ends
+    #[arg(
+        long = "tail",
+        value_name = "M",
+        conflicts_with = "peek",
+        help_heading = "Content Transforms"
+    )]
+    pub tail: Option<usize>,
+
+    /// Break lines longer than N characters, continuing them on lines starting with ↪
+    #[arg(
+        long = "wrap-cols",
+        value_name = "N",
+        value_parser = parse_positive,
+        conflicts_with = "exact",
+        help_heading = "Content Transforms"
+    )]
+    pub wrap_cols: Option<usize>,
+
+    /// Truncate files longer than N lines, with a marker
+    #[arg(
+        long = "max-lines-per-file",
+        value_name = "N",
+        help_heading = "Content Transforms"
+    )]
+    pub max_lines_per_file: Option<usize>,
+
+    /// Truncate files larger than N tokens, with a marker
+    #[arg(
+        long = "max-tokens-per-file",
+        value_name = "N",
+        help_heading = "Content Transforms"
+    )]
+    pub max_tokens_per_file: Option<usize>,
+
     // Output Format
+    /// Use the recommended structure for claude, gpt, gemini or deepseek
+    #[arg(long = "target", value_name = "MODEL", help_heading = "Output Format")]
+    pub target: Option<Target>,
+
     /// Output in Claude XML format
     #[arg(short = 'c', long = "cxml", help_heading = "Output Format")]
     pub claude_xml: bool,
@@ -105,18 +632,201 @@ pub struct Cli {
     #[arg(short = 'm', long = "markdown", help_heading = "Output Format")]
     pub markdown: bool,
 
+    /// File contents only, no path headers
+    #[arg(long = "raw", help_heading = "Output Format")]
+    pub raw: bool,
+
+    /// Line placed between files in --raw mode
+    #[arg(
+        long = "raw-separator",
+        value_name = "LINE",
+        requires = "raw",
+        help_heading = "Output Format"
+    )]
+    pub raw_separator: Option<String>,
+
+    /// Write llms.txt (index) and llms-full.txt (contents) into DIR
+    #[arg(long = "llms-txt", value_name = "DIR", help_heading = "Output Format")]
+    pub llms_txt: Option<PathBuf>,
+
+    /// Write JSONL for embedding: each file split at line boundaries into windows of up to SIZE tokens, with path, chunk_index, start_line and end_line
+    #[arg(
+        long = "chunks",
+        value_name = "SIZE",
+        value_parser = parse_positive,
+        conflicts_with_all = ["llms_txt", "output_sqlite", "max_tokens"],
+        help_heading = "Output Format"
+    )]
+    pub chunks: Option<usize>,
+
+    /// With --chunks, repeat up to N tokens of each window's last lines in the next
+    #[arg(
+        long = "chunk-overlap",
+        value_name = "N",
+        requires = "chunks",
+        help_heading = "Output Format"
+    )]
+    pub chunk_overlap: Option<usize>,
+
+    /// Write files into a SQLite `files` table instead of text
+    #[arg(
+        long = "output-sqlite",
+        value_name = "DB",
+        help_heading = "Output Format"
+    )]
+    pub output_sqlite: Option<PathBuf>,
+
+    /// Output a csv/tsv inventory (path, size, lines, language, sha256) without contents
+    #[arg(
+        long = "manifest",
+        value_name = "FORMAT",
+        help_heading = "Output Format"
+    )]
+    pub manifest: Option<ManifestFormat>,
+
     /// Add line numbers
     #[arg(short = 'n', long = "line-numbers", help_heading = "Output Format")]
     pub line_numbers: bool,
 
-    /// Save to file instead of printing
+    /// Put the contents of FILE at the very top (e.g. a system prompt)
+    #[arg(
+        long = "system-file",
+        value_name = "FILE",
+        help_heading = "Output Format"
+    )]
+    pub system_file: Option<PathBuf>,
+
+    /// Text placed before the bundle
+    #[arg(long = "prefix", value_name = "TEXT", help_heading = "Output Format")]
+    pub prefix: Option<String>,
+
+    /// Text placed after the bundle
+    #[arg(long = "suffix", value_name = "TEXT", help_heading = "Output Format")]
+    pub suffix: Option<String>,
+
+    /// Emit a directory's PROMPT.md/CONTEXT.md first as a context block
+    #[arg(long = "dir-preambles", help_heading = "Output Format")]
+    pub dir_preambles: bool,
+
+    /// Wrap each input path's files in a named collection (e.g. <collection name="backend">)
+    #[arg(long = "collections", help_heading = "Output Format")]
+    pub collections: bool,
+
+    /// Emit files with identical content once; later copies become an "[identical to <path>]" stub
+    #[arg(long = "dedupe", help_heading = "Output Format")]
+    pub dedupe: bool,
+
+    /// Skim mode: only the first 30 lines of each file, with a truncation note
+    #[arg(long = "peek", help_heading = "Output Format")]
+    pub peek: bool,
+
+    /// Stop including files once the output reaches N tokens, truncating the last one
+    #[arg(long = "max-tokens", value_name = "N", help_heading = "Output Format")]
+    pub max_tokens: Option<usize>,
+
+    /// With --max-tokens, list which files would be included, truncated or dropped instead of printing the bundle
+    #[arg(
+        long = "budget-plan",
+        requires = "max_tokens",
+        help_heading = "Output Format"
+    )]
+    pub budget_plan: bool,
+
+    /// Budget priority for --max-tokens: low files are dropped first, high files are never truncated
+    #[arg(
+        long = "priority",
+        action = clap::ArgAction::Append,
+        value_name = "GLOB:LEVEL",
+        requires = "max_tokens",
+        help_heading = "Output Format"
+    )]
+    pub priorities: Vec<String>,
+
+    /// Warn when the output exceeds the model's context window
+    #[arg(long = "model", value_name = "NAME", help_heading = "Output Format")]
+    pub model: Option<Model>,
+
+    /// Fail instead of warning when a sensitive file is skipped or, with --model, when the output does not fit
+    #[arg(long = "strict", help_heading = "Output Format")]
+    pub strict: bool,
+
+    /// Print the output's token count (o200k_base tokenizer) to stderr; with --toc, also annotate each tree entry
+    #[arg(long = "tokens", help_heading = "Output Format")]
+    pub tokens: bool,
+
+    /// Tokenizer for --tokens, --max-tokens and --stats (cl100k, o200k, llama3, approx)
+    #[arg(
+        long = "tokenizer",
+        value_name = "NAME",
+        help_heading = "Output Format"
+    )]
+    pub tokenizer: Option<Tokenizer>,
+
+    /// Put a YAML front-matter block (path, language, size, sha256) before each markdown file
+    #[arg(long = "front-matter", help_heading = "Output Format")]
+    pub front_matter: bool,
+
+    /// Keep file bytes exactly (no trailing-newline folding) and add encoding columns to --manifest
+    #[arg(
+        long = "exact",
+        conflicts_with = "line_numbers",
+        help_heading = "Output Format"
+    )]
+    pub exact: bool,
+
+    /// Within each directory, emit README/docs before code files
+    #[arg(long = "readme-first", help_heading = "Output Format")]
+    pub readme_first: bool,
+
+    /// Reorder files (git-churn: most commits in the last 90 days first; git-recency: most recently committed first)
+    #[arg(long = "sort", value_name = "KEY", help_heading = "Output Format")]
+    pub sort: Option<SortKey>,
+
+    /// Start with a header: fuse version, timestamp, command line, file and token counts
+    #[arg(long = "header", help_heading = "Output Format")]
+    pub header: bool,
+
+    /// Write --header and {date} timestamps in local time instead of UTC
+    #[arg(long = "local-time", help_heading = "Output Format")]
+    pub local_time: bool,
+
+    /// End with the last N commits (short hash and subject) touching the input paths
+    #[arg(
+        long = "with-git-log",
+        value_name = "N",
+        help_heading = "Output Format"
+    )]
+    pub with_git_log: Option<usize>,
+
+    /// End with a footer (file count, bytes, SHA-256 of the content) to detect truncation
+    #[arg(long = "integrity", help_heading = "Output Format")]
+    pub integrity: bool,
+
+    /// Omit timestamps and absolute paths from --header for byte-identical reruns
+    #[arg(long = "reproducible", help_heading = "Output Format")]
+    pub reproducible: bool,
+
+    /// Save to file instead of printing (repeat with per-output format flags)
     #[arg(
         short = 'o',
         long = "output",
+        action = clap::ArgAction::Append,
         value_name = "FILE",
         help_heading = "Output Format"
     )]
-    pub output_file: Option<PathBuf>,
+    pub output_files: Vec<PathBuf>,
+
+    /// Copy the output to the system clipboard instead of printing
+    #[arg(short = 'p', long = "clipboard", help_heading = "Output Format")]
+    pub clipboard: bool,
+
+    /// Also print to stdout when writing with -o or -p
+    #[arg(long = "tee", help_heading = "Output Format")]
+    pub tee: bool,
+
+    /// Keep the previous -o file as FILE.bak before replacing it
+    #[arg(long = "backup", help_heading = "Output Format")]
+    pub backup: bool,
 
     /// Include table of contents tree (auto: files+dirs if <100 lines, dirs only if ≥100)
     #[arg(long = "toc", help_heading = "Output Format")]
@@ -131,6 +841,22 @@ pub struct Cli {
     pub toc_files: bool,
 
     // Other
+    /// List every skipped file instead of summarizing them
+    #[arg(short = 'v', long = "verbose", help_heading = "Other")]
+    pub verbose: bool,
+
+    /// Write a JSONL trace of every visited path and the filter decision to FILE
+    #[arg(long = "trace", value_name = "FILE", help_heading = "Other")]
+    pub trace: Option<PathBuf>,
+
+    /// Reuse files unchanged since an earlier run with the same options, cached in DIR
+    #[arg(long = "cache", value_name = "DIR", help_heading = "Other")]
+    pub cache: Option<PathBuf>,
+
+    /// Print a report to stderr (tokens: per-file and per-directory counts, largest first; counts: files, lines, words, chars and bytes emitted)
+    #[arg(long = "stats", value_name = "KIND", help_heading = "Other")]
+    pub stats: Option<StatsKind>,
+
     /// Read null-separated paths from stdin
     #[arg(short = '0', long = "null", help_heading = "Other")]
     pub null_separator: bool,
@@ -140,6 +866,48 @@ pub struct Cli {
     pub version: Option<bool>,
 }
 
+/// Model families with a recommended prompt structure
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Target {
+    /// Claude XML documents with closing tags escaped inside content
+    Claude,
+    /// Markdown with a heading per file
+    Gpt,
+    /// Markdown with a heading per file
+    Gemini,
+    /// Plain markdown code blocks
+    Deepseek,
+}
+
+/// File orderings available through `--sort`
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum SortKey {
+    /// Most frequently committed files in the last 90 days first
+    #[value(name = "git-churn", alias = "churn")]
+    GitChurn,
+    /// Most recently committed files first
+    #[value(name = "git-recency")]
+    GitRecency,
+}
+
+/// Reports available through `--stats`
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum StatsKind {
+    /// Token counts per file and per directory, sorted descending
+    Tokens,
+    /// Files, lines, words, characters and bytes in the emitted output
+    Counts,
+}
+
+/// Parse a size or count that must be at least 1
+fn parse_positive(value: &str) -> std::result::Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 fn print_short_help() {
     println!(
         "{DESCRIPTION}\n\n{USAGE}\n\n{EXAMPLES}\n\nFor a full list of options, run `fuse --help`."
@@ -176,26 +944,76 @@ pub fn run() -> Result<()> {
         return Ok(());
     }
 
-    let args = Cli::parse();
+    // `fuse usage` reports on the local run history, unless a path named `usage` exists
+    if raw_args.len() == 2 && raw_args[1] == "usage" && !Path::new("usage").exists() {
+        return print_usage_report();
+    }
+
+    // `fuse schema <name>` prints a JSON Schema, unless a path named `schema` exists
+    if raw_args.len() <= 3
+        && raw_args.get(1).is_some_and(|a| a == "schema")
+        && !Path::new("schema").exists()
+    {
+        return print_schema(raw_args.get(2).map(String::as_str));
+    }
+
+    let matches = Cli::command().get_matches();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Validate table of contents flags
+    if args.toc_dirs_only && args.toc_files {
+        eprintln!("Error: Cannot specify both --toc-dirs-only and --toc-files");
+        std::process::exit(1);
+    }
 
     // Combine paths from arguments and stdin
     let mut all_paths = args.paths.clone();
 
     // Only read from stdin if no paths were provided via command line
     // This prevents stdin from being read when paths are already specified
-    if all_paths.is_empty() {
+    if all_paths.is_empty() && !selects_git_files(&args) {
         let stdin_paths = read_paths_from_stdin(args.null_separator)?;
         for path in stdin_paths {
             all_paths.push(PathBuf::from(path));
         }
     }
 
+    // Expand `@alias` arguments defined in .fuserc
+    let mut all_paths = config::expand_path_aliases(all_paths)?;
+
+    // Shallow-clone `https://host/org/repo[@ref]` arguments; the checkouts are
+    // removed when they go out of scope at the end of the run
+    let mut checkouts = Vec::new();
+    for path in all_paths.iter_mut() {
+        if let Some((url, reference)) = path.to_str().and_then(parse_remote) {
+            let checkout = RemoteCheckout::shallow_clone(&url, reference.as_deref())?;
+            *path = checkout.path().to_path_buf();
+            checkouts.push(checkout);
+        }
+    }
+
+    // Git-based selections on their own cover the current directory
+    if selects_git_files(&args) && all_paths.is_empty() {
+        all_paths.push(PathBuf::from("."));
+    }
+
     // Validate that we have at least one path
     if all_paths.is_empty() {
         print_short_help();
         std::process::exit(1);
     }
 
+    // Expand `dir/...` and `dir/**<suffix>` into a directory plus a file-name pattern
+    let mut root_includes = Vec::new();
+    for path in all_paths.iter_mut() {
+        if let Some((root, pattern)) = expand_recursive_sugar(path) {
+            if let Some(pattern) = pattern {
+                root_includes.push((root.clone(), pattern));
+            }
+            *path = root;
+        }
+    }
+
     // Validate that all paths exist
     for path in &all_paths {
         if !path.exists() {
@@ -204,10 +1022,109 @@ pub fn run() -> Result<()> {
         }
     }
 
-    // Validate table of contents flags
-    if args.toc_dirs_only && args.toc_files {
-        eprintln!("Error: Cannot specify both --toc-dirs-only and --toc-files");
-        std::process::exit(1);
+    // Take the files from the git index instead of walking the filesystem
+    if args.git_tracked {
+        let Some(tracked) = select_git_files(&all_paths, tracked_files) else {
+            eprintln!("Error: --git-tracked requires the paths to be inside a git repository");
+            std::process::exit(1);
+        };
+        if tracked.is_empty() {
+            eprintln!("Warning: No tracked files under the given paths");
+            return Ok(());
+        }
+        all_paths = tracked;
+    }
+
+    // Only the files git doesn't know about yet
+    if args.git_untracked {
+        let Some(untracked) = select_git_files(&all_paths, untracked_files) else {
+            eprintln!("Error: --git-untracked requires the paths to be inside a git repository");
+            std::process::exit(1);
+        };
+        if untracked.is_empty() {
+            eprintln!("Warning: No untracked files under the given paths");
+            return Ok(());
+        }
+        all_paths = untracked;
+    }
+
+    // Narrow the inputs down to the files staged in the git index
+    if args.staged {
+        let Some(staged) = select_git_files(&all_paths, staged_files) else {
+            eprintln!("Error: --staged requires the paths to be inside a git repository");
+            std::process::exit(1);
+        };
+        if staged.is_empty() {
+            eprintln!("Warning: No staged files under the given paths");
+            return Ok(());
+        }
+        all_paths = staged;
+    }
+
+    // Narrow the inputs down to the files last committed within the date range
+    if args.git_since.is_some() || args.git_until.is_some() {
+        let (since, until) = (args.git_since.as_deref(), args.git_until.as_deref());
+        let Some(committed) =
+            select_git_files(&all_paths, |path| files_last_committed(path, since, until))
+        else {
+            eprintln!("Error: --git-since and --git-until require the paths to be inside a git repository");
+            std::process::exit(1);
+        };
+        if committed.is_empty() {
+            eprintln!("Warning: No files last committed in that range under the given paths");
+            return Ok(());
+        }
+        all_paths = committed;
+    }
+
+    // Narrow the inputs down to the files touched in the range, summarizing it first
+    let mut pr_summary = None;
+    if let Some(range) = &args.pr_range {
+        if args.pr_summary {
+            pr_summary = range_summary(&all_paths, range)
+                .map(|(commits, shortstat)| pr_summary_section(range, &commits, &shortstat));
+        }
+        let Some(touched) = select_git_files(&all_paths, |path| range_files(path, range)) else {
+            eprintln!(
+                "Error: --pr-range requires the paths to be inside a git repository where {} resolves",
+                range
+            );
+            std::process::exit(1);
+        };
+        if touched.is_empty() {
+            eprintln!(
+                "Warning: No files touched in {} under the given paths",
+                range
+            );
+            return Ok(());
+        }
+        all_paths = touched;
+    }
+
+    // Narrow the inputs down to the files changed since the reference, keeping their hunks
+    let mut changed_hunks = Vec::new();
+    if let Some(reference) = &args.diff_only {
+        let changed = select_git_files(&all_paths, |path| {
+            let hunks = diff_hunks(path, reference)?;
+            let files = hunks.iter().map(|(file, _)| file.clone()).collect();
+            changed_hunks.extend(hunks);
+            Some(files)
+        });
+        let Some(changed) = changed else {
+            eprintln!(
+                "Error: --diff-only requires the paths to be inside a git repository where {} exists",
+                reference
+            );
+            std::process::exit(1);
+        };
+        if changed.is_empty() {
+            eprintln!(
+                "Warning: No changes since {} under the given paths",
+                reference
+            );
+            return Ok(());
+        }
+        all_paths = changed;
     }
 
     // Determine table of contents mode
@@ -223,36 +1140,1648 @@ pub fn run() -> Result<()> {
         None
     };
 
-    // Create file processor
-    let processor = FileProcessor::new(
-        args.extensions,
-        args.include_hidden,
-        args.ignore_files_only,
-        args.ignore_gitignore,
-        args.ignore_patterns,
-        args.line_numbers,
-        toc_mode,
-    )?;
+    // Fail fast on flags whose feature is missing, or note the fallback
+    for note in missing_feature_notes(&args, feature_enabled)? {
+        eprintln!("Note: {}", note);
+    }
+    let clipboard = args.clipboard && feature_enabled("clipboard");
+
+    let global_flags = FormatFlags::from_cli(&args);
+    // An explicit --tokenizer wins over the one implied by --model
+    let tokenizer = args
+        .tokenizer
+        .or(args.model.map(Model::tokenizer))
+        .unwrap_or_default();
+    let time_zone = if args.local_time {
+        TimeZone::Local
+    } else {
+        TimeZone::Utc
+    };
+
+    // Presets come first, then --ignore-from, then --ignore, so later
+    // patterns (including `!` negations) can override earlier ones
+    let mut ignore_patterns: Vec<String> = args
+        .presets
+        .iter()
+        .flat_map(|preset| preset.patterns())
+        .map(|pattern| pattern.to_string())
+        .collect();
+    if let Some(path) = &args.ignore_from {
+        ignore_patterns.extend(CustomIgnore::load_patterns(path)?);
+    }
+    ignore_patterns.extend(args.ignore_patterns.iter().cloned());
+
+    let mut known_names: Vec<String> = if args.no_known_names {
+        Vec::new()
+    } else {
+        KNOWN_FILE_NAMES
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    };
+    known_names.extend(args.known_names.iter().cloned());
 
-    // Determine output format and process files
-    let output = if args.claude_xml {
-        let mut formatter = XmlFormatter::new();
-        processor.process_paths(&all_paths, &mut formatter)?
-    } else if args.markdown {
-        let mut formatter = MarkdownFormatter::new();
-        processor.process_paths(&all_paths, &mut formatter)?
+    let test_filter = if args.no_tests {
+        TestFilter::NoTests
+    } else if args.tests_only {
+        TestFilter::TestsOnly
     } else {
-        let mut formatter = DefaultFormatter::new();
-        processor.process_paths(&all_paths, &mut formatter)?
+        TestFilter::All
     };
 
-    // Write output
-    if let Some(output_path) = args.output_file {
-        let mut file = File::create(output_path)?;
-        file.write_all(output.as_bytes())?;
+    // Create file processor
+    let mut processor = FileProcessor::builder()
+        .extensions(args.extensions.clone())
+        .include_hidden(args.include_hidden)
+        .ignore_files_only(args.ignore_files_only)
+        .ignore_gitignore(args.ignore_gitignore)
+        .ignore_patterns(ignore_patterns)
+        .line_numbers(args.line_numbers)
+        .toc_mode(toc_mode)
+        .build()?
+        .with_include_submodules(args.include_submodules)
+        .with_default_ignores(!args.no_default_ignores)
+        .with_include_lockfiles(args.include_lockfiles)
+        .with_allow_sensitive(args.allow_sensitive)
+        .with_strict(args.strict)
+        .with_include_vendored(args.include_vendored)
+        .with_include_generated(args.include_generated)
+        .with_max_depth(args.max_depth)
+        .with_sniff(args.sniff)
+        .with_known_names(known_names)
+        .with_skip_empty(args.skip_empty)
+        .with_transcode(args.transcode)
+        .with_strip_ansi(args.strip_ansi)
+        .with_pretty_json(args.pretty_json)
+        .with_test_filter(test_filter)
+        .with_binary_mode(args.binary)
+        .with_hex_dump_bytes(args.hex_bytes.unwrap_or(HEX_DUMP_BYTES))
+        .with_readme_first(args.readme_first)
+        .with_dir_preambles(args.dir_preambles)
+        .with_collections(args.collections)
+        .with_dedupe(args.dedupe)
+        .with_toc_tokens(args.tokens)
+        .with_verbose(args.verbose)
+        .with_tokenizer(tokenizer);
+
+    if let Some(system_file) = &args.system_file {
+        let system = std::fs::read_to_string(system_file)?;
+        processor = processor.with_prefix(system.trim_end());
+    }
+    if let Some(prefix) = &args.prefix {
+        processor = processor.with_prefix(prefix.as_str());
+    }
+    if let Some(suffix) = &args.suffix {
+        processor = processor.with_suffix(suffix.as_str());
+    }
+
+    for (root, pattern) in root_includes {
+        processor = processor.with_root_include(root, &pattern)?;
+    }
+    for pattern in &args.include_patterns {
+        processor = processor.with_include(pattern)?;
+    }
+    if let Some(command) = &args.filter_cmd {
+        processor = processor.with_filter_cmd(command);
+    }
+    if let Some(command) = &args.transform_cmd {
+        processor = processor.with_transform_cmd(command);
+    }
+    // A cache hit skips the transforms, so `--redact` couldn't report what it
+    // redacted; it always reads the files afresh
+    if let Some(cache_dir) = args.cache.as_ref().filter(|_| !args.redact) {
+        let cache = FileCache::open(cache_dir, &cache_key(&matches, &args, &all_paths));
+        processor = processor
+            .with_output_exclusion(cache.path())
+            .with_cache(cache);
+    }
+    for pattern in &args.force_include {
+        processor = processor.with_force_include(pattern)?;
+    }
+    for pattern in &args.contains {
+        processor = processor.with_contains(pattern)?;
+    }
+    for pattern in &args.not_contains {
+        processor = processor.with_not_contains(pattern)?;
+    }
+    for rule in &args.priorities {
+        processor = processor.with_priority(rule)?;
+    }
+    for output_path in args.output_files.iter().chain(&args.trace) {
+        processor = processor.with_output_exclusion(output_path);
+    }
+
+    if args.diff_only.is_some() {
+        processor = processor.with_transform(DiffHunks::new(changed_hunks));
+    }
+    if args.normalize {
+        processor = processor.with_transform(Normalize::new(args.tab_width));
+    }
+    if args.strip_docstrings {
+        processor = processor.with_transform(StripDocstrings);
+    }
+    if args.collapse_imports {
+        processor = processor.with_transform(CollapseImports);
+    }
+    #[cfg(feature = "signatures")]
+    if args.signatures {
+        processor = processor.with_transform(crate::signatures::Signatures);
+    }
+    if let Some(rows) = args.sample_rows {
+        processor = processor
+            .with_sample_rows(Some(rows))
+            .with_transform(SampleRows::new(rows));
+    }
+    let redact = args.redact.then(Redact::new);
+    if let Some(redact) = &redact {
+        processor = processor.with_transform(redact.clone());
+    }
+    // Replacements from a file run first, then those given on the command line
+    let mut replacements = match &args.replace_from {
+        Some(path) => RegexReplace::load_file(path)?,
+        None => Vec::new(),
+    };
+    for expression in &args.replace {
+        replacements.push(RegexReplace::parse(expression)?);
+    }
+    for replacement in replacements {
+        processor = processor.with_transform(replacement);
+    }
+    if args.strip_frontmatter {
+        processor = processor.with_transform(StripFrontMatter);
+    }
+    if args.strip_license_headers {
+        processor = processor.with_transform(StripLicenseHeader);
+    }
+    if args.strip_trailing_ws {
+        processor = processor.with_transform(StripTrailingWhitespace);
+    }
+    if args.squeeze_blank {
+        processor = processor.with_transform(SqueezeBlank);
+    }
+    if args.dedent {
+        processor = processor.with_transform(Dedent);
+    }
+    if args.compact {
+        processor = processor.with_transform(Compact);
+    }
+    if args.minify_markup {
+        processor = processor.with_transform(MinifyMarkup);
+    }
+    match (args.max_lines_per_file, args.max_tokens_per_file) {
+        (max_lines, Some(max_tokens)) => {
+            processor = processor.with_transform(FileLimit::new(max_lines, max_tokens, tokenizer));
+        }
+        (Some(max_lines), None) => {
+            processor = processor.with_transform(HeadTail::new(max_lines, 0));
+        }
+        (None, None) => {}
+    }
+    if args.head.is_some() || args.tail.is_some() {
+        processor = processor.with_transform(HeadTail::new(
+            args.head.unwrap_or(0),
+            args.tail.unwrap_or(0),
+        ));
+    }
+    if args.peek {
+        processor = processor.with_transform(HeadTail::new(HeadTail::PEEK_LINES, 0));
+    }
+    if let Some(cols) = args.wrap_cols {
+        processor = processor.with_transform(WrapLines::new(cols));
+    }
+    match args.sort {
+        Some(SortKey::GitChurn) => {
+            processor = processor.with_ordering(Churn::from_git(&all_paths));
+        }
+        Some(SortKey::GitRecency) => {
+            processor = processor.with_ordering(Recency::from_git(&all_paths));
+        }
+        None => {}
+    }
+
+    // llms.txt mode writes its own pair of files instead of a single output
+    if let Some(output_dir) = &args.llms_txt {
+        llms_txt::write_llms_txt(&processor, &all_paths, output_dir)?;
+        report_redactions(redact.as_ref());
+        return Ok(());
+    }
+
+    if let Some(db_path) = &args.output_sqlite {
+        write_sqlite_output(&processor, &all_paths, db_path)?;
+        report_redactions(redact.as_ref());
+        return Ok(());
+    }
+
+    // Files past the token budget would be dropped anyway, so stop reading there,
+    // unless the plan, the token report or the trace should cover every file
+    if let Some(max_tokens) = args.max_tokens {
+        if !args.budget_plan && args.stats != Some(StatsKind::Tokens) && args.trace.is_none() {
+            processor = processor.with_read_budget(max_tokens);
+        }
+    }
+
+    let git_log = args
+        .with_git_log
+        .map(|count| recent_commits(&all_paths, count))
+        .filter(|commits| !commits.is_empty())
+        .map(|commits| git_log_section(&commits));
+    let trailer = [pr_summary, git_log]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| format!("{}\n\n{}", a, b));
+
+    // Nothing needs every file or the finished bundle at once, so read, format and
+    // write one file at a time
+    let single_output = match args.output_files.as_slice() {
+        [] => true,
+        [output_path] => !args.tee && !needs_output(output_path),
+        _ => false,
+    };
+    if single_output
+        && processor.can_stream()
+        && !args.header
+        && args.chunks.is_none()
+        && args.stats.is_none()
+        && can_stream(&args, &None, &trailer, args.max_tokens, clipboard)
+    {
+        let choice = if args.output_files.is_empty() {
+            global_flags.choice()
+        } else {
+            output_format_flags(&matches, 1)[0].choice()
+        };
+        let mut decisions = Decisions::default();
+        let (written, bytes) = stream_output(
+            &processor,
+            &all_paths,
+            &mut decisions,
+            choice,
+            &args,
+            time_zone,
+            tokenizer,
+        )?;
+        report_redactions(redact.as_ref());
+        if let Some(trace_path) = &args.trace {
+            write_trace(&decisions, trace_path)?;
+        }
+        if written == 0 {
+            let report = decisions.report();
+            eprintln!("Warning: {}", explain_no_matches(&report, &args));
+            return Ok(());
+        }
+        record_history(&all_paths, bytes);
+        return Ok(());
+    }
+
+    // Walk and read once, then render every requested output from the same files
+    let (files, decisions) = processor.collect_files_with_decisions(&all_paths)?;
+    report_redactions(redact.as_ref());
+    if let Some(trace_path) = &args.trace {
+        write_trace(&decisions, trace_path)?;
+    }
+    if files.is_empty() {
+        // Explain the empty result rather than emitting an empty skeleton
+        let report = decisions.report();
+        eprintln!("Warning: {}", explain_no_matches(&report, &args));
+        return Ok(());
+    }
+    if let Some(StatsKind::Tokens) = args.stats {
+        let breakdown = TokenBreakdown::with_counter(&files, |file| processor.token_count(file));
+        eprintln!("{}", breakdown.render());
+    }
+
+    // Chunk mode writes JSONL records for embedding pipelines instead of a bundle
+    if let Some(size) = args.chunks {
+        let records =
+            chunks::render_jsonl(&files, size, args.chunk_overlap.unwrap_or(0), tokenizer);
+        if args.output_files.is_empty() {
+            write_stdout(&records)?;
+        }
+        for output_path in &args.output_files {
+            write_atomic(output_path, records.as_bytes(), args.backup)?;
+        }
+        return Ok(());
+    }
+    let header = args
+        .header
+        .then(|| generation_header(&raw_args, &files, args.reproducible, time_zone, tokenizer));
+    // The header, trailing sections and footer sit outside the formatted bundle, so take them off the budget up front
+    let budget = args.max_tokens.map(|max_tokens| {
+        let header_tokens = header.as_deref().map_or(0, |h| tokenizer.count(h) + 2);
+        let trailer_tokens = trailer.as_deref().map_or(0, |t| tokenizer.count(t) + 2);
+        let footer_tokens = if args.integrity {
+            let footer = integrity::Footer::new("", files.len()).render();
+            tokenizer.count(&footer) + 2
+        } else {
+            0
+        };
+        max_tokens.saturating_sub(header_tokens + trailer_tokens + footer_tokens)
+    });
+
+    if args.budget_plan {
+        let (plan, _) = render_output(
+            &processor,
+            &all_paths,
+            &files,
+            global_flags.choice(),
+            &args,
+            budget,
+        )?;
+        write_stdout(&plan)?;
+        return Ok(());
+    }
+
+    // Nothing needs the finished bundle as a whole, so write it out piece by piece
+    if args.output_files.is_empty() && can_stream(&args, &header, &trailer, budget, clipboard) {
+        let mut out = CountingWriter::new(io::BufWriter::new(io::stdout().lock()));
+        write_output(
+            &processor,
+            &all_paths,
+            Source::Collected(&files),
+            global_flags.choice(),
+            &args,
+            budget,
+            &mut out,
+        )?;
+        out.flush()?;
+        record_history(&all_paths, out.bytes);
+        return Ok(());
+    }
+
+    let output = if args.output_files.is_empty() {
+        let output = render_output(
+            &processor,
+            &all_paths,
+            &files,
+            global_flags.choice(),
+            &args,
+            budget,
+        )?;
+        let emitted = output.1;
+        let output = finish_output(&header, &trailer, output, &args);
+        check_context_window(&output, &args, tokenizer)?;
+        if !clipboard || args.tee {
+            write_stdout(&output)?;
+        }
+        if args.tokens {
+            eprintln!("Tokens: {}", tokenizer.count(&output));
+        }
+        if let Some(StatsKind::Counts) = args.stats {
+            eprintln!("{}", TextCounts::new(&output, emitted).render());
+        }
+        output
     } else {
-        print!("{}", output);
+        let output_flags = output_format_flags(&matches, args.output_files.len());
+
+        let streams = can_stream(&args, &header, &trailer, budget, clipboard) && !args.tee;
+        let mut first_output = None;
+        let mut streamed_bytes = None;
+        for (i, output_path) in args.output_files.iter().enumerate() {
+            let flags = &output_flags[i];
+            if streams && !needs_output(output_path) {
+                let output_path = resolve_output_template(
+                    output_path,
+                    &all_paths,
+                    i + 1,
+                    "",
+                    time_zone,
+                    tokenizer,
+                );
+                let mut bytes = 0;
+                write_atomic_with(&output_path, args.backup, |file| {
+                    let mut out = CountingWriter::new(file);
+                    write_output(
+                        &processor,
+                        &all_paths,
+                        Source::Collected(&files),
+                        flags.choice(),
+                        &args,
+                        budget,
+                        &mut out,
+                    )?;
+                    bytes = out.bytes;
+                    Ok::<_, crate::FilesToPromptError>(true)
+                })?;
+                streamed_bytes.get_or_insert(bytes);
+                continue;
+            }
+            let output = render_output(
+                &processor,
+                &all_paths,
+                &files,
+                flags.choice(),
+                &args,
+                budget,
+            )?;
+            let emitted = output.1;
+            let output = finish_output(&header, &trailer, output, &args);
+            check_context_window(&output, &args, tokenizer)?;
+
+            let output_path = resolve_output_template(
+                output_path,
+                &all_paths,
+                i + 1,
+                &output,
+                time_zone,
+                tokenizer,
+            );
+            write_atomic(&output_path, output.as_bytes(), args.backup)?;
+            if args.tokens {
+                eprintln!(
+                    "Tokens: {} ({})",
+                    tokenizer.count(&output),
+                    output_path.display()
+                );
+            }
+            if let Some(StatsKind::Counts) = args.stats {
+                eprintln!(
+                    "{} ({})",
+                    TextCounts::new(&output, emitted).render(),
+                    output_path.display()
+                );
+            }
+            first_output.get_or_insert(output);
+        }
+        if let (None, Some(bytes)) = (&first_output, streamed_bytes) {
+            record_history(&all_paths, bytes);
+            return Ok(());
+        }
+        let output = first_output.unwrap_or_default();
+        if args.tee {
+            write_stdout(&output)?;
+        }
+        output
+    };
+
+    if clipboard {
+        copy_to_clipboard(&output)?;
     }
 
+    record_history(&all_paths, output.len());
+
     Ok(())
 }
+
+/// Arguments that only shape how the files are written out, never their
+/// content, so they stay out of the `--cache` key
+const CACHE_NEUTRAL_ARGS: &[&str] = &[
+    "output_files",
+    "tee",
+    "backup",
+    "clipboard",
+    "cache",
+    "trace",
+    "verbose",
+    "stats",
+    "claude_xml",
+    "markdown",
+    "raw",
+    "raw_separator",
+    "line_numbers",
+    "table_of_contents",
+    "toc_dirs_only",
+    "toc_files",
+    "header",
+    "local_time",
+    "reproducible",
+    "integrity",
+    "tokens",
+    "model",
+    "budget_plan",
+];
+
+/// Hash of everything that shapes a run's file contents, naming its `--cache` file.
+///
+/// Besides the arguments, this covers the files they point at and the commit
+/// `--diff-only` resolves to, since either can change between runs. Relative
+/// paths depend on the working directory, so it is part of the key along with
+/// the version that wrote the entries.
+fn cache_key(matches: &ArgMatches, args: &Cli, paths: &[PathBuf]) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut key = format!("{}\0{}", env!("CARGO_PKG_VERSION"), cwd.display()).into_bytes();
+    // Matches list the arguments given first, so go by the definition order
+    // instead, leaving out argument groups
+    let command = Cli::command();
+    for id in command.get_arguments().map(|arg| arg.get_id().as_str()) {
+        if CACHE_NEUTRAL_ARGS.contains(&id) {
+            continue;
+        }
+        let Ok(Some(values)) = matches.try_get_raw(id) else {
+            continue;
+        };
+        key.extend_from_slice(format!("\0{}", id).as_bytes());
+        for value in values {
+            key.push(b'\0');
+            key.extend_from_slice(value.as_encoded_bytes());
+        }
+    }
+    for path in [&args.ignore_from, &args.replace_from, &args.system_file]
+        .into_iter()
+        .flatten()
+    {
+        key.push(b'\0');
+        key.extend_from_slice(&std::fs::read(path).unwrap_or_default());
+    }
+    if let Some(reference) = &args.diff_only {
+        let repo = paths.first().map_or(Path::new("."), PathBuf::as_path);
+        key.push(b'\0');
+        key.extend_from_slice(
+            resolve_commit(repo, reference)
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+    }
+    sha256_hex(&key)
+}
+
+/// Whether the bundle can go straight to stdout: nothing wraps, measures or
+/// copies the finished output, so it never has to be held in memory at once
+fn can_stream(
+    args: &Cli,
+    header: &Option<String>,
+    trailer: &Option<String>,
+    budget: Option<usize>,
+    clipboard: bool,
+) -> bool {
+    header.is_none()
+        && trailer.is_none()
+        && budget.is_none()
+        && !clipboard
+        && !args.integrity
+        && !args.tokens
+        && args.model.is_none()
+        && !matches!(args.stats, Some(StatsKind::Counts))
+}
+
+/// Does the `-o` template need the finished output to name the file?
+fn needs_output(output_path: &Path) -> bool {
+    output_path.to_string_lossy().contains("{tokens}")
+}
+
+/// Walk the paths and write each file as it is read to stdout, or to the single
+/// `-o` if given, recording the walk's filter decisions in `decisions`.
+///
+/// Returns the number of files written and the bytes they took. Nothing is
+/// written, and an existing `-o` file is kept, when no file passes the filters.
+fn stream_output(
+    processor: &FileProcessor,
+    paths: &[PathBuf],
+    decisions: &mut Decisions,
+    choice: FormatChoice,
+    args: &Cli,
+    time_zone: TimeZone,
+    tokenizer: Tokenizer,
+) -> Result<(usize, usize)> {
+    let Some(output_path) = args.output_files.first() else {
+        let mut out = CountingWriter::new(io::BufWriter::new(io::stdout().lock()));
+        let written = write_output(
+            processor,
+            paths,
+            Source::Stream(decisions),
+            choice,
+            args,
+            None,
+            &mut out,
+        )?;
+        out.flush()?;
+        return Ok((written, out.bytes));
+    };
+
+    let output_path = resolve_output_template(output_path, paths, 1, "", time_zone, tokenizer);
+    let mut result = (0, 0);
+    write_atomic_with(&output_path, args.backup, |file| {
+        let mut out = CountingWriter::new(file);
+        let written = write_output(
+            processor,
+            paths,
+            Source::Stream(decisions),
+            choice,
+            args,
+            None,
+            &mut out,
+        )?;
+        result = (written, out.bytes);
+        Ok::<_, crate::FilesToPromptError>(written > 0)
+    })?;
+    Ok(result)
+}
+
+/// Write `output` to stdout
+fn write_stdout(output: &str) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(output.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Append the run to the usage history if it has been opted into
+fn record_history(paths: &[PathBuf], bytes: usize) {
+    if let Some(history_path) = usage::history_path() {
+        if let Err(e) = usage::record_run(&history_path, paths, bytes) {
+            eprintln!("Warning: Could not record usage history: {}", e);
+        }
+    }
+}
+
+/// Writer that tallies the bytes passed through it, for the usage history
+struct CountingWriter<W> {
+    inner: W,
+    bytes: usize,
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, bytes: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Warn, or fail with `--strict`, when the output would not fit the `--model` context window
+fn check_context_window(output: &str, args: &Cli, tokenizer: Tokenizer) -> Result<()> {
+    let Some(model) = args.model else {
+        return Ok(());
+    };
+    let tokens = tokenizer.count(output);
+    if tokens <= model.context_window() {
+        return Ok(());
+    }
+
+    let error = crate::FilesToPromptError::ContextWindowExceeded {
+        model: model.name(),
+        tokens,
+        limit: model.context_window(),
+    };
+    if args.strict {
+        return Err(error);
+    }
+    eprintln!("Warning: {}; use --max-tokens to trim it", error);
+    Ok(())
+}
+
+/// Write one JSON line per visited file with the filter decision and its timing,
+/// and one per file or directory the walker pruned
+fn write_trace(decisions: &Decisions, trace_path: &Path) -> Result<()> {
+    let mut trace = String::new();
+    let mut record = |path: &Path, decision: &str, reason: Option<FilterReason>, elapsed: u128| {
+        trace.push_str(&format!(
+            "{{\"path\":{},\"decision\":\"{}\",\"rule\":{},\"elapsed_us\":{}}}\n",
+            json_string(&path.display().to_string()),
+            decision,
+            reason.map_or("null".to_string(), |r| format!("\"{}\"", r.as_str())),
+            elapsed
+        ));
+    };
+    for decision in &decisions.files {
+        let kind = if decision.reason.is_some() {
+            "excluded"
+        } else {
+            "included"
+        };
+        record(
+            &decision.path,
+            kind,
+            decision.reason,
+            decision.elapsed.as_micros(),
+        );
+    }
+    // Pruned directories are one record each, as the walker never looked inside
+    for (path, reason) in &decisions.pruned {
+        record(path, "pruned", Some(*reason), 0);
+    }
+    write_atomic(trace_path, trace.as_bytes(), false)?;
+    Ok(())
+}
+
+/// Tell the user on stderr how many secrets `--redact` replaced
+fn report_redactions(redact: Option<&Redact>) {
+    if let Some(summary) = redact.and_then(Redact::summary) {
+        eprintln!("{}", summary);
+    }
+}
+
+/// Describe which filter removed the most candidates and the flag that relaxes it
+fn explain_no_matches(report: &FilterReport, args: &Cli) -> String {
+    let Some((reason, count)) = report.dominant() else {
+        return "No files matched: the given paths contain no files.".to_string();
+    };
+
+    // Filters the walker applies can prune a whole directory, which counts once
+    let pruned = matches!(
+        reason,
+        FilterReason::Hidden
+            | FilterReason::Gitignore
+            | FilterReason::IgnorePattern
+            | FilterReason::Submodule
+            | FilterReason::DefaultIgnore
+            | FilterReason::Vendored
+            | FilterReason::Generated
+    );
+    let files = match (count == 1, pruned) {
+        (true, false) => "file was",
+        (false, false) => "files were",
+        (true, true) => "path was",
+        (false, true) => "paths were",
+    };
+    let hint = match reason {
+        FilterReason::Extension => format!(
+            "{} {} skipped by the extension filter (-e {}); add more -e flags or drop them",
+            count,
+            files,
+            args.extensions.join(" -e ")
+        ),
+        FilterReason::Hidden => format!(
+            "{} hidden {} skipped; use --include-hidden to include them",
+            count, files
+        ),
+        FilterReason::Gitignore => format!(
+            "{} {} skipped by .gitignore/.ignore rules; use --ignore-gitignore to include them",
+            count, files
+        ),
+        FilterReason::IgnorePattern => format!(
+            "{} {} skipped by --ignore patterns ({}); loosen the patterns or use --ignore-files-only",
+            count,
+            files,
+            args.presets
+                .iter()
+                .map(|preset| format!("--preset {}", preset.name()))
+                .chain(args.ignore_patterns.iter().cloned())
+                .chain(args.ignore_from.iter().map(|path| format!("--ignore-from {}", path.display())))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        FilterReason::IncludePattern => format!(
+            "{} {} skipped by --include patterns ({}); add or loosen a pattern",
+            count,
+            files,
+            args.include_patterns.join(", ")
+        ),
+        FilterReason::PathPattern => format!(
+            "{} {} skipped by the file-name pattern in a `dir/**<pattern>` path",
+            count, files
+        ),
+        FilterReason::PreviousOutput => format!(
+            "{} {} skipped as previous -o outputs",
+            count, files
+        ),
+        FilterReason::Binary => format!("{} binary {} skipped", count, files),
+        FilterReason::Content => format!(
+            "{} {} skipped by --contains/--not-contains content filters",
+            count, files
+        ),
+        FilterReason::Tests => format!(
+            "{} {} skipped by {}",
+            count,
+            files,
+            if args.tests_only { "--tests-only" } else { "--no-tests" }
+        ),
+        FilterReason::FilterCmd => format!(
+            "{} {} rejected by --filter-cmd `{}`",
+            count,
+            files,
+            args.filter_cmd.as_deref().unwrap_or_default()
+        ),
+        FilterReason::Empty => format!(
+            "{} empty {} skipped by --skip-empty",
+            count, files
+        ),
+        FilterReason::Generated => format!(
+            "{} {} skipped as generated (.gitattributes markers, minified content or DO NOT EDIT headers); use --include-generated to include them",
+            count, files
+        ),
+        FilterReason::DefaultIgnore => format!(
+            "{} {} skipped by the built-in ignore list (.git, node_modules, target, dist, ...); use --no-default-ignores to include them",
+            count, files
+        ),
+        FilterReason::Lockfile => format!(
+            "{} {} skipped as lockfiles; use --include-lockfiles to include them",
+            count, files
+        ),
+        FilterReason::Sensitive => format!(
+            "{} sensitive {} skipped (.env, private keys, credentials); use --allow-sensitive to include them",
+            count, files
+        ),
+        FilterReason::Vendored => format!(
+            "{} vendored {} skipped (vendor/, third_party/); use --include-vendored to include them",
+            count, files
+        ),
+        FilterReason::Submodule => format!(
+            "{} {} skipped as git submodules; use --include-submodules to include them",
+            count, files
+        ),
+    };
+    format!("No files matched: {}.", hint)
+}
+
+/// Build the opt-in `--header` block describing how the bundle was generated.
+///
+/// In reproducible mode the timestamp is dropped and absolute paths on the
+/// command line are made relative, so repeated runs are byte-identical.
+fn generation_header(
+    raw_args: &[String],
+    files: &[FileEntry],
+    reproducible: bool,
+    time_zone: TimeZone,
+    tokenizer: Tokenizer,
+) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let command = std::iter::once("fuse".to_string())
+        .chain(raw_args.iter().skip(1).map(|arg| {
+            let arg = if reproducible && Path::new(arg).is_absolute() {
+                match Path::new(arg).strip_prefix(&cwd) {
+                    Ok(relative) if !relative.as_os_str().is_empty() => {
+                        relative.display().to_string()
+                    }
+                    _ => Path::new(arg)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| ".".to_string()),
+                }
+            } else {
+                arg.clone()
+            };
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("'{}'", arg)
+            } else {
+                arg
+            }
+        }))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let tokens: usize = files
+        .iter()
+        .map(|file| tokenizer.count(&file.content))
+        .sum();
+
+    let mut lines = vec![format!("Generated by fuse {}", env!("CARGO_PKG_VERSION"))];
+    if !reproducible {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        lines.push(format!("Generated at: {}", time_zone.format_timestamp(now)));
+    }
+    lines.push(format!("Command: {}", command));
+    lines.push(format!(
+        "Files: {}, estimated tokens: {}",
+        files.len(),
+        tokens
+    ));
+    lines.join("\n")
+}
+
+fn with_header(header: &Option<String>, output: String) -> String {
+    match header {
+        Some(header) => format!("{}\n\n{}", header, output),
+        None => output,
+    }
+}
+
+/// Recent commit subjects for `--with-git-log`, laid out like a file section
+fn git_log_section(commits: &[String]) -> String {
+    format!("Recent commits\n---\n{}\n---", commits.join("\n"))
+}
+
+/// Commits and diffstat of the `--pr-range`, laid out like a file section
+fn pr_summary_section(range: &str, commits: &[String], shortstat: &str) -> String {
+    let mut lines = vec![format!(
+        "{} {}, {}",
+        commits.len(),
+        if commits.len() == 1 {
+            "commit"
+        } else {
+            "commits"
+        },
+        shortstat
+    )];
+    if !commits.is_empty() {
+        lines.push(String::new());
+        lines.extend(commits.iter().cloned());
+    }
+    format!("Changes in {}\n---\n{}\n---", range, lines.join("\n"))
+}
+
+/// Add the header and trailing sections (range summary, commit log), then the
+/// integrity footer covering everything above it
+fn finish_output(
+    header: &Option<String>,
+    trailer: &Option<String>,
+    (output, files): (String, usize),
+    args: &Cli,
+) -> String {
+    let mut output = with_header(header, output);
+    if let Some(trailer) = trailer {
+        output = format!("{}\n\n{}", output.trim_end_matches('\n'), trailer);
+    }
+    if args.integrity {
+        integrity::append_footer(output, files)
+    } else {
+        output
+    }
+}
+
+/// Output structure selected by the format flags
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FormatChoice {
+    Default,
+    Xml { escape_closing_tags: bool },
+    Markdown { headings: bool },
+    Raw,
+    Manifest(ManifestFormat),
+}
+
+/// The subset of flags that pick an output structure
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FormatFlags {
+    manifest: Option<ManifestFormat>,
+    raw: bool,
+    claude_xml: bool,
+    markdown: bool,
+    target: Option<Target>,
+}
+
+impl FormatFlags {
+    fn from_cli(args: &Cli) -> Self {
+        Self {
+            manifest: args.manifest,
+            raw: args.raw,
+            claude_xml: args.claude_xml,
+            markdown: args.markdown,
+            target: args.target,
+        }
+    }
+
+    fn choice(&self) -> FormatChoice {
+        if let Some(manifest_format) = self.manifest {
+            FormatChoice::Manifest(manifest_format)
+        } else if self.raw {
+            FormatChoice::Raw
+        } else if self.claude_xml {
+            FormatChoice::Xml {
+                escape_closing_tags: false,
+            }
+        } else if self.markdown {
+            FormatChoice::Markdown { headings: false }
+        } else if let Some(target) = self.target {
+            // Explicit format flags above take precedence over the preset
+            match target {
+                Target::Claude => FormatChoice::Xml {
+                    escape_closing_tags: true,
+                },
+                Target::Gpt | Target::Gemini => FormatChoice::Markdown { headings: true },
+                Target::Deepseek => FormatChoice::Markdown { headings: false },
+            }
+        } else {
+            FormatChoice::Default
+        }
+    }
+}
+
+/// Where `write_output` takes the files from
+enum Source<'a> {
+    /// Files collected beforehand
+    Collected(&'a [FileEntry]),
+    /// Read while writing, keeping the walk's filter decisions here
+    Stream(&'a mut Decisions),
+}
+
+/// Render collected files with the chosen formatter into a string
+fn render_output(
+    processor: &FileProcessor,
+    paths: &[PathBuf],
+    files: &[FileEntry],
+    choice: FormatChoice,
+    args: &Cli,
+    max_tokens: Option<usize>,
+) -> Result<(String, usize)> {
+    let mut buffer = Vec::new();
+    let emitted = write_output(
+        processor,
+        paths,
+        Source::Collected(files),
+        choice,
+        args,
+        max_tokens,
+        &mut buffer,
+    )?;
+    let output = String::from_utf8(buffer).expect("formatters produce UTF-8");
+    Ok((output, emitted))
+}
+
+/// Render the files with the chosen formatter, writing to `out` as it goes.
+///
+/// Returns the number of files written.
+fn write_output(
+    processor: &FileProcessor,
+    paths: &[PathBuf],
+    source: Source<'_>,
+    choice: FormatChoice,
+    args: &Cli,
+    max_tokens: Option<usize>,
+    out: &mut dyn Write,
+) -> Result<usize> {
+    match choice {
+        FormatChoice::Manifest(manifest_format) => {
+            let mut formatter = ManifestFormatter::new(manifest_format);
+            if args.exact {
+                formatter = formatter.with_encodings();
+            }
+            render_with(
+                processor,
+                paths,
+                source,
+                formatter,
+                max_tokens,
+                args.budget_plan,
+                out,
+            )
+        }
+        FormatChoice::Raw => {
+            let mut formatter = RawFormatter::new(args.raw_separator.clone());
+            if args.exact {
+                formatter = formatter.with_exact();
+            }
+            render_with(
+                processor,
+                paths,
+                source,
+                formatter,
+                max_tokens,
+                args.budget_plan,
+                out,
+            )
+        }
+        FormatChoice::Xml {
+            escape_closing_tags,
+        } => {
+            let mut formatter = XmlFormatter::new();
+            if escape_closing_tags {
+                formatter = formatter.with_escaped_closing_tags();
+            }
+            render_with(
+                processor,
+                paths,
+                source,
+                formatter,
+                max_tokens,
+                args.budget_plan,
+                out,
+            )
+        }
+        FormatChoice::Markdown { headings } => {
+            let mut formatter = MarkdownFormatter::new();
+            if headings {
+                formatter = formatter.with_headings();
+            }
+            if args.front_matter {
+                formatter = formatter.with_front_matter();
+            }
+            render_with(
+                processor,
+                paths,
+                source,
+                formatter,
+                max_tokens,
+                args.budget_plan,
+                out,
+            )
+        }
+        FormatChoice::Default => {
+            let formatter = DefaultFormatter::new();
+            render_with(
+                processor,
+                paths,
+                source,
+                formatter,
+                max_tokens,
+                args.budget_plan,
+                out,
+            )
+        }
+    }
+}
+
+/// Format with `formatter` into `out`, first fitting the files into the token budget if one is set.
+///
+/// Returns the number of files written. With `budget_plan`, the plan describing
+/// the fit is written instead of the bundle.
+fn render_with<F: OutputFormatter + Clone>(
+    processor: &FileProcessor,
+    paths: &[PathBuf],
+    source: Source<'_>,
+    mut formatter: F,
+    max_tokens: Option<usize>,
+    budget_plan: bool,
+    out: &mut dyn Write,
+) -> Result<usize> {
+    let files = match source {
+        Source::Collected(files) => files,
+        Source::Stream(decisions) => {
+            let (written, walked) = processor.stream_files(paths, &mut formatter, out)?;
+            *decisions = walked;
+            return Ok(written);
+        }
+    };
+    let Some(max_tokens) = max_tokens else {
+        processor.write_files(paths, files, &mut formatter, out)?;
+        return Ok(files.len());
+    };
+
+    let fit = processor.fit_token_budget(paths, files, &formatter, max_tokens)?;
+    if budget_plan {
+        out.write_all(fit.plan(files).as_bytes())?;
+        return Ok(fit.files.len());
+    }
+    if let Some(truncated) = &fit.truncated {
+        eprintln!(
+            "Token budget: truncated {} to fit {} tokens",
+            truncated.display(),
+            max_tokens
+        );
+    }
+    if !fit.dropped.is_empty() {
+        eprintln!(
+            "Token budget: dropped {} file(s) to fit {} tokens:",
+            fit.dropped.len(),
+            max_tokens
+        );
+        for path in &fit.dropped {
+            eprintln!("  {}", path.display());
+        }
+    }
+    processor.write_files(paths, &fit.files, &mut formatter, out)?;
+    Ok(fit.files.len())
+}
+
+/// The format flags bound to each of `outputs` `-o` targets.
+///
+/// Each flag applies to the nearest `-o` written before it, or to the first one
+/// when it comes before them all; an `-o` with no flags of its own gets the
+/// default format. With a single `-o`, every format flag applies to it.
+fn output_format_flags(matches: &ArgMatches, outputs: usize) -> Vec<FormatFlags> {
+    let mut flags = vec![FormatFlags::default(); outputs];
+    let output_indices: Vec<usize> = matches
+        .indices_of("output_files")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    let owner = |index: usize| {
+        output_indices
+            .iter()
+            .rposition(|&output| output < index)
+            .unwrap_or(0)
+    };
+    if outputs == 0 {
+        return flags;
+    }
+
+    // Switches left unset still report their default value's index
+    let switch = |id: &str| {
+        matches
+            .get_flag(id)
+            .then(|| matches.indices_of(id))
+            .flatten()
+            .into_iter()
+            .flatten()
+            .map(owner)
+    };
+    for output in switch("claude_xml") {
+        flags[output].claude_xml = true;
+    }
+    for output in switch("markdown") {
+        flags[output].markdown = true;
+    }
+    for output in switch("raw") {
+        flags[output].raw = true;
+    }
+    if let Some(index) = matches.index_of("manifest") {
+        flags[owner(index)].manifest = matches.get_one("manifest").copied();
+    }
+    if let Some(index) = matches.index_of("target") {
+        flags[owner(index)].target = matches.get_one("target").copied();
+    }
+
+    flags
+}
+
+/// Values substituted into `-o` path templates
+struct TemplateValues {
+    branch: String,
+    date: String,
+    /// 1-based position of the output among repeated `-o` flags
+    n: usize,
+    tokens: usize,
+}
+
+/// Resolve `{date}`, `{branch}`, `{n}` and `{tokens}` placeholders in an output path.
+///
+/// The branch comes from the git repository containing the first input path.
+/// Parent directories are created for templated paths so scheduled jobs can
+/// write into fresh snapshot folders.
+fn resolve_output_template(
+    path: &Path,
+    paths: &[PathBuf],
+    n: usize,
+    output: &str,
+    time_zone: TimeZone,
+    tokenizer: Tokenizer,
+) -> PathBuf {
+    let template = path.to_string_lossy();
+    if !OUTPUT_PLACEHOLDERS.iter().any(|p| template.contains(p)) {
+        return path.to_path_buf();
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let branch = if template.contains("{branch}") {
+        current_branch(
+            paths
+                .first()
+                .map(PathBuf::as_path)
+                .unwrap_or(Path::new(".")),
+        )
+    } else {
+        String::new()
+    };
+    let values = TemplateValues {
+        branch,
+        date: time_zone.format_date(now),
+        n,
+        tokens: if template.contains("{tokens}") {
+            tokenizer.count(output)
+        } else {
+            0
+        },
+    };
+
+    let resolved = PathBuf::from(expand_output_template(&template, &values));
+    if let Some(parent) = resolved.parent().filter(|p| !p.as_os_str().is_empty()) {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    resolved
+}
+
+fn expand_output_template(template: &str, values: &TemplateValues) -> String {
+    // Branch names like `feature/x` must not introduce extra path components
+    let branch = values.branch.replace(['/', '\\'], "-");
+    template
+        .replace("{date}", &values.date)
+        .replace("{branch}", &branch)
+        .replace("{n}", &values.n.to_string())
+        .replace("{tokens}", &values.tokens.to_string())
+}
+
+/// Optional cargo features compiled into this binary
+const ENABLED_FEATURES: &[&str] = &[
+    #[cfg(feature = "sqlite")]
+    "sqlite",
+    #[cfg(feature = "clipboard")]
+    "clipboard",
+    #[cfg(feature = "tokens")]
+    "tokens",
+    #[cfg(feature = "signatures")]
+    "signatures",
+    #[cfg(feature = "parquet")]
+    "parquet",
+    #[cfg(feature = "transcode")]
+    "transcode",
+];
+
+fn feature_enabled(feature: &str) -> bool {
+    ENABLED_FEATURES.contains(&feature)
+}
+
+/// Check the requested flags against the compiled-in features.
+///
+/// Flags with no sensible fallback fail with an error naming the feature to
+/// enable; the rest degrade and return a note explaining what happens instead:
+///
+/// | Flag                                   | Feature     | Without it                |
+/// |----------------------------------------|-------------|---------------------------|
+/// | `--output-sqlite`                      | `sqlite`    | error                     |
+/// | `--signatures`                         | `signatures`| error                     |
+/// | `-p`/`--clipboard`                     | `clipboard` | print to stdout           |
+/// | `--tokens`, `--max-tokens`, `--model`, | `tokens`    | ~4 bytes per token        |
+/// | `--max-tokens-per-file`,               |             | estimate                  |
+/// | `--stats tokens`, exact `--tokenizer`  |             |                           |
+/// | `--sample-rows`                        | `parquet`   | Parquet stays binary      |
+/// | `--transcode`                          | `transcode` | non-UTF-8 stays binary    |
+fn missing_feature_notes(args: &Cli, enabled: impl Fn(&str) -> bool) -> Result<Vec<String>> {
+    if args.output_sqlite.is_some() && !enabled("sqlite") {
+        return Err(crate::FilesToPromptError::FeatureDisabled { feature: "sqlite" });
+    }
+    if args.signatures && !enabled("signatures") {
+        return Err(crate::FilesToPromptError::FeatureDisabled {
+            feature: "signatures",
+        });
+    }
+
+    let mut notes = Vec::new();
+    if args.clipboard && !enabled("clipboard") {
+        notes.push(
+            "clipboard support was not compiled in (cargo feature `clipboard`); printing to stdout instead"
+                .to_string(),
+        );
+    }
+    let counts_tokens = args.tokens
+        || args.max_tokens.is_some()
+        || args.max_tokens_per_file.is_some()
+        || args.model.is_some()
+        || args.stats == Some(StatsKind::Tokens)
+        || args.tokenizer.is_some_and(Tokenizer::is_exact);
+    if counts_tokens && args.tokenizer != Some(Tokenizer::Approx) && !enabled("tokens") {
+        notes.push(
+            "exact token counting was not compiled in (cargo feature `tokens`); using the ~4 bytes per token estimate"
+                .to_string(),
+        );
+    }
+    if args.sample_rows.is_some() && !enabled("parquet") {
+        notes.push(
+            "Parquet support was not compiled in (cargo feature `parquet`); --sample-rows only samples CSV/TSV"
+                .to_string(),
+        );
+    }
+    if args.transcode && !enabled("transcode") {
+        notes.push(
+            "transcoding was not compiled in (cargo feature `transcode`); non-UTF-8 files are treated as binary"
+                .to_string(),
+        );
+    }
+    Ok(notes)
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(output: &str) -> Result<()> {
+    let clipboard_error = |e: arboard::Error| crate::FilesToPromptError::Clipboard(e.to_string());
+    let mut clipboard = arboard::Clipboard::new().map_err(clipboard_error)?;
+    clipboard.set_text(output).map_err(clipboard_error)
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_output: &str) -> Result<()> {
+    Err(crate::FilesToPromptError::FeatureDisabled {
+        feature: "clipboard",
+    })
+}
+
+#[cfg(feature = "sqlite")]
+fn write_sqlite_output(processor: &FileProcessor, paths: &[PathBuf], db_path: &Path) -> Result<()> {
+    let files = processor.collect_files(paths)?;
+    crate::sqlite::write_sqlite(db_path, &files)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn write_sqlite_output(
+    _processor: &FileProcessor,
+    _paths: &[PathBuf],
+    _db_path: &Path,
+) -> Result<()> {
+    Err(crate::FilesToPromptError::FeatureDisabled { feature: "sqlite" })
+}
+
+/// Whether the inputs are narrowed down by a git query (`--staged`, `--diff-only`, `--git-since`, ...)
+fn selects_git_files(args: &Cli) -> bool {
+    args.git_tracked
+        || args.git_untracked
+        || args.staged
+        || args.pr_range.is_some()
+        || args.diff_only.is_some()
+        || args.git_since.is_some()
+        || args.git_until.is_some()
+}
+
+/// Files listed by `list` for each of `paths` that lie under that path and exist
+/// on disk, relative to the working directory when they are inside it. `None` if `list` fails,
+/// e.g. because a path is outside a git repository.
+fn select_git_files(
+    paths: &[PathBuf],
+    mut list: impl FnMut(&Path) -> Option<Vec<PathBuf>>,
+) -> Option<Vec<PathBuf>> {
+    let cwd = std::env::current_dir()
+        .and_then(std::fs::canonicalize)
+        .unwrap_or_default();
+    let mut selected = Vec::new();
+    for path in paths {
+        let root = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+        for file in list(path)? {
+            if file.starts_with(&root) && file.is_file() {
+                let file = file
+                    .strip_prefix(&cwd)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(file);
+                if !selected.contains(&file) {
+                    selected.push(file);
+                }
+            }
+        }
+    }
+    Some(selected)
+}
+
+/// Interpret Go-style `dir/...` and `dir/**` path arguments.
+///
+/// Returns the directory to walk and an optional file-name pattern built from
+/// whatever follows the marker in the last segment (`src/**.rs` → `src`, `*.rs`).
+/// Paths that exist literally are left alone.
+fn expand_recursive_sugar(path: &Path) -> Option<(PathBuf, Option<String>)> {
+    if path.exists() {
+        return None;
+    }
+
+    let text = path.to_str()?;
+    let segment_start = text.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
+    let (prefix, segment) = text.split_at(segment_start);
+
+    let rest = segment
+        .strip_prefix("...")
+        .or_else(|| segment.strip_prefix("**"))?;
+
+    let root = match prefix.trim_end_matches(['/', '\\']) {
+        "" if prefix.is_empty() => PathBuf::from("."),
+        "" => PathBuf::from(prefix),
+        trimmed => PathBuf::from(trimmed),
+    };
+    let pattern = (!rest.is_empty()).then(|| format!("*{}", rest));
+
+    Some((root, pattern))
+}
+
+/// Print the `fuse usage` report from the local run history
+fn print_usage_report() -> Result<()> {
+    let Some(history_path) = usage::history_path() else {
+        println!(
+            "Usage history is disabled. Set {}=<file> to record runs locally.",
+            HISTORY_ENV_VAR
+        );
+        return Ok(());
+    };
+
+    let records = usage::read_history(&history_path)?;
+    println!("{}", usage::render_report(&records));
+    Ok(())
+}
+
+/// Print the JSON Schema for `fuse schema <name>`
+fn print_schema(name: Option<&str>) -> Result<()> {
+    match name.and_then(schema::schema) {
+        Some(schema) => {
+            println!("{}", schema);
+            Ok(())
+        }
+        None => {
+            let names = schema::SCHEMAS
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ");
+            eprintln!("Usage: fuse schema <NAME> (available: {})", names);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_feature_notes() {
+        let parse = |args: &[&str]| Cli::parse_from(["fuse", "src"].iter().chain(args));
+        let nothing_enabled = |_: &str| false;
+
+        assert!(missing_feature_notes(&parse(&[]), nothing_enabled)
+            .unwrap()
+            .is_empty());
+        assert!(
+            missing_feature_notes(&parse(&["--output-sqlite", "db"]), nothing_enabled).is_err()
+        );
+        assert!(missing_feature_notes(&parse(&["--signatures"]), nothing_enabled).is_err());
+
+        let notes = missing_feature_notes(&parse(&["-p", "--tokens"]), nothing_enabled).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert!(notes[0].contains("`clipboard`"));
+        assert!(notes[1].contains("`tokens`"));
+
+        let approx = parse(&["--tokens", "--tokenizer", "approx"]);
+        assert!(missing_feature_notes(&approx, nothing_enabled)
+            .unwrap()
+            .is_empty());
+        assert!(missing_feature_notes(&parse(&["-p"]), |_| true)
+            .unwrap()
+            .is_empty());
+    }
+
+    fn expand(arg: &str) -> Option<(PathBuf, Option<String>)> {
+        expand_recursive_sugar(Path::new(arg))
+    }
+
+    #[test]
+    fn test_expand_recursive_sugar() {
+        assert_eq!(expand("src/..."), Some((PathBuf::from("src"), None)));
+        assert_eq!(expand("src/**"), Some((PathBuf::from("src"), None)));
+        assert_eq!(expand("./..."), Some((PathBuf::from("."), None)));
+        assert_eq!(expand("..."), Some((PathBuf::from("."), None)));
+        assert_eq!(
+            expand("src/nested/**.rs"),
+            Some((PathBuf::from("src/nested"), Some("*.rs".to_string())))
+        );
+        assert_eq!(
+            expand("pkg/..._test.go"),
+            Some((PathBuf::from("pkg"), Some("*_test.go".to_string())))
+        );
+        assert_eq!(expand("/..."), Some((PathBuf::from("/"), None)));
+        assert_eq!(expand("src/lib.rs"), None);
+    }
+
+    #[test]
+    fn test_output_format_flags() {
+        let flags_for = |args: &[&str]| {
+            let matches = Cli::command().try_get_matches_from(args).unwrap();
+            let outputs = matches
+                .get_many::<PathBuf>("output_files")
+                .map_or(0, |values| values.count());
+            output_format_flags(&matches, outputs)
+        };
+
+        // A flag binds to the -o before it; an -o without flags gets the default
+        let flags = flags_for(&["fuse", "src", "-o", "a.txt", "-o", "b.md", "-m"]);
+        assert_eq!(flags[0].choice(), FormatChoice::Default);
+        assert_eq!(
+            flags[1].choice(),
+            FormatChoice::Markdown { headings: false }
+        );
+
+        let flags = flags_for(&[
+            "fuse",
+            "src",
+            "-n",
+            "--cxml",
+            "-o",
+            "a.xml",
+            "-o",
+            "b.md",
+            "-m",
+            "--output=c.csv",
+            "--manifest",
+            "csv",
+            "-o",
+            "d.txt",
+        ]);
+        assert_eq!(
+            flags[0].choice(),
+            FormatChoice::Xml {
+                escape_closing_tags: false
+            }
+        );
+        assert_eq!(
+            flags[1].choice(),
+            FormatChoice::Markdown { headings: false }
+        );
+        assert_eq!(
+            flags[2].choice(),
+            FormatChoice::Manifest(ManifestFormat::Csv)
+        );
+        assert_eq!(flags[3], FormatFlags::default());
+
+        // Short clusters bind like the separate flags
+        let flags = flags_for(&["fuse", "src", "-o", "a.xml", "-c", "-mo", "b.md"]);
+        assert!(flags[0].claude_xml && flags[0].markdown);
+        assert_eq!(flags[1], FormatFlags::default());
+
+        // With a single -o, flags anywhere apply to it
+        let flags = flags_for(&["fuse", "src", "-o", "a.md", "--markdown"]);
+        assert_eq!(
+            flags[0].choice(),
+            FormatChoice::Markdown { headings: false }
+        );
+    }
+
+    #[test]
+    fn test_expand_output_template() {
+        let values = TemplateValues {
+            branch: "feature/x".to_string(),
+            date: "2024-03-01".to_string(),
+            n: 2,
+            tokens: 1234,
+        };
+        assert_eq!(
+            expand_output_template("bundles/{branch}-{date}.md", &values),
+            "bundles/feature-x-2024-03-01.md"
+        );
+        assert_eq!(
+            expand_output_template("ctx-{n}-{tokens}t.md", &values),
+            "ctx-2-1234t.md"
+        );
+        assert_eq!(expand_output_template("out.md", &values), "out.md");
+    }
+}
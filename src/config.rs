@@ -0,0 +1,184 @@
+//! Project configuration read from a `.fuserc` file
+//!
+//! The file is looked up in the current directory and its ancestors, so a team
+//! can commit one at the repository root. It currently defines path aliases:
+//!
+//! ```text
+//! # .fuserc
+//! @core = ["src/core", "proto/"]
+//! @web = "web/src"
+//! ```
+//!
+//! `fuse @core @web -e ts` then expands to the listed paths.
+
+use crate::{FilesToPromptError, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the project configuration
+pub const CONFIG_FILE_NAME: &str = ".fuserc";
+
+/// Parsed `.fuserc` contents
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Alias name (without `@`) to the paths it stands for
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// Directory containing the config file; alias paths are relative to it
+    pub base_dir: PathBuf,
+}
+
+impl Config {
+    /// Find and parse the nearest `.fuserc` in `start` or its ancestors
+    pub fn discover(start: &Path) -> Result<Option<Self>> {
+        for dir in start.ancestors() {
+            let path = dir.join(CONFIG_FILE_NAME);
+            if path.is_file() {
+                let mut config = Self::parse(&fs::read_to_string(&path)?)?;
+                config.base_dir = dir.to_path_buf();
+                return Ok(Some(config));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Parse config text; blank lines and `#` comments are ignored
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config = Self::default();
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |reason: &str| {
+                FilesToPromptError::Config(format!(
+                    "{} line {}: {}",
+                    CONFIG_FILE_NAME,
+                    number + 1,
+                    reason
+                ))
+            };
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected `@name = [\"path\", ...]`"))?;
+            let name = name
+                .trim()
+                .strip_prefix('@')
+                .filter(|name| !name.is_empty())
+                .ok_or_else(|| invalid("alias names must start with `@`"))?;
+
+            let value = value.trim();
+            let list = value
+                .strip_prefix('[')
+                .and_then(|v| v.strip_suffix(']'))
+                .unwrap_or(value);
+            let paths = list
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| {
+                    item.strip_prefix('"')
+                        .and_then(|item| item.strip_suffix('"'))
+                        .map(str::to_string)
+                        .ok_or_else(|| invalid("paths must be double-quoted"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            config.aliases.insert(name.to_string(), paths);
+        }
+
+        Ok(config)
+    }
+
+    /// Replace `@alias` arguments with the paths they stand for
+    pub fn expand_aliases(&self, paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let mut expanded = Vec::new();
+
+        for path in paths {
+            let Some(name) = path.to_str().and_then(|p| p.strip_prefix('@')) else {
+                expanded.push(path);
+                continue;
+            };
+            let targets = self.aliases.get(name).ok_or_else(|| {
+                FilesToPromptError::Config(format!(
+                    "unknown alias @{} (defined: {})",
+                    name,
+                    self.aliases
+                        .keys()
+                        .map(|key| format!("@{}", key))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            })?;
+
+            for target in targets {
+                if self.base_dir.as_os_str().is_empty() || self.base_dir == cwd {
+                    expanded.push(PathBuf::from(target));
+                } else {
+                    expanded.push(self.base_dir.join(target));
+                }
+            }
+        }
+
+        Ok(expanded)
+    }
+}
+
+/// Expand `@alias` arguments using the nearest `.fuserc`, if any argument needs it
+pub fn expand_path_aliases(paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let uses_aliases = paths
+        .iter()
+        .any(|p| p.to_str().is_some_and(|p| p.starts_with('@')));
+    if !uses_aliases {
+        return Ok(paths);
+    }
+
+    let cwd = std::env::current_dir()?;
+    match Config::discover(&cwd)? {
+        Some(config) => config.expand_aliases(paths),
+        None => Err(FilesToPromptError::Config(format!(
+            "@aliases need a {} file in this directory or a parent",
+            CONFIG_FILE_NAME
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_aliases() {
+        let config = Config::parse(
+            "# shared areas\n@core = [\"src/core\", \"proto/\"]\n\n@web = \"web/src\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.aliases["core"], vec!["src/core", "proto/"]);
+        assert_eq!(config.aliases["web"], vec!["web/src"]);
+
+        assert!(Config::parse("core = [\"src\"]").is_err());
+        assert!(Config::parse("@core = [src]").is_err());
+    }
+
+    #[test]
+    fn test_expand_aliases() {
+        let config = Config::parse("@core = [\"src/core\", \"proto/\"]").unwrap();
+        let expanded = config
+            .expand_aliases(vec![PathBuf::from("@core"), PathBuf::from("README.md")])
+            .unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                PathBuf::from("src/core"),
+                PathBuf::from("proto/"),
+                PathBuf::from("README.md")
+            ]
+        );
+
+        let err = config.expand_aliases(vec![PathBuf::from("@web")]);
+        assert!(err.unwrap_err().to_string().contains("@core"));
+    }
+}
@@ -0,0 +1,106 @@
+//! Transcoding of non-UTF-8 text (`--transcode`, requires the `transcode` feature)
+//!
+//! A byte order mark decides the encoding when present. UTF-16 without one is
+//! recognised by its NUL-interleaved ASCII; anything else is left to chardetng
+//! (Latin-1/Windows-1252, Shift_JIS, GBK, ...). Decoded text that is still
+//! full of control characters was binary after all.
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+
+/// How many leading bytes are inspected for BOM-less UTF-16
+const UTF16_SAMPLE_SIZE: usize = 1024;
+
+/// Decode `bytes` to UTF-8, or `None` if they don't look like text in any encoding
+pub fn transcode(bytes: &[u8]) -> Option<String> {
+    let encoding = match Encoding::for_bom(bytes) {
+        Some((encoding, _)) => encoding,
+        None => match bomless_utf16(bytes) {
+            Some(encoding) => encoding,
+            None if bytes.contains(&0) => return None,
+            None => {
+                let mut detector = EncodingDetector::new();
+                detector.feed(bytes, true);
+                detector.guess(None, true)
+            }
+        },
+    };
+
+    let (text, _, malformed) = encoding.decode(bytes);
+    if malformed || mostly_control(&text) {
+        return None;
+    }
+    Some(text.into_owned())
+}
+
+/// UTF-16 without a BOM: most of the high (or low) bytes of the leading code
+/// units are NUL, as they are for ASCII text, and the others never are
+fn bomless_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(UTF16_SAMPLE_SIZE) & !1];
+    if sample.is_empty() {
+        return None;
+    }
+    let units = sample.len() / 2;
+    let nul_at = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let (even, odd) = (nul_at(0), nul_at(1));
+    if odd * 2 > units && even == 0 {
+        Some(UTF_16LE)
+    } else if even * 2 > units && odd == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// More than 10% control characters other than whitespace, as `is_binary` judges raw bytes
+fn mostly_control(text: &str) -> bool {
+    let total = text.chars().count();
+    let control = text
+        .chars()
+        .filter(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\x0C'))
+        .count();
+    total > 0 && control * 100 / total > 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str, bom: bool) -> Vec<u8> {
+        let mut bytes = if bom { vec![0xFF, 0xFE] } else { Vec::new() };
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes
+    }
+
+    #[test]
+    fn test_transcode_utf16() {
+        let text = "Option Explicit\r\nDim größe\r\n";
+        assert_eq!(transcode(&utf16le(text, true)).as_deref(), Some(text));
+        assert_eq!(transcode(&utf16le(text, false)).as_deref(), Some(text));
+    }
+
+    #[test]
+    fn test_transcode_latin1() {
+        let bytes = b"/* Cr\xe9\xe9 par Fran\xe7ois, d\xe9cembre */\nint x;\n";
+        assert_eq!(
+            transcode(bytes).as_deref(),
+            Some("/* Créé par François, décembre */\nint x;\n")
+        );
+    }
+
+    #[test]
+    fn test_binary_rejected() {
+        assert_eq!(transcode(b"\x7fELF\x02\x01\x01\x00\x00\x00\x00"), None);
+        assert_eq!(
+            transcode(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x80, 0x81]),
+            None
+        );
+    }
+}
@@ -1,6 +1,9 @@
 //! File extension to language mapping for syntax highlighting
 
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
 
 /// Get the mapping of file extensions to language names
 pub fn get_language_map() -> HashMap<&'static str, &'static str> {
@@ -30,6 +33,148 @@ pub fn get_language_for_extension(extension: &str) -> &str {
     get_language_map().get(extension).unwrap_or(&"")
 }
 
+/// Check whether a file name ends with any of the given extensions.
+///
+/// Extensions may be given with or without a leading dot and may be compound
+/// (`tar.gz`, `d.ts`, `blade.php`); they are matched as filename suffixes so
+/// `-e gz` still matches `archive.tar.gz`. An empty list matches every file.
+pub fn matches_extensions(path: &Path, extensions: &[String]) -> bool {
+    if extensions.is_empty() {
+        return true;
+    }
+
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+
+    extensions.iter().any(|ext| {
+        let ext = ext.strip_prefix('.').unwrap_or(ext);
+        // Require a non-empty stem so a dotfile like `.gz` isn't treated as having extension `gz`
+        name.len() > ext.len() + 1
+            && name.ends_with(ext)
+            && name[..name.len() - ext.len()].ends_with('.')
+    })
+}
+
+/// Bytes read from the start of a file when sniffing its language
+const SNIFF_BYTES: usize = 256;
+
+/// Extensions an extensionless file would have, judged by its first line:
+/// the interpreter of a `#!` line, or an `<?xml`/`<?php`/`<!DOCTYPE html` opener.
+pub fn sniff_extensions(path: &Path) -> &'static [&'static str] {
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    let read =
+        File::open(path).and_then(|file| file.take(SNIFF_BYTES as u64).read_to_end(&mut head));
+    if read.is_err() {
+        return &[];
+    }
+    let head = String::from_utf8_lossy(&head);
+    let first_line = head.lines().next().unwrap_or("").trim();
+
+    if let Some(shebang) = first_line.strip_prefix("#!") {
+        let mut words = shebang.split_whitespace();
+        let mut interpreter = words.next().unwrap_or("").rsplit('/').next().unwrap_or("");
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-')).unwrap_or("");
+        }
+        let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        return match interpreter {
+            "python" | "pypy" => &["py"],
+            "sh" | "bash" | "dash" | "ash" | "ksh" => &["sh", "bash"],
+            "zsh" => &["zsh", "sh"],
+            "fish" => &["fish"],
+            "node" | "nodejs" | "deno" | "bun" => &["js"],
+            "ts-node" | "tsx" => &["ts"],
+            "ruby" => &["rb"],
+            "perl" => &["pl"],
+            "php" => &["php"],
+            "lua" => &["lua"],
+            _ => &[],
+        };
+    }
+
+    let lower = first_line.to_ascii_lowercase();
+    if lower.starts_with("<?xml") {
+        &["xml"]
+    } else if lower.starts_with("<?php") {
+        &["php"]
+    } else if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        &["html"]
+    } else {
+        &[]
+    }
+}
+
+/// Well-known files without a useful extension, kept by `-e` filters anyway
+pub const KNOWN_FILE_NAMES: &[&str] = &[
+    "Makefile",
+    "GNUmakefile",
+    "Dockerfile",
+    "Containerfile",
+    "Justfile",
+    "justfile",
+    "Rakefile",
+    "Gemfile",
+    "Procfile",
+    "Vagrantfile",
+    "Jenkinsfile",
+    "Brewfile",
+    "CODEOWNERS",
+    "LICENSE",
+    "COPYING",
+    "NOTICE",
+    ".gitignore",
+    ".gitattributes",
+    ".dockerignore",
+    ".editorconfig",
+];
+
+/// The `-e` filter: files with one of the extensions, plus well-known names
+/// and, with `sniff`, extensionless files whose first line names a requested language
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtensionFilter {
+    pub extensions: Vec<String>,
+    /// Exact file names that pass whatever the extensions
+    pub names: Vec<String>,
+    /// Sniff extensionless files (`--sniff`)
+    pub sniff: bool,
+}
+
+impl ExtensionFilter {
+    /// Filter on `extensions`, keeping [`KNOWN_FILE_NAMES`]
+    pub fn new(extensions: Vec<String>) -> Self {
+        Self {
+            extensions,
+            names: KNOWN_FILE_NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            sniff: false,
+        }
+    }
+
+    /// Does the file at `path` pass? Everything does when no extensions are given.
+    pub fn matches(&self, path: &Path) -> bool {
+        if matches_extensions(path, &self.extensions) {
+            return true;
+        }
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        if self.names.iter().any(|known| known == name) {
+            return true;
+        }
+        if !self.sniff || name.trim_start_matches('.').contains('.') {
+            return false;
+        }
+
+        let sniffed = sniff_extensions(path);
+        self.extensions
+            .iter()
+            .any(|ext| sniffed.contains(&ext.strip_prefix('.').unwrap_or(ext)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -47,6 +192,73 @@ mod tests {
         assert_eq!(get_language_for_extension("yml"), "yaml");
     }
 
+    #[test]
+    fn test_matches_simple_extensions() {
+        let exts = vec!["py".to_string(), ".rs".to_string()];
+        assert!(matches_extensions(Path::new("src/main.py"), &exts));
+        assert!(matches_extensions(Path::new("lib.rs"), &exts));
+        assert!(!matches_extensions(Path::new("lib.rsx"), &exts));
+        assert!(!matches_extensions(Path::new("Makefile"), &exts));
+        assert!(!matches_extensions(Path::new(".rs"), &exts));
+        assert!(matches_extensions(Path::new("anything"), &[]));
+    }
+
+    #[test]
+    fn test_matches_compound_extensions() {
+        let exts = vec!["d.ts".to_string(), "tar.gz".to_string()];
+        assert!(matches_extensions(Path::new("types/index.d.ts"), &exts));
+        assert!(!matches_extensions(Path::new("index.ts"), &exts));
+        assert!(!matches_extensions(Path::new("bad.ts"), &exts));
+        assert!(matches_extensions(Path::new("release.tar.gz"), &exts));
+        assert!(matches_extensions(
+            Path::new("archive.tar.gz"),
+            &["gz".to_string()]
+        ));
+    }
+
+    #[test]
+    fn test_sniff_extensionless_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+        let deploy = write("deploy", "#!/usr/bin/env bash\nset -e\n");
+        let run = write("run", "#!/usr/bin/python3.11 -u\nprint()\n");
+        let feed = write("feed", "<?xml version=\"1.0\"?>\n<rss/>\n");
+        let notes = write("notes", "just some text\n");
+        let script = write("script.txt", "#!/bin/sh\n");
+
+        assert_eq!(sniff_extensions(&deploy), &["sh", "bash"]);
+        assert_eq!(sniff_extensions(&run), &["py"]);
+        assert_eq!(sniff_extensions(&feed), &["xml"]);
+        assert!(sniff_extensions(&notes).is_empty());
+
+        let mut filter = ExtensionFilter::new(vec!["py".to_string(), ".sh".to_string()]);
+        assert!(!filter.matches(&run));
+        filter.sniff = true;
+        assert!(filter.matches(&deploy));
+        assert!(filter.matches(&run));
+        assert!(!filter.matches(&feed));
+        assert!(!filter.matches(&notes));
+        assert!(!filter.matches(&script));
+    }
+
+    #[test]
+    fn test_known_file_names() {
+        let mut filter = ExtensionFilter::new(vec!["rs".to_string()]);
+        assert!(filter.matches(Path::new("Makefile")));
+        assert!(filter.matches(Path::new("docker/Dockerfile")));
+        assert!(filter.matches(Path::new(".gitignore")));
+        assert!(!filter.matches(Path::new("Makefile.am")));
+        assert!(!filter.matches(Path::new("notes")));
+
+        filter.names = vec!["BUILD.bazel".to_string()];
+        assert!(!filter.matches(Path::new("Makefile")));
+        assert!(filter.matches(Path::new("pkg/BUILD.bazel")));
+    }
+
     #[test]
     fn test_unknown_extension() {
         assert_eq!(get_language_for_extension("unknown"), "");
@@ -1,25 +1,506 @@
 //! Core file processing and directory traversal logic
 
-use crate::ignore::CustomIgnore;
+use crate::cache::FileCache;
+use crate::extensions::ExtensionFilter;
+use crate::filter_cmd::FilterCmd;
+use crate::ignore::{
+    force_included_files, is_default_ignored, is_lockfile, is_sensitive, is_submodule,
+    is_vendored_dir, CustomIgnore, GitAttributes, IncludePatterns, OutputExclusions, RootIncludes,
+    TestFilter,
+};
+use crate::ordering::OrderingStrategy;
 use crate::output::OutputFormatter;
+use crate::report::{format_size, SkipReport};
+#[cfg(feature = "parquet")]
+use crate::sample::is_parquet;
+use crate::transform::{ContentTransform, HeadTail};
+use crate::transform_cmd::TransformCmd;
 use crate::tree::TreeGenerator;
+use crate::utils::{hex_dump, pretty_json, sha256_hex, strip_ansi, Tokenizer};
 use crate::{FilesToPromptError, Result, TocMode};
 use ignore::WalkBuilder;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A file that passed all filters, with its (transformed) content
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub content: String,
+    /// Directory preamble (`PROMPT.md`/`CONTEXT.md`) emitted ahead of its directory's files
+    pub preamble: bool,
+    /// Index of the input path this file was found under
+    pub root: usize,
+}
+
+impl FileEntry {
+    pub fn new(path: PathBuf, content: String) -> Self {
+        Self {
+            path,
+            content,
+            preamble: false,
+            root: 0,
+        }
+    }
+}
+
+/// A formatted bundle together with the files it contains
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    pub output: String,
+    pub files: Vec<FileEntry>,
+    /// Tokenizer of the processor that made the bundle
+    pub tokenizer: Tokenizer,
+}
+
+impl Bundle {
+    /// Token count of the output with the processor's tokenizer
+    pub fn tokens(&self) -> usize {
+        self.tokenizer.count(&self.output)
+    }
+}
+
+/// Files selected to fit a token budget, and what had to give
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BudgetFit {
+    /// Files to format, the last possibly truncated
+    pub files: Vec<FileEntry>,
+    /// File cut short to use the remaining budget
+    pub truncated: Option<PathBuf>,
+    /// Files left out entirely
+    pub dropped: Vec<PathBuf>,
+}
+
+impl BudgetFit {
+    /// One line per file of `files` (the input to `fit_token_budget`) saying whether it
+    /// is included, truncated or dropped, followed by the totals
+    pub fn plan(&self, files: &[FileEntry]) -> String {
+        let mut plan = String::new();
+        for file in files {
+            let status = if self.truncated.as_ref() == Some(&file.path) {
+                "truncate"
+            } else if self.dropped.contains(&file.path) {
+                "drop"
+            } else {
+                "include"
+            };
+            plan.push_str(&format!("{:<9} {}\n", status, file.path.display()));
+        }
+
+        let truncated = usize::from(self.truncated.is_some());
+        plan.push_str(&format!(
+            "\n{} included, {} truncated, {} dropped\n",
+            self.files.len() - truncated,
+            truncated,
+            self.dropped.len()
+        ));
+        plan
+    }
+}
+
+/// What happens to a binary file (`--binary`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BinaryMode {
+    /// Leave it out silently
+    Skip,
+    /// Leave it out with a warning on stderr
+    #[default]
+    Warn,
+    /// Keep a one-line stub with the file size in place of the content
+    Placeholder,
+    /// Keep a hex dump of the first few kilobytes (`--hex-bytes`)
+    Hex,
+    /// Fail the run
+    Error,
+}
+
+/// Bytes of a binary file shown by `--binary hex` unless `--hex-bytes` says otherwise
+pub const HEX_DUMP_BYTES: usize = 4096;
+
+/// How a file fares when `fit_token_budget` has to cut
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Dropped first
+    Low,
+    Normal,
+    /// Kept first and never truncated
+    High,
+}
+
+/// Filter that removed a candidate file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterReason {
+    Extension,
+    Hidden,
+    Gitignore,
+    IgnorePattern,
+    IncludePattern,
+    PathPattern,
+    PreviousOutput,
+    Binary,
+    Content,
+    Submodule,
+    Generated,
+    DefaultIgnore,
+    Lockfile,
+    Sensitive,
+    Vendored,
+    Empty,
+    Tests,
+    FilterCmd,
+}
+
+impl FilterReason {
+    /// Stable snake_case name used in traces
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FilterReason::Extension => "extension",
+            FilterReason::Hidden => "hidden",
+            FilterReason::Gitignore => "gitignore",
+            FilterReason::IgnorePattern => "ignore_pattern",
+            FilterReason::IncludePattern => "include_pattern",
+            FilterReason::PathPattern => "path_pattern",
+            FilterReason::PreviousOutput => "previous_output",
+            FilterReason::Binary => "binary",
+            FilterReason::Content => "content",
+            FilterReason::Submodule => "submodule",
+            FilterReason::Generated => "generated",
+            FilterReason::DefaultIgnore => "default_ignore",
+            FilterReason::Lockfile => "lockfile",
+            FilterReason::Sensitive => "sensitive",
+            FilterReason::Vendored => "vendored",
+            FilterReason::Empty => "empty",
+            FilterReason::Tests => "tests",
+            FilterReason::FilterCmd => "filter_cmd",
+        }
+    }
+}
+
+/// How many candidate files each filter removed, used to explain an empty result.
+///
+/// A directory the walker pruned counts once, however many files it holds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilterReport {
+    pub extension: usize,
+    pub hidden: usize,
+    pub gitignore: usize,
+    pub ignore_pattern: usize,
+    pub include_pattern: usize,
+    pub path_pattern: usize,
+    pub previous_output: usize,
+    pub binary: usize,
+    pub content: usize,
+    pub submodule: usize,
+    pub generated: usize,
+    pub default_ignore: usize,
+    pub lockfile: usize,
+    pub sensitive: usize,
+    pub vendored: usize,
+    pub empty: usize,
+    pub tests: usize,
+    pub filter_cmd: usize,
+}
+
+impl FilterReport {
+    fn record(&mut self, reason: FilterReason) {
+        match reason {
+            FilterReason::Extension => self.extension += 1,
+            FilterReason::Hidden => self.hidden += 1,
+            FilterReason::Gitignore => self.gitignore += 1,
+            FilterReason::IgnorePattern => self.ignore_pattern += 1,
+            FilterReason::IncludePattern => self.include_pattern += 1,
+            FilterReason::PathPattern => self.path_pattern += 1,
+            FilterReason::PreviousOutput => self.previous_output += 1,
+            FilterReason::Binary => self.binary += 1,
+            FilterReason::Content => self.content += 1,
+            FilterReason::Submodule => self.submodule += 1,
+            FilterReason::Generated => self.generated += 1,
+            FilterReason::DefaultIgnore => self.default_ignore += 1,
+            FilterReason::Lockfile => self.lockfile += 1,
+            FilterReason::Sensitive => self.sensitive += 1,
+            FilterReason::Vendored => self.vendored += 1,
+            FilterReason::Empty => self.empty += 1,
+            FilterReason::Tests => self.tests += 1,
+            FilterReason::FilterCmd => self.filter_cmd += 1,
+        }
+    }
+
+    /// The filter that removed the most files, if any removed something
+    pub fn dominant(&self) -> Option<(FilterReason, usize)> {
+        [
+            (FilterReason::Extension, self.extension),
+            (FilterReason::Hidden, self.hidden),
+            (FilterReason::Gitignore, self.gitignore),
+            (FilterReason::IgnorePattern, self.ignore_pattern),
+            (FilterReason::IncludePattern, self.include_pattern),
+            (FilterReason::PathPattern, self.path_pattern),
+            (FilterReason::PreviousOutput, self.previous_output),
+            (FilterReason::Binary, self.binary),
+            (FilterReason::Content, self.content),
+            (FilterReason::Submodule, self.submodule),
+            (FilterReason::Generated, self.generated),
+            (FilterReason::DefaultIgnore, self.default_ignore),
+            (FilterReason::Lockfile, self.lockfile),
+            (FilterReason::Sensitive, self.sensitive),
+            (FilterReason::Vendored, self.vendored),
+            (FilterReason::Empty, self.empty),
+            (FilterReason::Tests, self.tests),
+            (FilterReason::FilterCmd, self.filter_cmd),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .max_by_key(|(_, count)| *count)
+    }
+}
+
+/// A filter decision made while walking
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    pub path: PathBuf,
+    /// Filter that dropped the file, `None` if it was included
+    pub reason: Option<FilterReason>,
+    /// Time spent deciding, reading the file included
+    pub elapsed: Duration,
+}
+
+/// Every filter decision of one `collect_files` walk, for `--trace` and for
+/// explaining an empty result
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Decisions {
+    /// Files the walk visited, in walk order
+    pub files: Vec<Decision>,
+    /// Directories and files the walker pruned before yielding them, each
+    /// recorded once rather than for everything below it
+    pub pruned: Vec<(PathBuf, FilterReason)>,
+}
+
+impl Decisions {
+    /// Count which filter removed each file or pruned entry
+    pub fn report(&self) -> FilterReport {
+        let mut report = FilterReport::default();
+        let visited = self.files.iter().filter_map(|decision| decision.reason);
+        for reason in visited.chain(self.pruned.iter().map(|(_, reason)| *reason)) {
+            report.record(reason);
+        }
+        report
+    }
+}
+
+/// Entries pruned by the walker, shared with its `filter_entry` callback
+type Pruned = Arc<Mutex<Vec<(PathBuf, FilterReason)>>>;
+
+/// Receives each file of a streamed walk as soon as it is read
+type Sink<'a> = dyn FnMut(&FileEntry) -> Result<()> + 'a;
+
+/// State of one walk over the input paths
+struct Walk<'a> {
+    files: Vec<FileEntry>,
+    skips: SkipReport,
+    /// Tokens of the files read so far, tallied for the read budget
+    tokens_read: usize,
+    decisions: Decisions,
+    /// Index of the input path being walked
+    root: usize,
+    /// Where files go when streaming, instead of being kept in `files`
+    sink: Option<&'a mut Sink<'a>>,
+    /// When set, files that would be read are only listed here, for a
+    /// `--filter-cmd` that takes every candidate at once
+    candidates: Option<Vec<PathBuf>>,
+    /// Paths such a command approved up front
+    approved: Option<HashSet<PathBuf>>,
+}
+
+impl<'a> Walk<'a> {
+    fn new(verbose: bool, sink: Option<&'a mut Sink<'a>>) -> Self {
+        Self {
+            files: Vec::new(),
+            skips: SkipReport::new(verbose),
+            tokens_read: 0,
+            decisions: Decisions::default(),
+            root: 0,
+            sink,
+            candidates: None,
+            approved: None,
+        }
+    }
+
+    fn decide(&mut self, path: &Path, reason: Option<FilterReason>, started: Instant) {
+        self.decisions.files.push(Decision {
+            path: path.to_path_buf(),
+            reason,
+            elapsed: started.elapsed(),
+        });
+    }
+
+    /// Tag the files read since `start` with their root and pass them to the sink
+    fn settle(&mut self, start: usize) -> Result<()> {
+        for file in &mut self.files[start..] {
+            file.root = self.root;
+        }
+        if let Some(sink) = &mut self.sink {
+            for file in self.files.drain(start..) {
+                sink(&file)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// File names recognised as directory preambles by `--dir-preambles`
+pub const PREAMBLE_FILE_NAMES: &[&str] = &["PROMPT.md", "CONTEXT.md"];
+
+/// File name endings of minified bundles
+const MINIFIED_SUFFIXES: &[&str] = &[".min.js", ".min.mjs", ".min.css"];
+
+/// Lines longer than this only come out of minifiers and code generators
+const GENERATED_LINE_LENGTH: usize = 5000;
+
+/// With `--pretty-json`, a `.json` file with a line this long counts as minified
+const MINIFIED_JSON_LINE_LENGTH: usize = 200;
+
+/// How many leading lines are searched for a "DO NOT EDIT" marker
+const GENERATED_HEADER_LINES: usize = 5;
 
 /// Handles file processing with filtering and directory traversal
 pub struct FileProcessor {
-    extensions: Vec<String>,
+    extension_filter: ExtensionFilter,
     include_hidden: bool,
     ignore_gitignore: bool,
+    include_submodules: bool,
+    default_ignores: bool,
+    include_lockfiles: bool,
+    allow_sensitive: bool,
+    strict: bool,
+    include_vendored: bool,
+    include_generated: bool,
+    max_depth: Option<usize>,
+    git_attributes: GitAttributes,
     line_numbers: bool,
     toc_mode: Option<TocMode>,
+    readme_first: bool,
+    dir_preambles: bool,
+    collections: bool,
+    dedupe: bool,
+    filter_cmd: Option<FilterCmd>,
+    transform_cmd: Option<TransformCmd>,
+    sample_rows: Option<usize>,
+    transcode: bool,
+    strip_ansi: bool,
+    pretty_json: bool,
+    toc_tokens: bool,
+    verbose: bool,
+    tokenizer: Tokenizer,
+    prefixes: Vec<String>,
+    suffixes: Vec<String>,
     custom_ignore: CustomIgnore,
+    include_patterns: IncludePatterns,
+    test_filter: TestFilter,
+    force_include: IncludePatterns,
+    root_includes: RootIncludes,
+    output_exclusions: OutputExclusions,
+    priorities: Vec<(glob::Pattern, Priority)>,
+    binary_mode: BinaryMode,
+    hex_dump_bytes: usize,
+    /// Drop zero-byte and whitespace-only files (`--skip-empty`)
+    skip_empty: bool,
+    /// Content must match every one of these (`--contains`)
+    contains: Vec<Regex>,
+    /// Content must match none of these (`--not-contains`)
+    not_contains: Vec<Regex>,
+    transforms: Vec<Box<dyn ContentTransform>>,
+    ordering: Option<Box<dyn OrderingStrategy>>,
+    /// Content of unchanged files from earlier runs (`--cache`)
+    cache: Option<Mutex<FileCache>>,
+    /// Stop walking once the files read hold more tokens than this (`--max-tokens`)
+    read_budget: Option<usize>,
+}
+
+/// Builds a `FileProcessor` from named options rather than `new`'s positional ones.
+///
+/// Everything else is set with the `with_*` methods of the built processor.
+#[derive(Default)]
+pub struct FileProcessorBuilder {
+    extensions: Vec<String>,
+    include_hidden: bool,
+    ignore_files_only: bool,
+    ignore_gitignore: bool,
+    ignore_patterns: Vec<String>,
+    line_numbers: bool,
+    toc_mode: Option<TocMode>,
+    ordering: Option<Box<dyn OrderingStrategy>>,
+}
+
+impl FileProcessorBuilder {
+    /// Only include files with these extensions
+    pub fn extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    pub fn include_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    /// Apply the ignore patterns to files only, not directories
+    pub fn ignore_files_only(mut self, ignore_files_only: bool) -> Self {
+        self.ignore_files_only = ignore_files_only;
+        self
+    }
+
+    pub fn ignore_gitignore(mut self, ignore_gitignore: bool) -> Self {
+        self.ignore_gitignore = ignore_gitignore;
+        self
+    }
+
+    pub fn ignore_patterns(mut self, ignore_patterns: Vec<String>) -> Self {
+        self.ignore_patterns = ignore_patterns;
+        self
+    }
+
+    pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    pub fn toc_mode(mut self, toc_mode: Option<TocMode>) -> Self {
+        self.toc_mode = toc_mode;
+        self
+    }
+
+    /// Replace the walk order with the order chosen by `ordering`
+    pub fn with_ordering(mut self, ordering: impl OrderingStrategy + 'static) -> Self {
+        self.ordering = Some(Box::new(ordering));
+        self
+    }
+
+    /// Create the processor, failing if an ignore pattern is invalid
+    pub fn build(self) -> Result<FileProcessor> {
+        let mut processor = FileProcessor::new(
+            self.extensions,
+            self.include_hidden,
+            self.ignore_files_only,
+            self.ignore_gitignore,
+            self.ignore_patterns,
+            self.line_numbers,
+            self.toc_mode,
+        )?;
+        processor.ordering = self.ordering;
+        Ok(processor)
+    }
 }
 
 impl FileProcessor {
+    /// Start building a processor from named options
+    pub fn builder() -> FileProcessorBuilder {
+        FileProcessorBuilder::default()
+    }
+
     /// Create a new FileProcessor with the specified options
     pub fn new(
         extensions: Vec<String>,
@@ -33,171 +514,964 @@ impl FileProcessor {
         let custom_ignore = CustomIgnore::new(ignore_patterns, ignore_files_only)?;
 
         Ok(Self {
-            extensions,
+            extension_filter: ExtensionFilter::new(extensions),
             include_hidden,
             ignore_gitignore,
+            include_submodules: false,
+            default_ignores: true,
+            include_lockfiles: false,
+            allow_sensitive: false,
+            strict: false,
+            include_vendored: false,
+            include_generated: false,
+            max_depth: None,
+            git_attributes: GitAttributes::default(),
             line_numbers,
             toc_mode,
+            readme_first: false,
+            dir_preambles: false,
+            collections: false,
+            dedupe: false,
+            filter_cmd: None,
+            transform_cmd: None,
+            sample_rows: None,
+            transcode: false,
+            strip_ansi: false,
+            pretty_json: false,
+            toc_tokens: false,
+            verbose: false,
+            tokenizer: Tokenizer::default(),
+            prefixes: Vec::new(),
+            suffixes: Vec::new(),
             custom_ignore,
+            include_patterns: IncludePatterns::default(),
+            test_filter: TestFilter::default(),
+            force_include: IncludePatterns::default(),
+            root_includes: RootIncludes::default(),
+            output_exclusions: OutputExclusions::default(),
+            priorities: Vec::new(),
+            binary_mode: BinaryMode::default(),
+            hex_dump_bytes: HEX_DUMP_BYTES,
+            skip_empty: false,
+            contains: Vec::new(),
+            not_contains: Vec::new(),
+            transforms: Vec::new(),
+            ordering: None,
+            cache: None,
+            read_budget: None,
         })
     }
 
+    /// Within each directory, emit README and other documentation files before code
+    pub fn with_readme_first(mut self, readme_first: bool) -> Self {
+        self.readme_first = readme_first;
+        self
+    }
+
+    /// Descend into git submodules instead of skipping them
+    pub fn with_include_submodules(mut self, include_submodules: bool) -> Self {
+        self.include_submodules = include_submodules;
+        self
+    }
+
+    /// Skip the built-in junk list (`.git/`, `node_modules/`, `.DS_Store`, ...);
+    /// on by default
+    pub fn with_default_ignores(mut self, default_ignores: bool) -> Self {
+        self.default_ignores = default_ignores;
+        self
+    }
+
+    /// Keep lockfiles such as `Cargo.lock` and `package-lock.json`, skipped by default
+    pub fn with_include_lockfiles(mut self, include_lockfiles: bool) -> Self {
+        self.include_lockfiles = include_lockfiles;
+        self
+    }
+
+    /// Emit `.env`, private keys and other credential files, skipped by default
+    pub fn with_allow_sensitive(mut self, allow_sensitive: bool) -> Self {
+        self.allow_sensitive = allow_sensitive;
+        self
+    }
+
+    /// Fail on a sensitive file instead of skipping it with a warning
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Descend into `vendor/` and `third_party/` trees, skipped by default
+    pub fn with_include_vendored(mut self, include_vendored: bool) -> Self {
+        self.include_vendored = include_vendored;
+        self
+    }
+
+    /// Keep files marked `linguist-generated` or `export-ignore` in `.gitattributes`,
+    /// and files that look minified or generated
+    pub fn with_include_generated(mut self, include_generated: bool) -> Self {
+        self.include_generated = include_generated;
+        self
+    }
+
+    /// Only descend `max_depth` levels below each input directory (1 = its direct children)
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// File names kept by extension filters whatever their extension, replacing
+    /// the built-in [`KNOWN_FILE_NAMES`](crate::extensions::KNOWN_FILE_NAMES)
+    pub fn with_known_names(mut self, names: Vec<String>) -> Self {
+        self.extension_filter.names = names;
+        self
+    }
+
+    /// With extension filters, also include extensionless files whose shebang or
+    /// header names one of the requested languages
+    pub fn with_sniff(mut self, sniff: bool) -> Self {
+        self.extension_filter.sniff = sniff;
+        self
+    }
+
+    /// Emit `PROMPT.md`/`CONTEXT.md` first in their directory as a special preamble block
+    pub fn with_dir_preambles(mut self, dir_preambles: bool) -> Self {
+        self.dir_preambles = dir_preambles;
+        self
+    }
+
+    /// Wrap the files found under each input path in a named collection
+    pub fn with_collections(mut self, collections: bool) -> Self {
+        self.collections = collections;
+        self
+    }
+
+    /// Pipe each file's content through `command` ahead of the built-in transforms
+    /// (see [`TransformCmd`])
+    pub fn with_transform_cmd(mut self, command: &str) -> Self {
+        self.transform_cmd = Some(TransformCmd::new(command));
+        self
+    }
+
+    /// Reuse the content of files unchanged since an earlier run (see [`FileCache`])
+    pub fn with_cache(mut self, cache: FileCache) -> Self {
+        self.cache = Some(Mutex::new(cache));
+        self
+    }
+
+    /// Keep only the files `command` approves (see [`FilterCmd`])
+    pub fn with_filter_cmd(mut self, command: &str) -> Self {
+        self.filter_cmd = Some(FilterCmd::new(command));
+        self
+    }
+
+    /// Render Parquet files, otherwise skipped as binary, as their schema and
+    /// first `rows` rows (CSV/TSV sampling is a [`SampleRows`](crate::sample::SampleRows) transform)
+    pub fn with_sample_rows(mut self, rows: Option<usize>) -> Self {
+        self.sample_rows = rows;
+        self
+    }
+
+    /// Decode Latin-1, UTF-16 and other non-UTF-8 text instead of skipping it as binary
+    pub fn with_transcode(mut self, transcode: bool) -> Self {
+        self.transcode = transcode;
+        self
+    }
+
+    /// Remove ANSI color and escape codes from file contents
+    pub fn with_strip_ansi(mut self, strip_ansi: bool) -> Self {
+        self.strip_ansi = strip_ansi;
+        self
+    }
+
+    /// Re-indent minified `.json` files, one member or element per line
+    pub fn with_pretty_json(mut self, pretty_json: bool) -> Self {
+        self.pretty_json = pretty_json;
+        self
+    }
+
+    /// Emit files with identical content once; later copies become an
+    /// `[identical to <path>]` stub
+    pub fn with_dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Annotate each table of contents entry with its token count
+    pub fn with_toc_tokens(mut self, toc_tokens: bool) -> Self {
+        self.toc_tokens = toc_tokens;
+        self
+    }
+
+    /// List every skipped file instead of summarizing once past the warnings budget
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Tokenizer used for token budgets and table of contents annotations
+    pub fn with_tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Add text emitted before the formatted bundle (repeated calls stack in order)
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    /// Add text emitted after the formatted bundle (repeated calls stack in order)
+    pub fn with_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffixes.push(suffix.into());
+        self
+    }
+
+    /// Only include files matching `pattern` (repeated calls add alternatives); see
+    /// [`IncludePatterns`] for how patterns match. Explicit file arguments are always kept.
+    pub fn with_include(mut self, pattern: &str) -> Result<Self> {
+        self.include_patterns.add(pattern)?;
+        Ok(self)
+    }
+
+    /// Keep only test files, or only non-test files; see [`is_test_file`](crate::ignore::is_test_file)
+    pub fn with_test_filter(mut self, test_filter: TestFilter) -> Self {
+        self.test_filter = test_filter;
+        self
+    }
+
+    /// Include files matching `pattern` even if gitignored, hidden or otherwise
+    /// filtered out. They come after the rest of their input's files.
+    pub fn with_force_include(mut self, pattern: &str) -> Result<Self> {
+        self.force_include.add(pattern)?;
+        Ok(self)
+    }
+
+    /// Choose what happens to binary files; they are skipped with a warning by default
+    pub fn with_binary_mode(mut self, binary_mode: BinaryMode) -> Self {
+        self.binary_mode = binary_mode;
+        self
+    }
+
+    /// How many leading bytes of each binary file `BinaryMode::Hex` dumps
+    pub fn with_hex_dump_bytes(mut self, bytes: usize) -> Self {
+        self.hex_dump_bytes = bytes;
+        self
+    }
+
+    /// Leave out files that are empty or contain only whitespace
+    pub fn with_skip_empty(mut self, skip_empty: bool) -> Self {
+        self.skip_empty = skip_empty;
+        self
+    }
+
+    /// Only include files whose content matches `pattern` (repeated calls must all match)
+    pub fn with_contains(mut self, pattern: &str) -> Result<Self> {
+        self.contains.push(compile_content_regex(pattern)?);
+        Ok(self)
+    }
+
+    /// Skip files whose content matches `pattern`
+    pub fn with_not_contains(mut self, pattern: &str) -> Result<Self> {
+        self.not_contains.push(compile_content_regex(pattern)?);
+        Ok(self)
+    }
+
+    /// Only include files whose names match `pattern` when walking the directory `root`
+    pub fn with_root_include(mut self, root: PathBuf, pattern: &str) -> Result<Self> {
+        self.root_includes.add(root, pattern)?;
+        Ok(self)
+    }
+
+    /// Never pick up the file written to `output` (or earlier snapshots of it) while walking directories
+    pub fn with_output_exclusion(mut self, output: &Path) -> Self {
+        self.output_exclusions.add(output);
+        self
+    }
+
+    /// Set the budget priority of files matching a `GLOB:LEVEL` rule such as
+    /// `src/**:high` or `tests/**:low`; the first matching rule wins
+    pub fn with_priority(mut self, rule: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            FilesToPromptError::PatternError(format!("Invalid priority '{}': {}", rule, reason))
+        };
+        let (pattern, level) = rule
+            .rsplit_once(':')
+            .ok_or_else(|| invalid("expected GLOB:high, GLOB:normal or GLOB:low"))?;
+        let priority = match level {
+            "high" => Priority::High,
+            "normal" => Priority::Normal,
+            "low" => Priority::Low,
+            _ => return Err(invalid("level must be high, normal or low")),
+        };
+        let pattern =
+            glob::Pattern::new(pattern.trim_start_matches("./")).map_err(|e| invalid(e.msg))?;
+        self.priorities.push((pattern, priority));
+        Ok(self)
+    }
+
+    /// Add a content transform, applied in insertion order before formatting
+    pub fn with_transform(mut self, transform: impl ContentTransform + 'static) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Stop walking and reading once the files read so far hold more than `tokens`
+    /// tokens, as any file after them would be dropped by `fit_token_budget`.
+    ///
+    /// Ignored when an ordering, priorities, `--filter-cmd` or dedupe could still
+    /// change which files come first after the walk.
+    pub fn with_read_budget(mut self, tokens: usize) -> Self {
+        self.read_budget = Some(tokens);
+        self
+    }
+
+    /// Replace the walk order with the order chosen by `ordering`
+    pub fn with_ordering(mut self, ordering: impl OrderingStrategy + 'static) -> Self {
+        self.ordering = Some(Box::new(ordering));
+        self
+    }
+
     /// Process multiple paths and generate output using the specified formatter
     pub fn process_paths<F: OutputFormatter>(
         &self,
         paths: &[PathBuf],
         formatter: &mut F,
     ) -> Result<String> {
-        let mut output = Vec::new();
+        let files = self.collect_files(paths)?;
+        self.format_files(paths, &files, formatter)
+    }
 
-        // Add start output
-        let start = formatter.start_output();
-        if !start.is_empty() {
-            output.push(start);
+    /// Like `process_paths`, but also return the included files and the output's
+    /// token count
+    pub fn bundle<F: OutputFormatter>(
+        &self,
+        paths: &[PathBuf],
+        formatter: &mut F,
+    ) -> Result<Bundle> {
+        let files = self.collect_files(paths)?;
+        let output = self.format_files(paths, &files, formatter)?;
+        Ok(Bundle {
+            output,
+            files,
+            tokenizer: self.tokenizer,
+        })
+    }
+
+    /// Choose the files that fit within `max_tokens` once formatted with `formatter`.
+    ///
+    /// Prefixes, the table of contents and the formatter's wrapper count against the
+    /// budget. Files are taken by priority, then in order; the first one that does not
+    /// fit is truncated with a marker if any of it fits, and every later file is dropped.
+    /// High-priority files are never truncated: one that does not fit is dropped and the
+    /// next is tried. Kept files stay in their original order. The formatter is only
+    /// cloned for measuring, so it can be used afterwards with `format_files`.
+    pub fn fit_token_budget<F: OutputFormatter + Clone>(
+        &self,
+        paths: &[PathBuf],
+        files: &[FileEntry],
+        formatter: &F,
+        max_tokens: usize,
+    ) -> Result<BudgetFit> {
+        let overhead =
+            self.tokenizer
+                .count(&self.format_files(paths, &[], &mut formatter.clone())?);
+        let mut remaining = max_tokens.saturating_sub(overhead);
+        let mut probe = formatter.clone();
+        let mut kept: Vec<Option<FileEntry>> = vec![None; files.len()];
+        let mut fit = BudgetFit::default();
+
+        let mut order: Vec<usize> = (0..files.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.priority(&files[i].path)));
+
+        for i in order {
+            let file = &files[i];
+            // Blocks are joined with a newline, counted as one token
+            let cost = self
+                .tokenizer
+                .count(&self.format_entry(&mut probe.clone(), file))
+                + 1;
+            if cost <= remaining {
+                self.format_entry(&mut probe, file);
+                remaining -= cost;
+                kept[i] = Some(file.clone());
+                continue;
+            }
+            if self.priority(&file.path) == Priority::High {
+                continue;
+            }
+
+            // Find the most lines of this file that still fit
+            let total_lines = file.content.lines().count();
+            let (mut low, mut high) = (0, total_lines.saturating_sub(1));
+            while low < high {
+                let mid = (low + high).div_ceil(2);
+                let entry = truncated_entry(file, mid);
+                if self
+                    .tokenizer
+                    .count(&self.format_entry(&mut probe.clone(), &entry))
+                    < remaining
+                {
+                    low = mid;
+                } else {
+                    high = mid - 1;
+                }
+            }
+            if low > 0 {
+                kept[i] = Some(truncated_entry(file, low));
+                fit.truncated = Some(file.path.clone());
+            }
+            break;
         }
 
-        // Generate and add table of contents if requested
-        if let Some(toc_mode) = self.toc_mode {
-            let tree_generator = TreeGenerator::new(
-                self.extensions.clone(),
-                self.include_hidden,
-                self.ignore_gitignore,
-                self.custom_ignore.clone(),
-            );
+        for (file, kept) in files.iter().zip(kept) {
+            match kept {
+                Some(entry) => fit.files.push(entry),
+                None => fit.dropped.push(file.path.clone()),
+            }
+        }
 
-            let trees = tree_generator.generate_tree(paths)?;
-            let toc = tree_generator.render_tree(&trees, toc_mode);
+        Ok(fit)
+    }
 
-            if !toc.is_empty() {
-                let formatted_toc = formatter.format_table_of_contents(&toc);
-                output.push(formatted_toc);
-                output.push(String::new()); // Add blank line after TOC
+    /// Budget priority from the first matching `with_priority` rule
+    fn priority(&self, path: &Path) -> Priority {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let path = path
+            .strip_prefix(&cwd)
+            .or_else(|_| path.strip_prefix("."))
+            .unwrap_or(path);
+        self.priorities
+            .iter()
+            .find(|(pattern, _)| pattern.matches_path(path))
+            .map_or(Priority::Normal, |(_, priority)| *priority)
+    }
+
+    fn format_entry<F: OutputFormatter>(&self, formatter: &mut F, file: &FileEntry) -> String {
+        if file.preamble {
+            formatter.format_preamble(&file.path, &file.content)
+        } else {
+            formatter.format_file(&file.path, &file.content, self.line_numbers)
+        }
+    }
+
+    /// Walk the given paths and read every file that passes the filters
+    pub fn collect_files(&self, paths: &[PathBuf]) -> Result<Vec<FileEntry>> {
+        Ok(self.collect_files_with_decisions(paths)?.0)
+    }
+
+    /// Like `collect_files`, but also return the filter decision made for each file
+    pub fn collect_files_with_decisions(
+        &self,
+        paths: &[PathBuf],
+    ) -> Result<(Vec<FileEntry>, Decisions)> {
+        let Walk {
+            mut files,
+            decisions,
+            ..
+        } = self.walk_paths(paths, None)?;
+        if let Some(ordering) = &self.ordering {
+            files = ordering.order(files);
+            if self.collections {
+                files.sort_by_key(|file| file.root);
             }
         }
+        if self.dedupe {
+            dedupe_files(&mut files);
+        }
+        Ok((files, decisions))
+    }
+
+    /// Whether `stream_files` can format each file as it is read: nothing that
+    /// needs every file at once, such as a table of contents, reordering or
+    /// deduplication, is enabled
+    pub fn can_stream(&self) -> bool {
+        self.toc_mode.is_none()
+            && self.ordering.is_none()
+            && !self.dedupe
+            && self.read_budget.is_none()
+    }
 
-        // Process each path
-        for path in paths {
-            self.process_single_path(path, formatter, &mut output)?;
+    /// Walk `paths` and write each file to `out` with `formatter` as soon as it
+    /// is read, never holding more than one file in memory.
+    ///
+    /// Writes the same bytes `format_files` would for the collected files, or
+    /// nothing at all when no file passes the filters. Returns the number of files
+    /// written with the filter decisions of the walk. Only valid if `can_stream`.
+    pub fn stream_files<F: OutputFormatter, W: io::Write + ?Sized>(
+        &self,
+        paths: &[PathBuf],
+        formatter: &mut F,
+        out: &mut W,
+    ) -> Result<(usize, Decisions)> {
+        let mut writer = BundleWriter::new(self, paths, formatter, out);
+        let mut sink = |file: &FileEntry| -> Result<()> {
+            if writer.files == 0 {
+                writer.start(&[])?;
+            }
+            writer.file(file)
+        };
+        let decisions = self.walk_paths(paths, Some(&mut sink))?.decisions;
+        let written = if writer.files > 0 {
+            writer.finish()?
+        } else {
+            0
+        };
+        Ok((written, decisions))
+    }
+
+    /// Walk every input path, handing files to `sink` if given
+    fn walk_paths<'a>(
+        &self,
+        paths: &[PathBuf],
+        sink: Option<&'a mut Sink<'a>>,
+    ) -> Result<Walk<'a>> {
+        let mut walk = Walk::new(self.verbose, sink);
+        walk.approved = self.approve_candidates(paths)?;
+        self.walk_roots(paths, &mut walk)?;
+
+        walk.skips.print();
+        if self.over_read_budget(walk.tokens_read) {
+            eprintln!(
+                "Token budget: stopped reading after {} file(s), already over {} tokens",
+                walk.files.len(),
+                self.read_budget.unwrap_or_default()
+            );
+        }
+        if let Some(cache) = &self.cache {
+            if let Err(e) = lock(cache).save() {
+                eprintln!("Warning: Could not save the cache: {}", e);
+            }
         }
+        Ok(walk)
+    }
 
-        // Add end output
-        let end = formatter.end_output();
-        if !end.is_empty() {
-            output.push(end);
+    /// Walk each input path in turn, stopping once the read budget is used up
+    fn walk_roots(&self, paths: &[PathBuf], walk: &mut Walk<'_>) -> Result<()> {
+        for (root, path) in paths.iter().enumerate() {
+            if self.over_read_budget(walk.tokens_read) {
+                break;
+            }
+            walk.root = root;
+            self.process_single_path(path, walk)?;
         }
+        Ok(())
+    }
 
-        Ok(output.join("\n"))
+    /// Run a `--filter-cmd` that reads the candidate list over every file the walk
+    /// would read, so the others are never read at all
+    fn approve_candidates(&self, paths: &[PathBuf]) -> Result<Option<HashSet<PathBuf>>> {
+        let Some(filter_cmd) = self.filter_cmd.as_ref().filter(|cmd| !cmd.per_file()) else {
+            return Ok(None);
+        };
+        let mut listing = Walk::new(false, None);
+        listing.candidates = Some(Vec::new());
+        self.walk_roots(paths, &mut listing)?;
+        let candidates = listing.candidates.unwrap_or_default();
+        let candidates: Vec<&Path> = candidates.iter().map(PathBuf::as_path).collect();
+        Ok(Some(filter_cmd.approve(&candidates)?))
     }
 
-    /// Process a single path (file or directory)
-    fn process_single_path<F: OutputFormatter>(
+    /// Format previously collected files, adding the table of contents for `paths` if enabled
+    pub fn format_files<F: OutputFormatter>(
         &self,
-        path: &Path,
+        paths: &[PathBuf],
+        files: &[FileEntry],
+        formatter: &mut F,
+    ) -> Result<String> {
+        let mut buffer = Vec::new();
+        self.write_files(paths, files, formatter, &mut buffer)?;
+        Ok(String::from_utf8(buffer).expect("formatters produce UTF-8"))
+    }
+
+    /// Like `format_files`, but write each piece to `out` as soon as it is formatted.
+    ///
+    /// Pieces are separated by a newline, so the bytes written are exactly the
+    /// string `format_files` returns, without ever holding the whole bundle.
+    pub fn write_files<F: OutputFormatter, W: io::Write + ?Sized>(
+        &self,
+        paths: &[PathBuf],
+        files: &[FileEntry],
         formatter: &mut F,
-        output: &mut Vec<String>,
+        out: &mut W,
     ) -> Result<()> {
+        let mut writer = BundleWriter::new(self, paths, formatter, out);
+        writer.start(files)?;
+        for file in files {
+            writer.file(file)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Why a file the walker yielded is dropped before it is read
+    fn walk_filter_reason(&self, root: &Path, path: &Path) -> Option<FilterReason> {
+        if !self.should_include_file_by_extension(path) {
+            Some(FilterReason::Extension)
+        } else if !self.include_hidden && self.is_hidden_file(path) {
+            Some(FilterReason::Hidden)
+        } else if self.custom_ignore.should_ignore_file(root, path) {
+            Some(FilterReason::IgnorePattern)
+        } else if !self.include_patterns.allows(root, path) {
+            Some(FilterReason::IncludePattern)
+        } else if !self.test_filter.allows(root, path) {
+            // --no-tests / --tests-only
+            Some(FilterReason::Tests)
+        } else if !self.root_includes.allows(root, path) {
+            // Patterns scoped to this root (e.g. from `src/**.rs`)
+            Some(FilterReason::PathPattern)
+        } else if self.output_exclusions.excludes(path) {
+            // Bundles written by previous runs
+            Some(FilterReason::PreviousOutput)
+        } else if !self.include_lockfiles && is_lockfile(path) {
+            Some(FilterReason::Lockfile)
+        } else {
+            None
+        }
+    }
+
+    /// Process a single path (file or directory)
+    fn process_single_path(&self, path: &Path, walk: &mut Walk<'_>) -> Result<()> {
         if path.is_file() {
-            self.process_file(path, formatter, output)?;
+            self.process_file(path, walk)?;
         } else if path.is_dir() {
-            self.process_directory(path, formatter, output)?;
+            self.process_directory(path, walk)?;
         }
         Ok(())
     }
 
-    /// Process a single file
-    fn process_file<F: OutputFormatter>(
-        &self,
-        file_path: &Path,
-        formatter: &mut F,
-        output: &mut Vec<String>,
-    ) -> Result<()> {
-        // Check if file should be included based on extension
-        if !self.should_include_file_by_extension(file_path) {
-            return Ok(());
+    /// Have the files read so far used up the read budget?
+    fn over_read_budget(&self, tokens_read: usize) -> bool {
+        let stops_early = self.ordering.is_none() && self.priorities.is_empty() && !self.dedupe;
+        stops_early && self.read_budget.is_some_and(|budget| tokens_read > budget)
+    }
+
+    /// Add the tokens of the files read since `start` to the running total, if a
+    /// read budget needs it
+    fn tally_tokens(&self, start: usize, walk: &mut Walk<'_>) {
+        if self.read_budget.is_some() {
+            walk.tokens_read += walk.files[start..]
+                .iter()
+                .map(|file| self.token_count(file))
+                .sum::<usize>();
         }
+    }
 
-        // Check if file is hidden and should be excluded
-        if !self.include_hidden && self.is_hidden_file(file_path) {
-            return Ok(());
+    /// Process a single file
+    fn process_file(&self, file_path: &Path, walk: &mut Walk<'_>) -> Result<()> {
+        let started = Instant::now();
+        let reason = if !self.should_include_file_by_extension(file_path) {
+            Some(FilterReason::Extension)
+        } else if !self.include_hidden && self.is_hidden_file(file_path) {
+            Some(FilterReason::Hidden)
+        } else {
+            return self.read_entry(file_path, false, walk, started);
+        };
+        walk.decide(file_path, reason, started);
+        Ok(())
+    }
+
+    /// Process a directory recursively
+    fn process_directory(&self, dir_path: &Path, walk: &mut Walk<'_>) -> Result<()> {
+        let start = walk.decisions.files.len();
+        let pruned = Arc::new(Mutex::new(Vec::new()));
+        let walker = self.build_walker(dir_path, Some(pruned.clone()))?;
+        // Everything the walker yielded, and the directories it descended into
+        let mut yielded = HashSet::new();
+        let mut descended = Vec::new();
+        let mut complete = true;
+
+        for result in walker {
+            // Every later file would be dropped, so don't walk any further
+            if self.over_read_budget(walk.tokens_read) {
+                complete = false;
+                break;
+            }
+            let entry = match result {
+                Ok(entry) => entry,
+                Err(err) => return Err(map_walk_error(err)),
+            };
+
+            let path = entry.path();
+            yielded.insert(path.to_path_buf());
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+            if is_dir && self.max_depth.is_none_or(|depth| entry.depth() < depth) {
+                descended.push(path.to_path_buf());
+            }
+            if entry.depth() == 0 {
+                continue;
+            }
+
+            let is_file = entry
+                .file_type()
+                .map(|ft| ft.is_file())
+                .unwrap_or_else(|| path.is_file());
+            if !is_file {
+                continue;
+            }
+
+            let started = Instant::now();
+            if let Some(reason) = self.walk_filter_reason(dir_path, path) {
+                if reason == FilterReason::Lockfile {
+                    walk.skips.skip_lockfile(path);
+                }
+                walk.decide(path, Some(reason), started);
+                continue;
+            }
+
+            self.read_entry(path, true, walk, started)?;
         }
 
-        match self.read_file_content(file_path) {
-            Ok(content) => {
-                let formatted = formatter.format_file(file_path, &content, self.line_numbers);
-                output.push(formatted);
+        let mut pruned = std::mem::take(&mut *pruned.lock().unwrap());
+        if complete {
+            pruned.extend(self.skipped_by_walker(&descended, &yielded, &pruned));
+        }
+        for (path, reason) in pruned {
+            if reason == FilterReason::Vendored {
+                walk.skips.skip_vendored(&path);
             }
-            Err(FilesToPromptError::BinaryFile { path }) => {
-                eprintln!("Warning: Skipping binary file {}", path.display());
+            walk.decisions.pruned.push((path, reason));
+        }
+
+        if !self.force_include.is_empty() && !self.over_read_budget(walk.tokens_read) {
+            let walked: HashSet<PathBuf> = walk.decisions.files[start..]
+                .iter()
+                .filter(|decision| decision.reason.is_none())
+                .map(|decision| decision.path.clone())
+                .collect();
+            for path in force_included_files(dir_path, &self.force_include, self.max_depth) {
+                if !walked.contains(&path) {
+                    self.read_entry(&path, false, walk, Instant::now())?;
+                }
             }
-            Err(e) => return Err(e),
         }
 
         Ok(())
     }
 
-    /// Process a directory recursively
-    fn process_directory<F: OutputFormatter>(
+    /// Entries of the `descended` directories the walker's own hidden and gitignore
+    /// rules skipped: neither `yielded` nor `pruned` by our filters
+    fn skipped_by_walker(
         &self,
-        dir_path: &Path,
-        formatter: &mut F,
-        output: &mut Vec<String>,
+        descended: &[PathBuf],
+        yielded: &HashSet<PathBuf>,
+        pruned: &[(PathBuf, FilterReason)],
+    ) -> Vec<(PathBuf, FilterReason)> {
+        let pruned: HashSet<&Path> = pruned.iter().map(|(path, _)| path.as_path()).collect();
+        let mut skipped = Vec::new();
+        for dir in descended {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut children: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| !yielded.contains(path) && !pruned.contains(path.as_path()))
+                .collect();
+            children.sort();
+            for path in children {
+                let hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                let reason = if hidden && !self.include_hidden {
+                    FilterReason::Hidden
+                } else {
+                    FilterReason::Gitignore
+                };
+                skipped.push((path, reason));
+            }
+        }
+        skipped
+    }
+
+    /// Read and transform a file that passed all filters, recording the decision
+    /// timed from `started`
+    ///
+    /// Files named on the command line skip the generated-file heuristics
+    /// (`detect_generated` false), as they do the other filters.
+    fn read_entry(
+        &self,
+        path: &Path,
+        detect_generated: bool,
+        walk: &mut Walk<'_>,
+        started: Instant,
     ) -> Result<()> {
-        let walker = self.build_walker(dir_path)?;
+        let start = walk.files.len();
+        let reason = self.read_into(path, detect_generated, walk)?;
+        self.tally_tokens(start, walk);
+        walk.decide(path, reason, started);
+        walk.settle(start)
+    }
 
-        for result in walker {
-            let entry = match result {
-                Ok(entry) => entry,
-                Err(err) => return Err(map_walk_error(err)),
-            };
+    /// Add the file at `path` to the walk's files, or return the filter that drops it
+    fn read_into(
+        &self,
+        path: &Path,
+        detect_generated: bool,
+        walk: &mut Walk<'_>,
+    ) -> Result<Option<FilterReason>> {
+        let Walk {
+            files,
+            skips,
+            candidates,
+            approved,
+            ..
+        } = walk;
 
-            let path = entry.path();
-            if entry.depth() == 0 {
-                continue;
+        // Unlike the other filters, this one also holds for files named explicitly
+        if !self.allow_sensitive && is_sensitive(path) {
+            if self.strict {
+                return Err(FilesToPromptError::SensitiveFile {
+                    path: path.to_path_buf(),
+                });
             }
+            skips.skip_sensitive(path);
+            return Ok(Some(FilterReason::Sensitive));
+        }
 
-            let is_file = entry
-                .file_type()
-                .map(|ft| ft.is_file())
-                .unwrap_or_else(|| path.is_file());
-            if !is_file {
-                continue;
+        // Ask `--filter-cmd` before doing any work on the file
+        if let Some(candidates) = candidates {
+            candidates.push(path.to_path_buf());
+            return Ok(None);
+        }
+        if let Some(filter_cmd) = &self.filter_cmd {
+            let kept = match approved {
+                Some(approved) => approved.contains(path),
+                None => filter_cmd.approves(path)?,
+            };
+            if !kept {
+                return Ok(Some(FilterReason::FilterCmd));
             }
+        }
 
-            // Check if file should be included based on extension
-            if !self.should_include_file_by_extension(path) {
-                continue;
+        let cached = self.cache.as_ref().zip(fs::metadata(path).ok());
+        if let Some((cache, metadata)) = &cached {
+            if let Some(entry) = lock(cache).lookup(path, metadata) {
+                files.push(self.text_entry(path, entry.content.clone()));
+                return Ok(None);
             }
+        }
 
-            // Check if file is hidden and should be excluded
-            if !self.include_hidden && self.is_hidden_file(path) {
-                continue;
+        match self.read_file_content(path, detect_generated) {
+            Ok(content) if self.is_skipped_empty(&content) => Ok(Some(FilterReason::Empty)),
+            Ok(content) if !self.content_matches(&content) => Ok(Some(FilterReason::Content)),
+            Ok(content) => {
+                let content = match &self.transform_cmd {
+                    Some(command) => command.run(path, &content)?,
+                    None => content,
+                };
+                let content = self.apply_transforms(path, content);
+                if let Some((cache, metadata)) = &cached {
+                    let tokens = self.tokenizer.count(&content);
+                    lock(cache).insert(path, metadata, &content, tokens);
+                }
+                files.push(self.text_entry(path, content));
+                Ok(None)
             }
-
-            // Check custom ignore patterns for files
-            if self.custom_ignore.should_ignore_file(path) {
-                continue;
+            #[cfg(feature = "parquet")]
+            Err(FilesToPromptError::BinaryFile { path }) if self.samples_parquet(&path) => {
+                let rows = self.sample_rows.unwrap_or_default();
+                let content = crate::sample::sample_parquet(&path, rows)?;
+                files.push(FileEntry::new(path, content));
+                Ok(None)
             }
-
-            // Process the file
-            match self.read_file_content(path) {
-                Ok(content) => {
-                    let formatted = formatter.format_file(path, &content, self.line_numbers);
-                    output.push(formatted);
+            Err(FilesToPromptError::BinaryFile { path }) => match self.binary_mode {
+                BinaryMode::Skip => Ok(Some(FilterReason::Binary)),
+                BinaryMode::Warn => {
+                    skips.skip_binary(&path);
+                    Ok(Some(FilterReason::Binary))
+                }
+                BinaryMode::Placeholder => {
+                    let size = fs::metadata(&path)?.len();
+                    let content = format!("[binary file omitted: {}]", format_size(size));
+                    files.push(FileEntry::new(path, content));
+                    Ok(None)
                 }
-                Err(FilesToPromptError::BinaryFile { path }) => {
-                    eprintln!("Warning: Skipping binary file {}", path.display());
+                BinaryMode::Hex => {
+                    // Only the dumped bytes are read; the size comes from the metadata
+                    let file = fs::File::open(&path)?;
+                    let total = file.metadata()?.len();
+                    let mut bytes = Vec::new();
+                    file.take(self.hex_dump_bytes as u64)
+                        .read_to_end(&mut bytes)?;
+                    let content = hex_dump(&bytes, total);
+                    files.push(FileEntry::new(path, content));
+                    Ok(None)
                 }
-                Err(e) => return Err(e),
+                BinaryMode::Error => Err(FilesToPromptError::BinaryFile { path }),
+            },
+            Err(FilesToPromptError::GeneratedFile { path, reason }) => {
+                skips.skip_generated(&path, reason);
+                Ok(Some(FilterReason::Generated))
             }
+            Err(e) => Err(e),
         }
+    }
 
-        Ok(())
+    /// Entry for a text file, marked when it is a directory preamble
+    fn text_entry(&self, path: &Path, content: String) -> FileEntry {
+        let mut entry = FileEntry::new(path.to_path_buf(), content);
+        entry.preamble = self.dir_preambles && is_preamble(path);
+        entry
     }
 
-    fn build_walker(&self, dir_path: &Path) -> Result<ignore::Walk> {
+    /// Token count of a collected file, taken from the cache when it is there
+    pub fn token_count(&self, file: &FileEntry) -> usize {
+        self.cache
+            .as_ref()
+            .and_then(|cache| lock(cache).tokens(&file.path, &file.content))
+            .unwrap_or_else(|| self.tokenizer.count(&file.content))
+    }
+
+    /// Is this a Parquet file rendered by `--sample-rows` rather than treated as binary?
+    #[cfg(feature = "parquet")]
+    fn samples_parquet(&self, path: &Path) -> bool {
+        self.sample_rows.is_some() && is_parquet(path)
+    }
+
+    /// Is this zero-byte or whitespace-only content dropped by `--skip-empty`?
+    fn is_skipped_empty(&self, content: &str) -> bool {
+        self.skip_empty && content.trim().is_empty()
+    }
+
+    /// Does the raw content pass `--contains` and `--not-contains`?
+    fn content_matches(&self, content: &str) -> bool {
+        self.contains.iter().all(|regex| regex.is_match(content))
+            && !self
+                .not_contains
+                .iter()
+                .any(|regex| regex.is_match(content))
+    }
+
+    fn apply_transforms(&self, path: &Path, content: String) -> String {
+        self.transforms.iter().fold(content, |content, transform| {
+            transform.apply(path, &content)
+        })
+    }
+
+    /// Build the gitignore-aware walker for `dir_path`, pruning hidden, ignored,
+    /// submodule, default-ignored, vendored and generated directories.
+    ///
+    /// Entries the walk prunes are pushed onto `pruned` with the filter that
+    /// pruned them, if given.
+    fn build_walker(&self, dir_path: &Path, pruned: Option<Pruned>) -> Result<ignore::Walk> {
         let mut builder = WalkBuilder::new(dir_path);
-        builder.sort_by_file_name(|a, b| a.cmp(b));
+        if self.readme_first || self.dir_preambles {
+            let readme_first = self.readme_first;
+            let dir_preambles = self.dir_preambles;
+            let rank = move |path: &Path| {
+                (
+                    !(dir_preambles && is_preamble(path)),
+                    if readme_first { doc_rank(path) } else { 0 },
+                )
+            };
+            builder.sort_by_file_path(move |a, b| {
+                rank(a)
+                    .cmp(&rank(b))
+                    .then_with(|| a.file_name().cmp(&b.file_name()))
+            });
+        } else {
+            builder.sort_by_file_name(|a, b| a.cmp(b));
+        }
         builder.follow_links(false);
+        builder.max_depth(self.max_depth);
         if self.include_hidden {
             builder.hidden(false);
         }
@@ -220,69 +1494,137 @@ impl FileProcessor {
         let root = dir_path.to_path_buf();
         let custom_for_dirs = self.custom_ignore.clone();
         let include_hidden = self.include_hidden;
+        let include_submodules = self.include_submodules;
+        let default_ignores = self.default_ignores;
+        let include_vendored = self.include_vendored;
+        let git_attributes = (!self.include_generated).then(|| self.git_attributes.clone());
         builder.filter_entry(move |entry| {
             if entry.path() == root {
                 return true;
             }
 
             let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
+            let hidden = entry
+                .path()
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.starts_with('.'))
+                .unwrap_or(false);
 
-            if !include_hidden
-                && is_dir
-                && entry
-                    .path()
-                    .file_name()
-                    .and_then(|name| name.to_str())
-                    .map(|name| name.starts_with('.'))
-                    .unwrap_or(false)
+            let reason = if !include_hidden && is_dir && hidden {
+                FilterReason::Hidden
+            } else if is_dir && custom_for_dirs.should_ignore_dir(&root, entry.path()) {
+                FilterReason::IgnorePattern
+            } else if is_dir && !include_submodules && is_submodule(entry.path()) {
+                FilterReason::Submodule
+            } else if default_ignores && is_default_ignored(entry.path(), is_dir) {
+                FilterReason::DefaultIgnore
+            } else if is_dir && !include_vendored && is_vendored_dir(entry.path()) {
+                FilterReason::Vendored
+            } else if git_attributes
+                .as_ref()
+                .is_some_and(|attributes| attributes.is_skipped(entry.path()))
             {
-                return false;
-            }
+                FilterReason::Generated
+            } else {
+                return true;
+            };
 
-            if is_dir && custom_for_dirs.should_ignore_dir(entry.path()) {
-                return false;
+            if let Some(pruned) = &pruned {
+                pruned
+                    .lock()
+                    .unwrap()
+                    .push((entry.path().to_path_buf(), reason));
             }
-
-            true
+            false
         });
 
         Ok(builder.build())
     }
 
-    /// Read file content and handle binary files
-    fn read_file_content(&self, path: &Path) -> Result<String> {
-        let bytes = fs::read(path)?;
-
-        if Self::is_binary(&bytes) {
-            return Err(FilesToPromptError::BinaryFile {
-                path: path.to_path_buf(),
-            });
+    /// Read file content, rejecting binary files and, with `detect_generated`,
+    /// minified or generated-looking ones
+    fn read_file_content(&self, path: &Path, detect_generated: bool) -> Result<String> {
+        let mut bytes = fs::read(path)?;
+        // Escape codes must go before the binary check, which they would trip
+        if self.strip_ansi {
+            bytes = strip_ansi(&bytes);
         }
 
-        match String::from_utf8(bytes) {
-            Ok(content) => Ok(content),
-            Err(_) => Err(FilesToPromptError::BinaryFile {
+        let mut content = self
+            .decode(bytes)
+            .ok_or_else(|| FilesToPromptError::BinaryFile {
                 path: path.to_path_buf(),
-            }),
+            })?;
+        // Before the generated check, which would skip the file for its long line
+        if self.pretty_json && Self::is_minified_json(path, &content) {
+            if let Some(pretty) = pretty_json(&content) {
+                content = pretty;
+            }
         }
-    }
 
-    /// Check if a file should be included based on its extension
-    fn should_include_file_by_extension(&self, path: &Path) -> bool {
-        if self.extensions.is_empty() {
-            return true;
+        if detect_generated && !self.include_generated {
+            if let Some(reason) = Self::looks_generated(path, &content) {
+                return Err(FilesToPromptError::GeneratedFile {
+                    path: path.to_path_buf(),
+                    reason,
+                });
+            }
         }
 
-        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
-            // Check if file ends with any of the specified extensions
-            self.extensions.iter().any(|ext| {
-                // Handle extensions with or without leading dot
-                let ext = ext.strip_prefix('.').unwrap_or(ext);
-                extension == ext
-            })
+        Ok(content)
+    }
+
+    /// File bytes as text: UTF-8 as is, other encodings only with `--transcode`
+    fn decode(&self, bytes: Vec<u8>) -> Option<String> {
+        let bytes = if Self::is_binary(&bytes) {
+            bytes
         } else {
-            false
+            match String::from_utf8(bytes) {
+                Ok(content) => return Some(content),
+                Err(e) => e.into_bytes(),
+            }
+        };
+        self.transcode.then(|| transcode(&bytes)).flatten()
+    }
+
+    fn is_minified_json(path: &Path, content: &str) -> bool {
+        path.extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+            && content
+                .lines()
+                .any(|line| line.len() > MINIFIED_JSON_LINE_LENGTH)
+    }
+
+    /// Why a file looks minified or machine-generated, if it does: a `.min.js`
+    /// style name, a very long line, or a "DO NOT EDIT"/`@generated` header
+    fn looks_generated(path: &Path, content: &str) -> Option<&'static str> {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if MINIFIED_SUFFIXES
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+        {
+            return Some("minified file name");
+        }
+        if content
+            .lines()
+            .any(|line| line.len() > GENERATED_LINE_LENGTH)
+        {
+            return Some("very long lines");
         }
+        let header = content.lines().take(GENERATED_HEADER_LINES);
+        if header
+            .into_iter()
+            .any(|line| line.contains("DO NOT EDIT") || line.contains("@generated"))
+        {
+            return Some("generated-code header");
+        }
+        None
+    }
+
+    /// Check if a file should be included based on its extension
+    fn should_include_file_by_extension(&self, path: &Path) -> bool {
+        self.extension_filter.matches(path)
     }
 
     /// Check if a file is hidden (starts with '.')
@@ -316,6 +1658,224 @@ impl FileProcessor {
     }
 }
 
+#[cfg(feature = "transcode")]
+fn transcode(bytes: &[u8]) -> Option<String> {
+    crate::encoding::transcode(bytes)
+}
+
+#[cfg(not(feature = "transcode"))]
+fn transcode(_bytes: &[u8]) -> Option<String> {
+    None
+}
+
+/// The cache, even if a panic elsewhere poisoned its lock
+fn lock(cache: &Mutex<FileCache>) -> std::sync::MutexGuard<'_, FileCache> {
+    cache.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Writes a bundle piece by piece, opening and closing collections as files arrive.
+///
+/// Pieces are separated by a newline, which `emit` writes ahead of every piece
+/// but the first.
+struct BundleWriter<'a, F, W: ?Sized> {
+    processor: &'a FileProcessor,
+    paths: &'a [PathBuf],
+    formatter: &'a mut F,
+    out: &'a mut W,
+    first: bool,
+    /// Root index and name of the open collection
+    collection: Option<(usize, String)>,
+    /// Files written so far
+    files: usize,
+}
+
+impl<'a, F: OutputFormatter, W: io::Write + ?Sized> BundleWriter<'a, F, W> {
+    fn new(
+        processor: &'a FileProcessor,
+        paths: &'a [PathBuf],
+        formatter: &'a mut F,
+        out: &'a mut W,
+    ) -> Self {
+        Self {
+            processor,
+            paths,
+            formatter,
+            out,
+            first: true,
+            collection: None,
+            files: 0,
+        }
+    }
+
+    fn emit(&mut self, piece: &str) -> io::Result<()> {
+        if !self.first {
+            self.out.write_all(b"\n")?;
+        }
+        self.first = false;
+        self.out.write_all(piece.as_bytes())
+    }
+
+    /// Write the prefixes, the start of the output and the table of contents of `files`
+    fn start(&mut self, files: &[FileEntry]) -> Result<()> {
+        let processor = self.processor;
+        // Instruction text goes outside the formatter's own wrapper
+        for prefix in &processor.prefixes {
+            self.emit(prefix)?;
+            self.emit("")?;
+        }
+
+        let start = self.formatter.start_output();
+        if !start.is_empty() {
+            self.emit(&start)?;
+        }
+
+        // Generate and add table of contents if requested
+        if let Some(toc_mode) = processor.toc_mode {
+            let mut tree_generator = TreeGenerator::new();
+            if processor.toc_tokens {
+                let token_counts = files
+                    .iter()
+                    .map(|file| (file.path.clone(), processor.token_count(file)))
+                    .collect();
+                tree_generator = tree_generator.with_token_counts(token_counts);
+            }
+            let trees = tree_generator.generate_tree(self.paths, files);
+            let toc = tree_generator.render_tree(&trees, toc_mode);
+
+            if !toc.is_empty() {
+                let formatted_toc = self.formatter.format_table_of_contents(&toc);
+                if !formatted_toc.is_empty() {
+                    self.emit(&formatted_toc)?;
+                    self.emit("")?; // Add blank line after TOC
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write one file, grouped per input root when collections are enabled
+    fn file(&mut self, file: &FileEntry) -> Result<()> {
+        if self.processor.collections
+            && self
+                .collection
+                .as_ref()
+                .is_none_or(|(root, _)| *root != file.root)
+        {
+            self.end_collection()?;
+            let name = self
+                .paths
+                .get(file.root)
+                .map(|root| collection_name(root))
+                .unwrap_or_default();
+            let start = self.formatter.start_collection(&name);
+            if !start.is_empty() {
+                self.emit(&start)?;
+            }
+            self.collection = Some((file.root, name));
+        }
+
+        let entry = self.processor.format_entry(self.formatter, file);
+        self.emit(&entry)?;
+        self.files += 1;
+        Ok(())
+    }
+
+    fn end_collection(&mut self) -> Result<()> {
+        if let Some((_, name)) = self.collection.take() {
+            let end = self.formatter.end_collection(&name);
+            if !end.is_empty() {
+                self.emit(&end)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Close the open collection and write the end of the output and the
+    /// suffixes, returning the number of files written
+    fn finish(mut self) -> Result<usize> {
+        self.end_collection()?;
+        let end = self.formatter.end_output();
+        if !end.is_empty() {
+            self.emit(&end)?;
+        }
+
+        for suffix in &self.processor.suffixes {
+            self.emit("")?;
+            self.emit(suffix)?;
+        }
+        Ok(self.files)
+    }
+}
+
+/// Name a collection after its input root (`.` resolves to the current directory's name)
+fn collection_name(root: &Path) -> String {
+    root.canonicalize()
+        .ok()
+        .as_deref()
+        .unwrap_or(root)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root.display().to_string())
+}
+
+/// Copy of `file` cut to its first `lines` lines with a truncation marker
+fn truncated_entry(file: &FileEntry, lines: usize) -> FileEntry {
+    let mut entry = file.clone();
+    entry.content = HeadTail::new(lines, 0).apply(&file.path, &file.content);
+    entry
+}
+
+/// Is this file a directory preamble?
+fn is_preamble(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| PREAMBLE_FILE_NAMES.contains(&name))
+        .unwrap_or(false)
+}
+
+/// Sort rank used by `--readme-first`: READMEs, then other docs, then everything else
+fn doc_rank(path: &Path) -> u8 {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if name.starts_with("readme") {
+        0
+    } else if [".md", ".markdown", ".rst", ".adoc", ".txt"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+    {
+        1
+    } else {
+        2
+    }
+}
+
+/// Replace each file whose content repeats an earlier file's with a stub naming
+/// the first copy. Blank files are left alone; the stub would be longer.
+fn dedupe_files(files: &mut [FileEntry]) {
+    let mut first_copies: HashMap<String, PathBuf> = HashMap::new();
+    for file in files {
+        if file.content.trim().is_empty() {
+            continue;
+        }
+        let hash = sha256_hex(file.content.as_bytes());
+        match first_copies.get(&hash) {
+            Some(first) => file.content = format!("[identical to {}]", first.display()),
+            None => {
+                first_copies.insert(hash, file.path.clone());
+            }
+        }
+    }
+}
+
+fn compile_content_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| {
+        FilesToPromptError::PatternError(format!("Invalid content regex '{}': {}", pattern, e))
+    })
+}
+
 fn map_walk_error(err: ignore::Error) -> FilesToPromptError {
     if let Some(io_err) = err.io_error() {
         FilesToPromptError::Io(io::Error::new(io_err.kind(), io_err.to_string()))
@@ -327,7 +1887,7 @@ fn map_walk_error(err: ignore::Error) -> FilesToPromptError {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::output::DefaultFormatter;
+    use crate::output::{DefaultFormatter, XmlFormatter};
     use std::fs;
     use tempfile::TempDir;
 
@@ -349,6 +1909,25 @@ mod tests {
         assert!(!processor.should_include_file_by_extension(&PathBuf::from("test.rs")));
     }
 
+    #[test]
+    fn test_should_include_file_by_compound_extension() {
+        let processor = FileProcessor::new(
+            vec!["d.ts".to_string(), "blade.php".to_string()],
+            false,
+            false,
+            false,
+            vec![],
+            false,
+            None,
+        )
+        .unwrap();
+
+        assert!(processor.should_include_file_by_extension(&PathBuf::from("index.d.ts")));
+        assert!(processor.should_include_file_by_extension(&PathBuf::from("home.blade.php")));
+        assert!(!processor.should_include_file_by_extension(&PathBuf::from("index.ts")));
+        assert!(!processor.should_include_file_by_extension(&PathBuf::from("index.php")));
+    }
+
     #[test]
     fn test_should_include_file_no_extensions() {
         let processor =
@@ -369,6 +1948,225 @@ mod tests {
         assert!(!processor.is_hidden_file(&PathBuf::from("visible.txt")));
     }
 
+    #[test]
+    fn test_explain_filters() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join(".config")).unwrap();
+        fs::write(temp_dir.path().join(".config/settings.py"), "x = 1").unwrap();
+        fs::write(temp_dir.path().join(".config/local.py"), "y = 1").unwrap();
+        fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+        for name in ["index.js", "util.js", "pkg/main.js"] {
+            fs::write(temp_dir.path().join("node_modules").join(name), "").unwrap();
+        }
+        fs::write(temp_dir.path().join("a.py"), "a = 1").unwrap();
+        fs::write(temp_dir.path().join("b.py"), "b = 1").unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "notes").unwrap();
+
+        let processor = FileProcessor::new(
+            vec!["rs".to_string()],
+            false,
+            false,
+            false,
+            vec![],
+            false,
+            None,
+        )
+        .unwrap();
+        let paths = [temp_dir.path().to_path_buf()];
+        let (files, decisions) = processor.collect_files_with_decisions(&paths).unwrap();
+        assert!(files.is_empty());
+        let report = decisions.report();
+
+        // Pruned directories count once, not once per file inside
+        assert_eq!(report.extension, 3);
+        assert_eq!(report.hidden, 1);
+        assert_eq!(report.default_ignore, 1);
+        assert_eq!(report.dominant(), Some((FilterReason::Extension, 3)));
+    }
+
+    #[test]
+    fn test_content_filters() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.rs"), "if flags.FeatureFlagX {}").unwrap();
+        fs::write(
+            temp_dir.path().join("b.rs"),
+            "// FeatureFlagX is deprecated",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("c.rs"), "fn unrelated() {}").unwrap();
+
+        let processor = FileProcessor::new(vec![], false, false, false, vec![], false, None)
+            .unwrap()
+            .with_contains("FeatureFlag[A-Z]")
+            .unwrap()
+            .with_not_contains("(?i)deprecated")
+            .unwrap();
+        let paths = [temp_dir.path().to_path_buf()];
+        let (files, decisions) = processor.collect_files_with_decisions(&paths).unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert!(files[0].path.ends_with("a.rs"));
+        let report = decisions.report();
+        assert_eq!(report.content, 2);
+        assert!(processor.with_contains("(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_dedupe_files() {
+        let mut files = vec![
+            FileEntry::new(
+                PathBuf::from("a/.eslintrc"),
+                "{ \"root\": true }".to_string(),
+            ),
+            FileEntry::new(PathBuf::from("a/__init__.py"), String::new()),
+            FileEntry::new(
+                PathBuf::from("b/.eslintrc"),
+                "{ \"root\": true }".to_string(),
+            ),
+            FileEntry::new(PathBuf::from("b/__init__.py"), String::new()),
+            FileEntry::new(PathBuf::from("b/main.py"), "print()".to_string()),
+        ];
+        dedupe_files(&mut files);
+
+        let contents: Vec<&str> = files.iter().map(|f| f.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            [
+                "{ \"root\": true }",
+                "",
+                "[identical to a/.eslintrc]",
+                "",
+                "print()"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_looks_generated() {
+        let generated =
+            |name: &str, content: &str| FileProcessor::looks_generated(Path::new(name), content);
+        assert_eq!(
+            generated("dist/app.min.js", "var a=1;"),
+            Some("minified file name")
+        );
+        assert_eq!(
+            generated("bundle.js", &"x".repeat(GENERATED_LINE_LENGTH + 1)),
+            Some("very long lines")
+        );
+        assert_eq!(
+            generated(
+                "api.pb.go",
+                "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n"
+            ),
+            Some("generated-code header")
+        );
+        assert_eq!(generated("main.go", "package main\n"), None);
+        assert_eq!(
+            generated(
+                "notes.md",
+                "intro\n\n\n\n\n\nDO NOT EDIT the config by hand\n"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_submodules_skipped_by_default() {
+        let temp_dir = TempDir::new().unwrap();
+        let submodule = temp_dir.path().join("plugins/tool");
+        fs::create_dir_all(&submodule).unwrap();
+        fs::write(submodule.join(".git"), "gitdir: ../../.git/modules/tool").unwrap();
+        fs::write(submodule.join("tool.rs"), "fn tool() {}").unwrap();
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let processor =
+            FileProcessor::new(vec![], false, false, false, vec![], false, None).unwrap();
+        let paths = [temp_dir.path().to_path_buf()];
+        let names = |files: Vec<FileEntry>| -> Vec<String> {
+            files
+                .iter()
+                .map(|f| f.path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+
+        let (files, decisions) = processor.collect_files_with_decisions(&paths).unwrap();
+        assert_eq!(names(files), ["main.rs"]);
+        let report = decisions.report();
+        assert_eq!(report.submodule, 1);
+
+        let processor = processor.with_include_submodules(true);
+        assert_eq!(
+            names(processor.collect_files(&paths).unwrap()),
+            ["main.rs", "tool.rs"]
+        );
+    }
+
+    #[test]
+    fn test_doc_rank() {
+        assert_eq!(doc_rank(Path::new("pkg/README.md")), 0);
+        assert_eq!(doc_rank(Path::new("pkg/readme")), 0);
+        assert_eq!(doc_rank(Path::new("pkg/DESIGN.md")), 1);
+        assert_eq!(doc_rank(Path::new("pkg/lib.rs")), 2);
+        assert_eq!(doc_rank(Path::new("pkg/subdir")), 2);
+    }
+
+    #[test]
+    fn test_stream_files_matches_format_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("first");
+        let second = temp_dir.path().join("second");
+        fs::create_dir_all(first.join("sub")).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        fs::write(first.join("a.txt"), "alpha").unwrap();
+        fs::write(first.join("sub/b.txt"), "beta").unwrap();
+        fs::write(second.join("c.txt"), "gamma").unwrap();
+
+        let processor = FileProcessor::new(vec![], false, false, false, vec![], false, None)
+            .unwrap()
+            .with_collections(true)
+            .with_prefix("Review this.");
+        assert!(processor.can_stream());
+        let paths = [first, second, temp_dir.path().join("missing")];
+
+        let files = processor.collect_files(&paths).unwrap();
+        let expected = processor
+            .format_files(&paths, &files, &mut XmlFormatter::new())
+            .unwrap();
+        let mut streamed = Vec::new();
+        let (written, decisions) = processor
+            .stream_files(&paths, &mut XmlFormatter::new(), &mut streamed)
+            .unwrap();
+
+        assert_eq!(written, 3);
+        assert_eq!(decisions.files.len(), 3);
+        assert_eq!(String::from_utf8(streamed).unwrap(), expected);
+
+        // Nothing at all is written when every file is filtered out
+        let processor = processor.with_contains("absent").unwrap();
+        let mut streamed = Vec::new();
+        let (written, _) = processor
+            .stream_files(&paths, &mut XmlFormatter::new(), &mut streamed)
+            .unwrap();
+        assert_eq!(written, 0);
+        assert!(streamed.is_empty());
+    }
+
+    #[test]
+    fn test_bundle_counts_with_the_tokenizer() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "x".repeat(40)).unwrap();
+        let paths = [temp_dir.path().to_path_buf()];
+
+        let processor = FileProcessor::new(vec![], false, false, false, vec![], false, None)
+            .unwrap()
+            .with_tokenizer(Tokenizer::Approx);
+        let bundle = processor
+            .bundle(&paths, &mut DefaultFormatter::new())
+            .unwrap();
+        assert_eq!(bundle.files.len(), 1);
+        assert_eq!(bundle.tokens(), bundle.output.len().div_ceil(4));
+    }
+
     #[test]
     fn test_process_single_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -377,15 +2175,187 @@ mod tests {
 
         let processor =
             FileProcessor::new(vec![], false, false, false, vec![], false, None).unwrap();
-        let mut formatter = DefaultFormatter::new();
-        let mut output = Vec::new();
+        let mut walk = Walk::new(false, None);
+
+        processor.process_file(&file_path, &mut walk).unwrap();
+        let files = walk.files;
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, file_path);
+        assert_eq!(files[0].content, "Hello, world!");
+
+        let output = processor
+            .format_files(
+                std::slice::from_ref(&file_path),
+                &files,
+                &mut DefaultFormatter::new(),
+            )
+            .unwrap();
+        assert!(output.contains("test.txt"));
+        assert!(output.contains("Hello, world!"));
+    }
+
+    #[test]
+    fn test_write_files_streams_pieces() {
+        /// Keeps every write separately
+        struct Pieces(Vec<Vec<u8>>);
+        impl io::Write for Pieces {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.push(buf.to_vec());
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
 
+        let files = vec![
+            FileEntry::new("a.txt".into(), "first".to_string()),
+            FileEntry::new("b.txt".into(), "second".to_string()),
+        ];
+        let processor = FileProcessor::new(vec![], false, false, false, vec![], false, None)
+            .unwrap()
+            .with_prefix("Review this")
+            .with_suffix("Thanks");
+
+        let mut pieces = Pieces(Vec::new());
         processor
-            .process_file(&file_path, &mut formatter, &mut output)
+            .write_files(&[], &files, &mut DefaultFormatter::new(), &mut pieces)
+            .unwrap();
+        let expected = processor
+            .format_files(&[], &files, &mut DefaultFormatter::new())
+            .unwrap();
+
+        // Each file is written on its own, and together they match the joined output
+        assert!(pieces.0.len() > files.len());
+        assert_eq!(pieces.0.concat(), expected.as_bytes());
+        assert!(expected.starts_with("Review this\n\n"));
+        assert!(expected.ends_with("\n\nThanks"));
+    }
+
+    #[test]
+    fn test_read_budget_stops_walk() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            // Forty bytes, so ten tokens with the byte estimate
+            fs::write(temp_dir.path().join(name), "x".repeat(40)).unwrap();
+        }
+        let paths = vec![temp_dir.path().to_path_buf()];
+        let processor = || FileProcessor::new(vec![], false, false, false, vec![], false, None);
+
+        let budgeted = processor()
+            .unwrap()
+            .with_tokenizer(Tokenizer::Approx)
+            .with_read_budget(15);
+        let names: Vec<_> = budgeted
+            .collect_files(&paths)
+            .unwrap()
+            .iter()
+            .map(|file| file.path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+
+        // Dedupe could still free up room after the walk, so everything is read
+        let deduped = processor()
+            .unwrap()
+            .with_tokenizer(Tokenizer::Approx)
+            .with_read_budget(15)
+            .with_dedupe(true);
+        assert_eq!(deduped.collect_files(&paths).unwrap().len(), 3);
+
+        // The filter command decides before reading, so the budget still applies
+        let filtered = processor()
+            .unwrap()
+            .with_tokenizer(Tokenizer::Approx)
+            .with_read_budget(5)
+            .with_filter_cmd("grep -v a.txt");
+        let (files, decisions) = filtered.collect_files_with_decisions(&paths).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].path.ends_with("b.txt"));
+        assert_eq!(decisions.files[0].reason, Some(FilterReason::FilterCmd));
+        assert_eq!(decisions.files.len(), 2);
+    }
+
+    #[test]
+    fn test_ordering_strategy() {
+        struct Largest;
+        impl OrderingStrategy for Largest {
+            fn order(&self, mut files: Vec<FileEntry>) -> Vec<FileEntry> {
+                files.sort_by_key(|file| std::cmp::Reverse(file.content.len()));
+                files
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let (a, b) = (temp_dir.path().join("a"), temp_dir.path().join("b"));
+        for (dir, sizes) in [(&a, [1, 30]), (&b, [20, 40])] {
+            fs::create_dir(dir).unwrap();
+            for (i, size) in sizes.into_iter().enumerate() {
+                fs::write(dir.join(format!("{}.txt", i)), "x".repeat(size)).unwrap();
+            }
+        }
+        let paths = vec![a, b];
+        let processor = FileProcessor::builder()
+            .with_ordering(Largest)
+            .build()
+            .unwrap();
+        let sizes =
+            |files: Vec<FileEntry>| files.iter().map(|f| f.content.len()).collect::<Vec<_>>();
+
+        assert_eq!(
+            sizes(processor.collect_files(&paths).unwrap()),
+            [40, 30, 20, 1]
+        );
+
+        let processor = processor.with_collections(true);
+        assert_eq!(
+            sizes(processor.collect_files(&paths).unwrap()),
+            [30, 1, 40, 20]
+        );
+    }
+
+    #[test]
+    fn test_fit_token_budget_priorities() {
+        let lines: String = (0..20).map(|i| format!("lib line {:>10}\n", i)).collect();
+        let files = vec![
+            FileEntry::new(PathBuf::from("tests/t.rs"), "x".repeat(40)),
+            FileEntry::new(PathBuf::from("src/main.rs"), "y".repeat(40)),
+            FileEntry::new(PathBuf::from("docs/huge.md"), "z".repeat(4000)),
+            FileEntry::new(PathBuf::from("src/lib.rs"), lines),
+        ];
+        let processor = FileProcessor::new(vec![], false, false, false, vec![], false, None)
+            .unwrap()
+            .with_tokenizer(Tokenizer::Approx)
+            .with_priority("src/main.rs:high")
+            .unwrap()
+            .with_priority("docs/**:high")
+            .unwrap()
+            .with_priority("tests/**:low")
+            .unwrap();
+
+        let fit = processor
+            .fit_token_budget(&[], &files, &DefaultFormatter::new(), 100)
             .unwrap();
+        let kept: Vec<_> = fit.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            kept,
+            vec![PathBuf::from("src/main.rs"), PathBuf::from("src/lib.rs")]
+        );
+        assert_eq!(fit.truncated, Some(PathBuf::from("src/lib.rs")));
+        assert_eq!(
+            fit.dropped,
+            vec![PathBuf::from("tests/t.rs"), PathBuf::from("docs/huge.md")]
+        );
+
+        assert_eq!(
+            fit.plan(&files),
+            "drop      tests/t.rs\n\
+             include   src/main.rs\n\
+             drop      docs/huge.md\n\
+             truncate  src/lib.rs\n\
+             \n1 included, 1 truncated, 2 dropped\n"
+        );
 
-        assert_eq!(output.len(), 1);
-        assert!(output[0].contains("test.txt"));
-        assert!(output[0].contains("Hello, world!"));
+        assert!(processor.with_priority("src/**:urgent").is_err());
     }
 }
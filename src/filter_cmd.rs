@@ -0,0 +1,163 @@
+//! External predicate command (`--filter-cmd`)
+//!
+//! A command containing `{}` runs once per candidate through `sh -c`, with the
+//! placeholder replaced by the quoted path; exit status 0 keeps the file.
+//! Without a placeholder the command runs once, reads the candidate paths on
+//! stdin, one per line, and keeps the paths it prints back on stdout. As with
+//! `grep`, exit status 1 just means nothing was selected.
+
+use crate::{FilesToPromptError, Result};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Placeholder replaced by the candidate path in per-file mode
+pub const PATH_PLACEHOLDER: &str = "{}";
+
+/// Shell command deciding which candidate files to keep
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterCmd {
+    command: String,
+}
+
+impl FilterCmd {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+        }
+    }
+
+    /// Whether the command runs once per file rather than once per list
+    pub fn per_file(&self) -> bool {
+        self.command.contains(PATH_PLACEHOLDER)
+    }
+
+    /// The subset of `paths` the command approves
+    pub fn approve(&self, paths: &[&Path]) -> Result<HashSet<PathBuf>> {
+        if paths.is_empty() {
+            return Ok(HashSet::new());
+        }
+        if self.per_file() {
+            let mut approved = HashSet::new();
+            for path in paths {
+                if self.approves(path)? {
+                    approved.insert(path.to_path_buf());
+                }
+            }
+            Ok(approved)
+        } else {
+            self.approve_list(paths)
+        }
+    }
+
+    /// Whether a per-file command approves `path`
+    pub fn approves(&self, path: &Path) -> Result<bool> {
+        let command = self
+            .command
+            .replace(PATH_PLACEHOLDER, &shell_quote(&path.to_string_lossy()));
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status()
+            .map_err(|e| self.error(e))?;
+        Ok(status.success())
+    }
+
+    fn approve_list(&self, paths: &[&Path]) -> Result<HashSet<PathBuf>> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.error(e))?;
+
+        let input: String = paths
+            .iter()
+            .map(|path| format!("{}\n", path.to_string_lossy()))
+            .collect();
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        // Written from a separate thread so a command that prints before it has
+        // read everything can't deadlock against a full pipe
+        let writer = std::thread::spawn(move || {
+            // A command that stops reading early (e.g. `head`) closes the pipe;
+            // what it printed so far is still its answer
+            let _ = stdin.write_all(input.as_bytes());
+        });
+        let output = child.wait_with_output().map_err(|e| self.error(e))?;
+        let _ = writer.join();
+
+        if !matches!(output.status.code(), Some(0 | 1)) {
+            return Err(FilesToPromptError::FilterCommand(format!(
+                "`{}` exited with {}",
+                self.command, output.status
+            )));
+        }
+        let kept: HashSet<&str> = std::str::from_utf8(&output.stdout)
+            .map_err(|_| {
+                FilesToPromptError::FilterCommand(format!(
+                    "`{}` printed invalid UTF-8",
+                    self.command
+                ))
+            })?
+            .lines()
+            .collect();
+        Ok(paths
+            .iter()
+            .filter(|path| kept.contains(path.to_string_lossy().as_ref()))
+            .map(|path| path.to_path_buf())
+            .collect())
+    }
+
+    fn error(&self, error: std::io::Error) -> FilesToPromptError {
+        FilesToPromptError::FilterCommand(format!("could not run `{}`: {}", self.command, error))
+    }
+}
+
+/// Quote `value` for POSIX `sh`
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("src/main.rs"), "'src/main.rs'");
+        assert_eq!(shell_quote("it's.txt"), r"'it'\''s.txt'");
+    }
+
+    #[test]
+    fn test_approve_per_file_and_list() {
+        let paths = [
+            Path::new("src/a.rs"),
+            Path::new("src/b.py"),
+            Path::new("it's.rs"),
+        ];
+        let expected: HashSet<PathBuf> = [PathBuf::from("src/a.rs"), PathBuf::from("it's.rs")]
+            .into_iter()
+            .collect();
+
+        let per_file = FilterCmd::new("case {} in *.rs) exit 0;; *) exit 1;; esac");
+        assert!(per_file.per_file());
+        assert_eq!(per_file.approve(&paths).unwrap(), expected);
+
+        let list = FilterCmd::new("grep '\\.rs$'");
+        assert!(!list.per_file());
+        assert_eq!(list.approve(&paths).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_failing_list_command_is_an_error() {
+        let none = FilterCmd::new("grep nothing-matches").approve(&[Path::new("a.rs")]);
+        assert!(none.unwrap().is_empty());
+
+        let result = FilterCmd::new("exit 3").approve(&[Path::new("a.rs")]);
+        assert!(matches!(result, Err(FilesToPromptError::FilterCommand(_))));
+    }
+}
@@ -0,0 +1,745 @@
+//! Queries against the git repository containing the input paths
+//!
+//! Everything here shells out to `git` and degrades gracefully: outside a
+//! repository, or without git installed, callers get a neutral fallback.
+//! Cloning remote inputs is the exception, since there is nothing to fall back to.
+
+use crate::{FilesToPromptError, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// How far back `--sort git-churn` looks when counting commits
+pub const CHURN_WINDOW: &str = "90 days ago";
+
+/// Directory to run git in for `path`: the path itself or its parent
+fn git_dir(path: &Path) -> &Path {
+    if path.is_dir() {
+        path
+    } else {
+        path.parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    }
+}
+
+/// Run git in `dir` and return its stdout, if it succeeded
+fn git_stdout(dir: &Path, args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Run git in `dir` and return its trimmed stdout, if it succeeded
+fn git_output(dir: &Path, args: &[&str]) -> Option<String> {
+    git_stdout(dir, args).map(|stdout| stdout.trim().to_string())
+}
+
+/// Top level of the repository containing `path`, canonicalized
+fn toplevel(path: &Path) -> Option<PathBuf> {
+    let toplevel = PathBuf::from(git_output(
+        git_dir(path),
+        &["rev-parse", "--show-toplevel"],
+    )?);
+    Some(fs::canonicalize(&toplevel).unwrap_or(toplevel))
+}
+
+/// Commit `reference` names in the repository containing `path`
+pub fn resolve_commit(path: &Path, reference: &str) -> Option<String> {
+    let commit = format!("{}^{{commit}}", reference);
+    git_output(
+        git_dir(path),
+        &["rev-parse", "--verify", "--quiet", &commit],
+    )
+}
+
+/// Current git branch for `path`, or `unknown` outside a repository
+pub fn current_branch(path: &Path) -> String {
+    git_output(git_dir(path), &["rev-parse", "--abbrev-ref", "HEAD"])
+        .filter(|branch| !branch.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Number of commits since `since` touching each file in the repositories
+/// containing `paths`, keyed by canonical path.
+///
+/// Paths outside a repository contribute nothing.
+pub fn commit_counts(paths: &[PathBuf], since: &str) -> HashMap<PathBuf, usize> {
+    let mut toplevels = Vec::new();
+    for path in paths {
+        if let Some(toplevel) = toplevel(path) {
+            if !toplevels.contains(&toplevel) {
+                toplevels.push(toplevel);
+            }
+        }
+    }
+
+    let mut counts = HashMap::new();
+    for toplevel in toplevels {
+        let since = format!("--since={}", since);
+        let args = [
+            "-c",
+            "core.quotePath=false",
+            "log",
+            &since,
+            "--name-only",
+            "--format=",
+        ];
+        let Some(log) = git_output(&toplevel, &args) else {
+            continue;
+        };
+        for file in log.lines().filter(|line| !line.is_empty()) {
+            *counts.entry(toplevel.join(file)).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// Unix time of the last commit touching each file in the repositories
+/// containing `paths`, keyed by canonical path.
+///
+/// Paths outside a repository contribute nothing.
+pub fn last_commit_times(paths: &[PathBuf]) -> HashMap<PathBuf, i64> {
+    let mut toplevels = Vec::new();
+    for path in paths {
+        if let Some(toplevel) = toplevel(path) {
+            if !toplevels.contains(&toplevel) {
+                toplevels.push(toplevel);
+            }
+        }
+    }
+
+    let mut times = HashMap::new();
+    for toplevel in toplevels {
+        let args = [
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--name-only",
+            "--format=%x00%ct",
+        ];
+        let Some(log) = git_output(&toplevel, &args) else {
+            continue;
+        };
+        // Newest commits come first, so the first time seen for a file is its last commit
+        let mut time = 0;
+        for line in log.lines().filter(|line| !line.is_empty()) {
+            match line.strip_prefix('\0') {
+                Some(timestamp) => time = timestamp.parse().unwrap_or(0),
+                None => {
+                    times.entry(toplevel.join(line)).or_insert(time);
+                }
+            }
+        }
+    }
+    times
+}
+
+/// The last `count` commits touching `paths` as `<short hash> <subject>` lines,
+/// newest first. Paths in different repositories get up to `count` commits each.
+pub fn recent_commits(paths: &[PathBuf], count: usize) -> Vec<String> {
+    let mut repositories: Vec<(PathBuf, Vec<PathBuf>)> = Vec::new();
+    for path in paths {
+        let Some(toplevel) = toplevel(path) else {
+            continue;
+        };
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+        match repositories.iter_mut().find(|(t, _)| *t == toplevel) {
+            Some((_, paths)) => paths.push(path),
+            None => repositories.push((toplevel, vec![path])),
+        }
+    }
+
+    let mut commits = Vec::new();
+    for (toplevel, paths) in repositories {
+        let max_count = format!("--max-count={}", count);
+        let mut args = vec!["log", &max_count, "--format=%h %s", "--"];
+        args.extend(paths.iter().filter_map(|path| path.to_str()));
+        if let Some(log) = git_output(&toplevel, &args) {
+            commits.extend(log.lines().map(str::to_string));
+        }
+    }
+    commits
+}
+
+/// Files staged in the index of the repository containing `path`, as canonical
+/// paths. Deletions are left out. `None` outside a repository.
+pub fn staged_files(path: &Path) -> Option<Vec<PathBuf>> {
+    let toplevel = toplevel(path)?;
+    let args = [
+        "-c",
+        "core.quotePath=false",
+        "diff",
+        "--cached",
+        "--name-only",
+        "--diff-filter=d",
+    ];
+    let staged = git_output(&toplevel, &args)?;
+    Some(
+        staged
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|file| toplevel.join(file))
+            .collect(),
+    )
+}
+
+/// Files tracked in the repository containing `path` (`git ls-files`), as
+/// canonical paths. `None` outside a repository.
+pub fn tracked_files(path: &Path) -> Option<Vec<PathBuf>> {
+    let toplevel = toplevel(path)?;
+    Some(
+        ls_files(&toplevel)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|file| toplevel.join(file))
+            .collect(),
+    )
+}
+
+/// Files in the repository containing `path` that git doesn't track yet and
+/// doesn't ignore, as canonical paths. `None` outside a repository.
+pub fn untracked_files(path: &Path) -> Option<Vec<PathBuf>> {
+    let toplevel = toplevel(path)?;
+    let args = [
+        "-c",
+        "core.quotePath=false",
+        "ls-files",
+        "--others",
+        "--exclude-standard",
+    ];
+    Some(
+        git_output(&toplevel, &args)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|file| toplevel.join(file))
+            .collect(),
+    )
+}
+
+/// `git ls-files` output for the repository at `toplevel`, relative to it
+fn ls_files(toplevel: &Path) -> Option<String> {
+    git_output(toplevel, &["-c", "core.quotePath=false", "ls-files"])
+}
+
+/// Tracked files in the repository containing `path` whose last commit falls
+/// within `since` and `until`, as canonical paths. The bounds take any date git
+/// understands (`2024-01-01`, `2 weeks ago`). `None` outside a repository.
+pub fn files_last_committed(
+    path: &Path,
+    since: Option<&str>,
+    until: Option<&str>,
+) -> Option<Vec<PathBuf>> {
+    let toplevel = toplevel(path)?;
+    let tracked = ls_files(&toplevel)?;
+    let committed_since = |date: &str| -> Option<HashSet<String>> {
+        let since = format!("--since={}", date);
+        let args = [
+            "-c",
+            "core.quotePath=false",
+            "log",
+            &since,
+            "--name-only",
+            "--format=",
+        ];
+        let log = git_output(&toplevel, &args)?;
+        Some(log.lines().map(str::to_string).collect())
+    };
+    // Last committed at or after `since` means committed since then; at or
+    // before `until` means not committed since then
+    let recent = match since {
+        Some(since) => Some(committed_since(since)?),
+        None => None,
+    };
+    let too_recent = match until {
+        Some(until) => Some(committed_since(until)?),
+        None => None,
+    };
+
+    Some(
+        tracked
+            .lines()
+            .filter(|file| !file.is_empty())
+            .filter(|file| recent.as_ref().is_none_or(|recent| recent.contains(*file)))
+            .filter(|file| {
+                !too_recent
+                    .as_ref()
+                    .is_some_and(|too_recent| too_recent.contains(*file))
+            })
+            .map(|file| toplevel.join(file))
+            .collect(),
+    )
+}
+
+/// Files changed in `range` (`base...head`, `base..head`) in the repository
+/// containing `path`, as canonical paths. Deletions are left out. `None`
+/// outside a repository or when the range doesn't resolve.
+pub fn range_files(path: &Path, range: &str) -> Option<Vec<PathBuf>> {
+    let toplevel = toplevel(path)?;
+    let args = [
+        "-c",
+        "core.quotePath=false",
+        "diff",
+        "--name-only",
+        "--diff-filter=d",
+        range,
+        "--",
+    ];
+    Some(
+        git_output(&toplevel, &args)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|file| toplevel.join(file))
+            .collect(),
+    )
+}
+
+/// Commits in `range` touching `paths` as `<short hash> <subject>` lines, oldest
+/// first, with git's `--shortstat` line for the range. Uses the repository
+/// containing the first path.
+pub fn range_summary(paths: &[PathBuf], range: &str) -> Option<(Vec<String>, String)> {
+    let toplevel = toplevel(paths.first()?)?;
+    let pathspecs: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        .collect();
+    let pathspecs: Vec<&str> = pathspecs.iter().filter_map(|path| path.to_str()).collect();
+
+    let mut log_args = vec!["log", "--reverse", "--format=%h %s", range, "--"];
+    log_args.extend(&pathspecs);
+    let commits = git_output(&toplevel, &log_args)?
+        .lines()
+        .map(str::to_string)
+        .collect();
+
+    let mut stat_args = vec!["diff", "--shortstat", range, "--"];
+    stat_args.extend(&pathspecs);
+    let shortstat = git_output(&toplevel, &stat_args)?;
+    Some((commits, shortstat))
+}
+
+/// Unified diff hunks between `reference` and the working tree for each changed
+/// file in the repository containing `path`, keyed by canonical path.
+///
+/// Deleted and binary files are left out. `None` outside a repository or when
+/// `reference` doesn't resolve.
+pub fn diff_hunks(path: &Path, reference: &str) -> Option<Vec<(PathBuf, String)>> {
+    let toplevel = toplevel(path)?;
+    let args = [
+        "-c",
+        "core.quotePath=false",
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        "--no-prefix",
+        reference,
+        "--",
+    ];
+    let diff = git_stdout(&toplevel, &args)?;
+    Some(
+        split_diff(&diff)
+            .into_iter()
+            .map(|(file, hunks)| (toplevel.join(file), hunks))
+            .collect(),
+    )
+}
+
+/// Split `git diff --no-prefix` output into the new path and hunks of each file
+fn split_diff(diff: &str) -> Vec<(String, String)> {
+    let mut files = Vec::new();
+    let mut file: Option<String> = None;
+    let mut hunks = String::new();
+    let mut finish = |file: Option<String>, hunks: &mut String| match file {
+        Some(file) if file != "/dev/null" && !hunks.is_empty() => {
+            files.push((file, std::mem::take(hunks)))
+        }
+        _ => hunks.clear(),
+    };
+
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            finish(file.take(), &mut hunks);
+        } else if !hunks.is_empty() || line.starts_with("@@") {
+            hunks.push_str(line);
+        } else if let Some(name) = line.strip_prefix("+++ ") {
+            // Names with spaces get a trailing tab
+            file = Some(name.trim_end_matches(['\n', '\t']).to_string());
+        }
+    }
+    finish(file, &mut hunks);
+    files
+}
+
+/// Split a remote repository argument such as `https://github.com/org/repo@v1.2`
+/// into the clone URL and the optional branch or tag after `@`.
+///
+/// Returns `None` for anything that isn't an `http(s)://` URL.
+pub fn parse_remote(arg: &str) -> Option<(String, Option<String>)> {
+    let rest = arg
+        .strip_prefix("https://")
+        .or_else(|| arg.strip_prefix("http://"))?;
+    // An `@` before the first `/` is user info, not a ref
+    let (host, path) = rest.split_once('/')?;
+    let scheme = &arg[..arg.len() - rest.len()];
+    match path.split_once('@') {
+        Some((path, reference)) if !reference.is_empty() => Some((
+            format!("{}{}/{}", scheme, host, path),
+            Some(reference.to_string()),
+        )),
+        _ => Some((arg.to_string(), None)),
+    }
+}
+
+/// Shallow clone of a remote repository in a temporary directory, removed on drop
+#[derive(Debug)]
+pub struct RemoteCheckout {
+    _temp_dir: TempDir,
+    path: PathBuf,
+}
+
+impl RemoteCheckout {
+    /// Clone `url` at `reference` (a branch or tag; the default branch if `None`)
+    /// with `--depth 1`
+    pub fn shallow_clone(url: &str, reference: Option<&str>) -> Result<Self> {
+        let temp_dir = tempfile::Builder::new().prefix("fuse-clone-").tempdir()?;
+        let name = url
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .map(|name| name.trim_end_matches(".git"))
+            .filter(|name| !name.is_empty())
+            .unwrap_or("repo");
+        let checkout = Self {
+            path: temp_dir.path().join(name),
+            _temp_dir: temp_dir,
+        };
+
+        let mut command = Command::new("git");
+        // Fail instead of prompting for credentials on private or mistyped URLs
+        command.env("GIT_TERMINAL_PROMPT", "0");
+        command.args(["clone", "--quiet", "--depth", "1", "--single-branch"]);
+        if let Some(reference) = reference {
+            command.args(["--branch", reference]);
+        }
+        let output = command.arg("--").arg(url).arg(&checkout.path).output()?;
+        if !output.status.success() {
+            return Err(FilesToPromptError::Git(format!(
+                "Could not clone {}: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(checkout)
+    }
+
+    /// Root of the working tree
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_commit_counts() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q"]);
+        for (i, files) in [&["a.txt", "b.txt"][..], &["b.txt"]].iter().enumerate() {
+            for file in *files {
+                fs::write(repo.join(file), i.to_string()).unwrap();
+            }
+            git(repo, &["add", "."]);
+            git(repo, &["commit", "-q", "-m", "change"]);
+        }
+
+        let counts = commit_counts(&[repo.to_path_buf()], CHURN_WINDOW);
+        let repo = fs::canonicalize(repo).unwrap();
+        assert_eq!(counts.get(&repo.join("a.txt")), Some(&1));
+        assert_eq!(counts.get(&repo.join("b.txt")), Some(&2));
+    }
+
+    #[test]
+    fn test_last_commit_times() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q"]);
+        for (file, date) in [
+            ("a.txt", "2021-01-01T00:00:00Z"),
+            ("b.txt", "2022-01-01T00:00:00Z"),
+            ("a.txt", "2023-01-01T00:00:00Z"),
+        ] {
+            fs::write(repo.join(file), date).unwrap();
+            git(repo, &["add", file]);
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(repo)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(["commit", "-q", "-m", file])
+                .env("GIT_COMMITTER_DATE", date)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+
+        let times = last_commit_times(&[repo.to_path_buf()]);
+        let repo = fs::canonicalize(repo).unwrap();
+        assert_eq!(times.get(&repo.join("a.txt")), Some(&1_672_531_200));
+        assert_eq!(times.get(&repo.join("b.txt")), Some(&1_640_995_200));
+    }
+
+    #[test]
+    fn test_recent_commits() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q"]);
+        fs::create_dir(repo.join("src")).unwrap();
+        for (file, subject) in [
+            ("src/a.rs", "Add a"),
+            ("README.md", "Write readme"),
+            ("src/b.rs", "Add b"),
+            ("src/a.rs", "Fix a"),
+        ] {
+            fs::write(repo.join(file), subject).unwrap();
+            git(repo, &["add", file]);
+            git(repo, &["commit", "-q", "-m", subject]);
+        }
+
+        let subjects = |paths: &[PathBuf], count| -> Vec<String> {
+            recent_commits(paths, count)
+                .iter()
+                .map(|line| line.split_once(' ').unwrap().1.to_string())
+                .collect()
+        };
+        assert_eq!(
+            subjects(&[repo.join("src")], 10),
+            vec!["Fix a", "Add b", "Add a"]
+        );
+        assert_eq!(subjects(&[repo.to_path_buf()], 2), vec!["Fix a", "Add b"]);
+        assert_eq!(
+            subjects(&[repo.join("src/a.rs"), repo.join("README.md")], 10),
+            vec!["Fix a", "Write readme", "Add a"]
+        );
+    }
+
+    #[test]
+    fn test_staged_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q"]);
+        for file in ["kept.txt", "deleted.txt", "unstaged.txt"] {
+            fs::write(repo.join(file), file).unwrap();
+        }
+        git(repo, &["add", "kept.txt", "deleted.txt"]);
+        git(repo, &["commit", "-q", "-m", "initial"]);
+        fs::write(repo.join("kept.txt"), "changed").unwrap();
+        fs::write(repo.join("new.txt"), "new").unwrap();
+        git(repo, &["add", "kept.txt", "new.txt"]);
+        git(repo, &["rm", "-q", "deleted.txt"]);
+
+        let repo = fs::canonicalize(repo).unwrap();
+        assert_eq!(
+            staged_files(&repo),
+            Some(vec![repo.join("kept.txt"), repo.join("new.txt")])
+        );
+    }
+
+    #[test]
+    fn test_files_last_committed() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q"]);
+        let commit = |file: &str, date: &str| {
+            fs::write(repo.join(file), date).unwrap();
+            git(repo, &["add", file]);
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(repo)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(["commit", "-q", "-m", file])
+                .env("GIT_COMMITTER_DATE", date)
+                .env("GIT_AUTHOR_DATE", date)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        commit("old.txt", "2020-06-01T12:00:00Z");
+        commit("edited.txt", "2021-06-01T12:00:00Z");
+        commit("mid.txt", "2022-06-01T12:00:00Z");
+        commit("edited.txt", "2024-06-01T12:00:00Z");
+        fs::write(repo.join("untracked.txt"), "new").unwrap();
+        fs::write(repo.join("ignored.log"), "noise").unwrap();
+        fs::write(repo.join(".gitignore"), "*.log\n").unwrap();
+
+        let repo = fs::canonicalize(repo).unwrap();
+        assert_eq!(
+            untracked_files(&repo),
+            Some(vec![repo.join(".gitignore"), repo.join("untracked.txt")])
+        );
+        assert_eq!(
+            tracked_files(&repo),
+            Some(vec![
+                repo.join("edited.txt"),
+                repo.join("mid.txt"),
+                repo.join("old.txt")
+            ])
+        );
+        let files = |since, until| {
+            let mut files = files_last_committed(&repo, since, until).unwrap();
+            files.sort();
+            files
+        };
+        assert_eq!(
+            files(Some("2022-01-01"), None),
+            vec![repo.join("edited.txt"), repo.join("mid.txt")]
+        );
+        assert_eq!(
+            files(None, Some("2023-01-01")),
+            vec![repo.join("mid.txt"), repo.join("old.txt")]
+        );
+        assert_eq!(
+            files(Some("2021-01-01"), Some("2023-01-01")),
+            vec![repo.join("mid.txt")]
+        );
+    }
+
+    #[test]
+    fn test_range_files_and_summary() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q", "-b", "main"]);
+        fs::write(repo.join("base.txt"), "base\n").unwrap();
+        fs::write(repo.join("gone.txt"), "gone\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-q", "-m", "Base"]);
+        git(repo, &["checkout", "-q", "-b", "feature"]);
+        fs::write(repo.join("new.txt"), "new\n").unwrap();
+        git(repo, &["add", "new.txt"]);
+        git(repo, &["commit", "-q", "-m", "Add new"]);
+        git(repo, &["rm", "-q", "gone.txt"]);
+        git(repo, &["commit", "-q", "-m", "Remove gone"]);
+
+        let repo = fs::canonicalize(repo).unwrap();
+        assert_eq!(
+            range_files(&repo, "main...feature"),
+            Some(vec![repo.join("new.txt")])
+        );
+        let (commits, shortstat) =
+            range_summary(std::slice::from_ref(&repo), "main...feature").unwrap();
+        let subjects: Vec<_> = commits
+            .iter()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect();
+        assert_eq!(subjects, ["Add new", "Remove gone"]);
+        assert_eq!(shortstat, "2 files changed, 1 insertion(+), 1 deletion(-)");
+        assert_eq!(range_files(&repo, "main...nope"), None);
+    }
+
+    #[test]
+    fn test_diff_hunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q"]);
+        fs::write(repo.join("same.txt"), "same\n").unwrap();
+        fs::write(repo.join("edit me.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(repo.join("gone.txt"), "bye\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-q", "-m", "initial"]);
+        fs::write(repo.join("edit me.txt"), "one\n2\nthree\n").unwrap();
+        fs::remove_file(repo.join("gone.txt")).unwrap();
+
+        let repo = fs::canonicalize(repo).unwrap();
+        assert_eq!(
+            diff_hunks(&repo, "HEAD"),
+            Some(vec![(
+                repo.join("edit me.txt"),
+                "@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n".to_string()
+            )])
+        );
+        assert_eq!(diff_hunks(&repo, "no-such-ref"), None);
+    }
+
+    #[test]
+    fn test_parse_remote() {
+        assert_eq!(
+            parse_remote("https://github.com/org/repo"),
+            Some(("https://github.com/org/repo".to_string(), None))
+        );
+        assert_eq!(
+            parse_remote("https://github.com/org/repo@feature/x"),
+            Some((
+                "https://github.com/org/repo".to_string(),
+                Some("feature/x".to_string())
+            ))
+        );
+        assert_eq!(
+            parse_remote("https://user@example.com/repo.git"),
+            Some(("https://user@example.com/repo.git".to_string(), None))
+        );
+        assert_eq!(parse_remote("src/lib.rs"), None);
+    }
+
+    #[test]
+    fn test_remote_checkout() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("origin");
+        fs::create_dir(&repo).unwrap();
+        git(&repo, &["init", "-q"]);
+        fs::write(repo.join("a.txt"), "first").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "first"]);
+        git(&repo, &["tag", "v1"]);
+        fs::write(repo.join("a.txt"), "second").unwrap();
+        git(&repo, &["commit", "-q", "-am", "second"]);
+
+        let url = format!("file://{}", repo.display());
+        let checkout = RemoteCheckout::shallow_clone(&url, Some("v1")).unwrap();
+        assert!(checkout.path().ends_with("origin"));
+        assert_eq!(
+            fs::read_to_string(checkout.path().join("a.txt")).unwrap(),
+            "first"
+        );
+        let cloned = checkout.path().to_path_buf();
+        drop(checkout);
+        assert!(!cloned.exists());
+
+        assert!(RemoteCheckout::shallow_clone(&url, Some("no-such-tag")).is_err());
+    }
+
+    #[test]
+    fn test_outside_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(staged_files(temp_dir.path()), None);
+        assert_eq!(tracked_files(temp_dir.path()), None);
+        assert_eq!(untracked_files(temp_dir.path()), None);
+        assert!(recent_commits(&[temp_dir.path().to_path_buf()], 5).is_empty());
+        assert_eq!(diff_hunks(temp_dir.path(), "HEAD"), None);
+        assert_eq!(
+            files_last_committed(temp_dir.path(), Some("2024-01-01"), None),
+            None
+        );
+        assert!(commit_counts(&[temp_dir.path().to_path_buf()], CHURN_WINDOW).is_empty());
+        assert_eq!(current_branch(temp_dir.path()), "unknown");
+    }
+}
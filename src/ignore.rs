@@ -2,11 +2,128 @@
 //!
 //! This module focuses on user-specified `--ignore` patterns. Gitignore semantics
 //! are handled through the `ignore` crate in the traversal code, which means this
-//! helper only needs to reason about additional patterns supplied via CLI flags.
+//! helper only needs to reason about additional patterns supplied via CLI flags,
+//! plus the `.gitattributes` markers for generated and export-ignored paths.
 
 use crate::{FilesToPromptError, Result};
-use glob::Pattern;
-use std::path::Path;
+use glob::{MatchOptions, Pattern};
+use ignore::WalkBuilder;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Whether `dir` is the working tree of a git submodule, which has a `.git`
+/// file pointing at the superproject's module store instead of a `.git` directory.
+pub fn is_submodule(dir: &Path) -> bool {
+    dir.join(".git").is_file()
+}
+
+/// Directories skipped by default, even with `--ignore-gitignore`
+pub const DEFAULT_IGNORED_DIRS: &[&str] = &[
+    ".git",
+    ".hg",
+    ".svn",
+    "node_modules",
+    "target",
+    "dist",
+    "__pycache__",
+];
+
+/// Files skipped by default, even with `--ignore-gitignore`
+pub const DEFAULT_IGNORED_FILES: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini"];
+
+/// Whether `path` is on the built-in ignore list (`--no-default-ignores` disables it)
+pub fn is_default_ignored(path: &Path, is_dir: bool) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    if is_dir {
+        DEFAULT_IGNORED_DIRS.contains(&name)
+    } else {
+        DEFAULT_IGNORED_FILES.contains(&name)
+    }
+}
+
+/// Lockfiles skipped unless `--include-lockfiles`: large, generated and of little use in a prompt
+pub const LOCKFILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "Podfile.lock",
+    "go.sum",
+    "mix.lock",
+    "flake.lock",
+    "packages.lock.json",
+];
+
+/// Secrets and private keys skipped unless `--allow-sensitive`: some files should never ship
+pub const SENSITIVE_NAMES: &[&str] = &[
+    ".env",
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    "credentials.json",
+    "client_secret.json",
+    "service-account.json",
+    "secrets.yml",
+    "secrets.yaml",
+    ".netrc",
+    ".pgpass",
+    ".htpasswd",
+    ".git-credentials",
+    ".npmrc",
+    ".pypirc",
+];
+
+/// Extensions of private keys, keystores and state files that embed secrets
+pub const SENSITIVE_EXTENSIONS: &[&str] = &[
+    "pem", "key", "p12", "pfx", "jks", "keystore", "ppk", "kdbx", "tfstate",
+];
+
+/// `.env.<suffix>` templates that hold placeholders rather than secrets
+const ENV_TEMPLATE_SUFFIXES: &[&str] = &["example", "sample", "template", "dist"];
+
+/// Whether the file at `path` likely holds credentials (`.env`, `id_rsa`, `*.pem`, ...)
+pub fn is_sensitive(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    if let Some(suffix) = name.strip_prefix(".env.") {
+        return !ENV_TEMPLATE_SUFFIXES.contains(&suffix);
+    }
+    SENSITIVE_NAMES.contains(&name)
+        || path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| SENSITIVE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Vendored dependency trees skipped unless `--include-vendored`
+pub const VENDORED_DIRS: &[&str] = &["vendor", "third_party", "third-party"];
+
+/// Whether the file at `path` is a known lockfile
+pub fn is_lockfile(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| LOCKFILE_NAMES.contains(&name))
+}
+
+/// Whether the directory at `path` holds vendored dependencies
+pub fn is_vendored_dir(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| VENDORED_DIRS.contains(&name))
+}
 
 /// Normalise a path to a forward-slash separated string for glob matching.
 fn normalise_path(path: &Path) -> String {
@@ -21,9 +138,20 @@ struct CustomPattern {
     original: String,
     glob: Pattern,
     directory_only: bool,
+    /// Contains a `/` before any trailing one, so it matches the path
+    /// relative to the input root rather than the name
+    anchored: bool,
+    /// Started with `!`: re-includes what earlier patterns ignored
+    negated: bool,
 }
 
 /// Represents user-supplied ignore patterns.
+///
+/// Patterns without a `/` (other than a trailing one) match file and directory
+/// names anywhere; patterns with one, like `src/**/fixtures/*` or `/build`, match
+/// the path relative to the input root being walked. As in `.gitignore`, a
+/// leading `!` re-includes paths an earlier pattern ignored, and the last
+/// matching pattern wins.
 #[derive(Clone)]
 pub struct CustomIgnore {
     patterns: Vec<CustomPattern>,
@@ -36,15 +164,27 @@ impl CustomIgnore {
         let mut compiled = Vec::new();
         for pattern in patterns {
             let trimmed = pattern.trim();
+            let (trimmed, negated) = match trimmed.strip_prefix('!') {
+                Some(rest) => (rest, true),
+                None => (trimmed.strip_prefix('\\').unwrap_or(trimmed), false),
+            };
             if trimmed.is_empty() {
                 continue;
             }
-            let glob = Pattern::new(trimmed)
-                .map_err(|e| FilesToPromptError::PatternError(e.msg.into()))?;
+            let anchored = trimmed.trim_end_matches('/').contains('/');
+            let source = if anchored {
+                trimmed.trim_start_matches('/').trim_end_matches('/')
+            } else {
+                trimmed
+            };
+            let glob =
+                Pattern::new(source).map_err(|e| FilesToPromptError::PatternError(e.msg.into()))?;
             compiled.push(CustomPattern {
                 original: trimmed.to_string(),
                 glob,
                 directory_only: trimmed.ends_with('/'),
+                anchored,
+                negated,
             });
         }
 
@@ -54,6 +194,21 @@ impl CustomIgnore {
         })
     }
 
+    /// Read patterns from a file, one per line, skipping blank lines and `#`
+    /// comments. A leading `\` escapes a literal `#` or `!`.
+    pub fn load_patterns(path: &Path) -> Result<Vec<String>> {
+        let content = fs::read_to_string(path)?;
+        Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                line.strip_prefix("\\#")
+                    .map_or(line.to_string(), |rest| format!("#{}", rest))
+            })
+            .collect())
+    }
+
     /// Returns true when no patterns were provided.
     pub fn is_empty(&self) -> bool {
         self.patterns.is_empty()
@@ -64,17 +219,17 @@ impl CustomIgnore {
         self.ignore_files_only
     }
 
-    /// Should the given file be ignored?
-    pub fn should_ignore_file(&self, path: &Path) -> bool {
-        self.should_ignore(path, true)
+    /// Should the given file, found while walking `root`, be ignored?
+    pub fn should_ignore_file(&self, root: &Path, path: &Path) -> bool {
+        self.should_ignore(root, path, true)
     }
 
-    /// Should the given directory be ignored?
-    pub fn should_ignore_dir(&self, path: &Path) -> bool {
-        self.should_ignore(path, false)
+    /// Should the given directory, found while walking `root`, be ignored?
+    pub fn should_ignore_dir(&self, root: &Path, path: &Path) -> bool {
+        self.should_ignore(root, path, false)
     }
 
-    fn should_ignore(&self, path: &Path, is_file: bool) -> bool {
+    fn should_ignore(&self, root: &Path, path: &Path, is_file: bool) -> bool {
         if self.patterns.is_empty() {
             return false;
         }
@@ -83,13 +238,29 @@ impl CustomIgnore {
             return false;
         }
 
+        let relative = normalise_path(path.strip_prefix(root).unwrap_or(path));
         self.patterns
             .iter()
-            .any(|pattern| Self::matches_pattern(pattern, path, is_file))
+            .rev()
+            .find(|pattern| Self::matches_pattern(pattern, path, &relative, is_file))
+            .is_some_and(|pattern| !pattern.negated)
     }
 
-    fn matches_pattern(pattern: &CustomPattern, path: &Path, is_file: bool) -> bool {
+    fn matches_pattern(
+        pattern: &CustomPattern,
+        path: &Path,
+        relative: &str,
+        is_file: bool,
+    ) -> bool {
         let glob = &pattern.glob;
+        if pattern.anchored {
+            let options = MatchOptions {
+                require_literal_separator: true,
+                ..MatchOptions::new()
+            };
+            return !(is_file && pattern.directory_only) && glob.matches_with(relative, options);
+        }
+
         if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
             if glob.matches(name) {
                 return true;
@@ -102,29 +273,434 @@ impl CustomIgnore {
             }
         }
 
-        let normalised = normalise_path(path);
-        if glob.matches(&normalised) {
+        if !is_file && pattern.directory_only {
+            let target = pattern.original.trim_end_matches('/');
+            if relative == target || relative.starts_with(&format!("{}/", target)) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Curated `--ignore` sets for common project types (`--preset`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preset {
+    Node,
+    Python,
+    Rust,
+    Java,
+}
+
+impl Preset {
+    /// Name as accepted on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Node => "node",
+            Preset::Python => "python",
+            Preset::Rust => "rust",
+            Preset::Java => "java",
+        }
+    }
+
+    /// Dependency, build-output, cache and coverage paths the ecosystem produces
+    pub fn patterns(self) -> &'static [&'static str] {
+        match self {
+            Preset::Node => &[
+                "node_modules/",
+                "bower_components/",
+                "dist/",
+                "build/",
+                "coverage/",
+                ".nyc_output/",
+                ".next/",
+                ".nuxt/",
+                ".turbo/",
+                "*.min.js",
+                "*.min.css",
+                "*.map",
+            ],
+            Preset::Python => &[
+                "__pycache__/",
+                "*.pyc",
+                ".venv/",
+                "venv/",
+                ".tox/",
+                ".pytest_cache/",
+                ".mypy_cache/",
+                ".ruff_cache/",
+                "*.egg-info/",
+                "build/",
+                "dist/",
+                "htmlcov/",
+                ".coverage",
+            ],
+            Preset::Rust => &["target/"],
+            Preset::Java => &["target/", "build/", "out/", ".gradle/", "*.class", "*.jar"],
+        }
+    }
+}
+
+/// File-name patterns that restrict which files are included beneath specific roots.
+///
+/// These come from path arguments such as `src/**.rs`, where the pattern only
+/// applies to files found under `src`.
+#[derive(Clone, Default)]
+pub struct RootIncludes {
+    patterns: Vec<(PathBuf, Pattern)>,
+}
+
+impl RootIncludes {
+    /// Restrict files beneath `root` to names matching `pattern`.
+    pub fn add(&mut self, root: PathBuf, pattern: &str) -> Result<()> {
+        let glob =
+            Pattern::new(pattern).map_err(|e| FilesToPromptError::PatternError(e.msg.into()))?;
+        self.patterns.push((root, glob));
+        Ok(())
+    }
+
+    /// Is the file at `path`, found while walking `root`, allowed?
+    pub fn allows(&self, root: &Path, path: &Path) -> bool {
+        let mut scoped = self
+            .patterns
+            .iter()
+            .filter(|(pattern_root, _)| pattern_root == root)
+            .peekable();
+        if scoped.peek().is_none() {
             return true;
         }
 
-        if !is_file {
-            let mut with_trailing = normalised.clone();
-            if !with_trailing.ends_with('/') {
-                with_trailing.push('/');
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        scoped.any(|(_, glob)| glob.matches(&name))
+    }
+}
+
+/// Positive `--include` globs: when any are given, only matching files are kept.
+///
+/// Patterns containing a `/` match the path relative to the directory being
+/// walked (a leading `/` anchors a bare name there, e.g. `/Cargo.toml`); other
+/// patterns match the file name at any depth.
+#[derive(Clone, Default)]
+pub struct IncludePatterns {
+    /// Each glob, and whether it matches the relative path rather than the name
+    patterns: Vec<(Pattern, bool)>,
+}
+
+impl IncludePatterns {
+    pub fn add(&mut self, pattern: &str) -> Result<()> {
+        let anchored = pattern.contains('/');
+        let glob = Pattern::new(pattern.trim_start_matches('/'))
+            .map_err(|e| FilesToPromptError::PatternError(e.msg.into()))?;
+        self.patterns.push((glob, anchored));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Is the file at `path`, found while walking `root`, allowed?
+    pub fn allows(&self, root: &Path, path: &Path) -> bool {
+        self.patterns.is_empty() || self.matches(root, path)
+    }
+
+    /// Does the file at `path`, found while walking `root`, match any pattern?
+    pub fn matches(&self, root: &Path, path: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let relative = normalise_path(path.strip_prefix(root).unwrap_or(path));
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        self.patterns.iter().any(|(glob, anchored)| {
+            if *anchored {
+                glob.matches_with(&relative, options)
+            } else {
+                glob.matches_with(&name, options)
             }
-            if glob.matches(&with_trailing) {
-                return true;
+        })
+    }
+}
+
+/// Files under `root` matching `patterns` with every ignore rule off
+/// (`--force-include`); only `.git` is never entered.
+pub fn force_included_files(
+    root: &Path,
+    patterns: &IncludePatterns,
+    max_depth: Option<usize>,
+) -> Vec<PathBuf> {
+    let mut walker = WalkBuilder::new(root);
+    walker
+        .standard_filters(false)
+        .max_depth(max_depth)
+        .follow_links(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(|entry| entry.file_name() != ".git");
+    walker
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| patterns.matches(root, path))
+        .collect()
+}
+
+/// Directories whose contents are tests
+pub const TEST_DIRS: &[&str] = &["test", "tests", "__tests__", "spec", "testdata"];
+
+/// File names of tests across ecosystems
+pub const TEST_FILE_PATTERNS: &[&str] = &[
+    "*_test.go",
+    "test_*.py",
+    "*_test.py",
+    "*.test.[jt]s",
+    "*.test.[jt]sx",
+    "*.spec.[jt]s",
+    "*.spec.[jt]sx",
+    "*_spec.rb",
+    "*Test.java",
+    "*Test.kt",
+    "*Tests.cs",
+    "*_test.rs",
+];
+
+/// Whether the file at `path`, found while walking `root`, is a test by
+/// directory (`tests/`, `__tests__/`, ...) or name (`*_test.go`, `*.spec.ts`, ...)
+pub fn is_test_file(root: &Path, path: &Path) -> bool {
+    // The root's own name counts, so `fuse tests/ --tests-only` keeps everything
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let in_test_dir = root
+        .file_name()
+        .into_iter()
+        .chain(
+            relative
+                .parent()
+                .into_iter()
+                .flat_map(|parent| parent.components().map(|component| component.as_os_str())),
+        )
+        .any(|name| TEST_DIRS.contains(&name.to_string_lossy().as_ref()));
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    in_test_dir
+        || TEST_FILE_PATTERNS
+            .iter()
+            .any(|pattern| Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(&name)))
+}
+
+/// Which side of the test/non-test split to keep (`--no-tests`, `--tests-only`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TestFilter {
+    #[default]
+    All,
+    NoTests,
+    TestsOnly,
+}
+
+impl TestFilter {
+    /// Is the file at `path`, found while walking `root`, kept?
+    pub fn allows(self, root: &Path, path: &Path) -> bool {
+        match self {
+            TestFilter::All => true,
+            TestFilter::NoTests => !is_test_file(root, path),
+            TestFilter::TestsOnly => is_test_file(root, path),
+        }
+    }
+}
+
+/// Placeholders that may appear in `-o` paths
+pub const OUTPUT_PLACEHOLDERS: &[&str] = &["{date}", "{branch}", "{n}", "{tokens}"];
+
+/// Output files (and their backups) that must never be fused back into a bundle.
+///
+/// Re-running `fuse . -o bundle.md` would otherwise pick up the previous
+/// bundle. Placeholders in output names match any text, so snapshots from
+/// earlier runs are excluded as well.
+#[derive(Clone, Default)]
+pub struct OutputExclusions {
+    outputs: Vec<(PathBuf, Pattern)>,
+}
+
+impl OutputExclusions {
+    /// Exclude the output written to `output` (before placeholder expansion)
+    pub fn add(&mut self, output: &Path) {
+        let Some(name) = output.file_name().map(|n| n.to_string_lossy()) else {
+            return;
+        };
+        let dir = output
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let Ok(dir) = dir.canonicalize() else {
+            // Nothing can have been written into a directory that does not exist
+            return;
+        };
+
+        let name = OUTPUT_PLACEHOLDERS
+            .iter()
+            .fold(Pattern::escape(&name), |name, placeholder| {
+                name.replace(placeholder, "*")
+            });
+        for glob in [name.clone(), format!("{}.bak", name)] {
+            if let Ok(glob) = Pattern::new(&glob) {
+                self.outputs.push((dir.clone(), glob));
             }
+        }
+    }
 
-            if pattern.directory_only {
-                let target = pattern.original.trim_end_matches('/');
-                if normalised == target || normalised.starts_with(&format!("{}/", target)) {
-                    return true;
+    /// Is the file at `path` one of the excluded outputs?
+    pub fn excludes(&self, path: &Path) -> bool {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        let mut candidates = self
+            .outputs
+            .iter()
+            .filter(|(_, glob)| glob.matches(&name))
+            .peekable();
+        if candidates.peek().is_none() {
+            return false;
+        }
+
+        let dir = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        match dir.canonicalize() {
+            Ok(dir) => candidates.any(|(output_dir, _)| *output_dir == dir),
+            Err(_) => false,
+        }
+    }
+}
+
+/// One `.gitattributes` line that sets or unsets an attribute we skip on
+#[derive(Debug, Clone)]
+struct AttributeRule {
+    pattern: Pattern,
+    /// Patterns without a slash match the file name at any depth
+    basename: bool,
+    generated: Option<bool>,
+    export_ignore: Option<bool>,
+}
+
+impl AttributeRule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("[attr]") {
+            return None;
+        }
+        let mut fields = line.split_whitespace();
+        let pattern = fields.next()?;
+
+        let (mut generated, mut export_ignore) = (None, None);
+        for attribute in fields {
+            let (name, value) = match attribute.split_once('=') {
+                Some((name, value)) => (name, value != "false"),
+                None => match attribute.strip_prefix(['-', '!']) {
+                    Some(name) => (name, false),
+                    None => (attribute, true),
+                },
+            };
+            match name {
+                "linguist-generated" => generated = Some(value),
+                "export-ignore" => export_ignore = Some(value),
+                _ => {}
+            }
+        }
+        if generated.is_none() && export_ignore.is_none() {
+            return None;
+        }
+
+        let pattern = pattern.trim_end_matches('/');
+        let basename = !pattern.contains('/');
+        let pattern = Pattern::new(pattern.trim_start_matches('/')).ok()?;
+        Some(Self {
+            pattern,
+            basename,
+            generated,
+            export_ignore,
+        })
+    }
+
+    /// Does the rule cover `relative` (a path below the `.gitattributes` directory)?
+    fn matches(&self, relative: &str) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        if self.basename {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            self.pattern.matches_with(name, options)
+        } else {
+            self.pattern.matches_with(relative, options)
+        }
+    }
+}
+
+/// Paths marked `linguist-generated` or `export-ignore` in `.gitattributes`.
+///
+/// Every `.gitattributes` from the repository root down to the file is
+/// consulted, deeper files and later lines taking precedence. A rule matching
+/// a directory covers everything inside it. Files are parsed once and cached,
+/// and clones share the cache.
+#[derive(Clone, Default)]
+pub struct GitAttributes {
+    rules: Arc<Mutex<HashMap<PathBuf, Arc<Vec<AttributeRule>>>>>,
+}
+
+impl GitAttributes {
+    /// Is `path` generated or export-ignored?
+    pub fn is_skipped(&self, path: &Path) -> bool {
+        let Ok(path) = fs::canonicalize(path) else {
+            return false;
+        };
+        // Directories from the repository root (or filesystem root) down to the file
+        let mut dirs = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            dirs.push(dir);
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+
+        let (mut generated, mut export_ignore) = (false, false);
+        for dir in dirs.into_iter().rev() {
+            let relative = normalise_path(path.strip_prefix(dir).unwrap_or(&path));
+            // The path itself and every directory between it and `dir`
+            let candidates: Vec<&str> = relative
+                .match_indices('/')
+                .map(|(i, _)| &relative[..i])
+                .chain([relative.as_str()])
+                .collect();
+            for rule in self.rules_in(dir).iter() {
+                if candidates.iter().any(|candidate| rule.matches(candidate)) {
+                    generated = rule.generated.unwrap_or(generated);
+                    export_ignore = rule.export_ignore.unwrap_or(export_ignore);
                 }
             }
         }
+        generated || export_ignore
+    }
 
-        false
+    /// Rules of the `.gitattributes` in `dir`, empty if there is none
+    fn rules_in(&self, dir: &Path) -> Arc<Vec<AttributeRule>> {
+        let mut cache = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let content = fs::read_to_string(dir.join(".gitattributes")).unwrap_or_default();
+                Arc::new(content.lines().filter_map(AttributeRule::parse).collect())
+            })
+            .clone()
     }
 }
 
@@ -140,39 +716,235 @@ mod tests {
     #[test]
     fn empty_patterns_never_ignore() {
         let matcher = CustomIgnore::new(vec![], false).unwrap();
-        assert!(!matcher.should_ignore_file(&path("foo")));
-        assert!(!matcher.should_ignore_dir(&path("foo")));
+        assert!(!matcher.should_ignore_file(&path("."), &path("foo")));
+        assert!(!matcher.should_ignore_dir(&path("."), &path("foo")));
     }
 
     #[test]
     fn ignores_files_using_globs() {
         let matcher = CustomIgnore::new(vec!["*.log".into(), "temp*".into()], false).unwrap();
-        assert!(matcher.should_ignore_file(&path("debug.log")));
-        assert!(matcher.should_ignore_file(&path("temp_data.txt")));
-        assert!(!matcher.should_ignore_file(&path("keep.txt")));
+        let root = path("repo");
+        assert!(matcher.should_ignore_file(&root, &path("repo/debug.log")));
+        assert!(matcher.should_ignore_file(&root, &path("repo/logs/temp_data.txt")));
+        assert!(!matcher.should_ignore_file(&root, &path("repo/keep.txt")));
     }
 
     #[test]
     fn ignores_directories_when_allowed() {
         let matcher = CustomIgnore::new(vec!["build/".into()], false).unwrap();
-        assert!(matcher.should_ignore_dir(&path("build")));
-        assert!(matcher.should_ignore_dir(&path("build/subdir")));
+        let root = path("repo");
+        assert!(matcher.should_ignore_dir(&root, &path("repo/build")));
+        assert!(matcher.should_ignore_dir(&root, &path("repo/build/subdir")));
+        assert!(matcher.should_ignore_dir(&root, &path("repo/src/build")));
+        assert!(!matcher.should_ignore_file(&root, &path("repo/build")));
     }
 
     #[test]
     fn ignore_files_only_skips_directories() {
         let matcher = CustomIgnore::new(vec!["build/".into()], true).unwrap();
-        assert!(!matcher.should_ignore_dir(&path("build")));
+        assert!(!matcher.should_ignore_dir(&path("repo"), &path("repo/build")));
         assert!(matcher.ignore_files_only());
     }
 
     #[test]
-    fn matches_against_normalised_paths() {
-        let matcher =
-            CustomIgnore::new(vec!["src/**/*.rs".into(), "nested/file.txt".into()], false).unwrap();
-        assert!(matcher.should_ignore_file(&path("src/lib.rs")));
-        assert!(matcher.should_ignore_file(&path("src/foo/mod.rs")));
-        assert!(matcher.should_ignore_file(&path("nested/file.txt")));
-        assert!(!matcher.should_ignore_file(&path("nested/file.md")));
+    fn negated_patterns_reinclude_and_last_match_wins() {
+        let matcher = CustomIgnore::new(
+            vec!["*.log".into(), "!keep.log".into(), "\\!bang.txt".into()],
+            false,
+        )
+        .unwrap();
+        let root = path("repo");
+        assert!(matcher.should_ignore_file(&root, &path("repo/debug.log")));
+        assert!(!matcher.should_ignore_file(&root, &path("repo/keep.log")));
+        assert!(matcher.should_ignore_file(&root, &path("repo/!bang.txt")));
+
+        let matcher = CustomIgnore::new(vec!["!keep.log".into(), "*.log".into()], false).unwrap();
+        assert!(matcher.should_ignore_file(&root, &path("repo/keep.log")));
+    }
+
+    #[test]
+    fn load_patterns_skips_comments() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file = temp_dir.path().join("ignore.txt");
+        fs::write(&file, "# generated\n*.log\n\n  !keep.log  \n\\#hash\n").unwrap();
+        assert_eq!(
+            CustomIgnore::load_patterns(&file).unwrap(),
+            vec!["*.log", "!keep.log", "#hash"]
+        );
+    }
+
+    #[test]
+    fn presets_skip_ecosystem_output() {
+        let patterns = [Preset::Node, Preset::Python]
+            .iter()
+            .flat_map(|preset| preset.patterns())
+            .map(|pattern| pattern.to_string())
+            .collect();
+        let matcher = CustomIgnore::new(patterns, false).unwrap();
+        let root = path("app");
+        assert!(matcher.should_ignore_dir(&root, &path("app/web/node_modules")));
+        assert!(matcher.should_ignore_dir(&root, &path("app/pkg/__pycache__")));
+        assert!(matcher.should_ignore_dir(&root, &path("app/pkg.egg-info")));
+        assert!(matcher.should_ignore_file(&root, &path("app/static/app.min.js")));
+        assert!(!matcher.should_ignore_file(&root, &path("app/static/app.js")));
+        assert!(!matcher.should_ignore_dir(&root, &path("app/src")));
+    }
+
+    #[test]
+    fn default_ignores_match_names() {
+        assert!(is_default_ignored(&path("repo/.git"), true));
+        assert!(is_default_ignored(&path("web/node_modules"), true));
+        assert!(is_default_ignored(&path("docs/.DS_Store"), false));
+        assert!(!is_default_ignored(&path("src/target"), false));
+        assert!(!is_default_ignored(&path("src"), true));
+    }
+
+    #[test]
+    fn lockfiles_and_vendored_dirs() {
+        assert!(is_lockfile(&path("Cargo.lock")));
+        assert!(is_lockfile(&path("web/package-lock.json")));
+        assert!(!is_lockfile(&path("Cargo.toml")));
+        assert!(is_vendored_dir(&path("go/vendor")));
+        assert!(is_vendored_dir(&path("third_party")));
+        assert!(!is_vendored_dir(&path("src/vendors")));
+    }
+
+    #[test]
+    fn sensitive_files() {
+        assert!(is_sensitive(&path(".env")));
+        assert!(is_sensitive(&path("web/.env.production")));
+        assert!(is_sensitive(&path("home/.ssh/id_ed25519")));
+        assert!(is_sensitive(&path("certs/server.PEM")));
+        assert!(is_sensitive(&path("gcp/credentials.json")));
+        assert!(!is_sensitive(&path(".env.example")));
+        assert!(!is_sensitive(&path("home/.ssh/id_ed25519.pub")));
+        assert!(!is_sensitive(&path("src/env.rs")));
+    }
+
+    #[test]
+    fn test_files_by_directory_or_name() {
+        let root = path("repo");
+        let is_test = |file: &str| is_test_file(&root, &root.join(file));
+        assert!(is_test("tests/cli.rs"));
+        assert!(is_test("web/__tests__/app.js"));
+        assert!(is_test("pkg/server_test.go"));
+        assert!(is_test("app/test_models.py"));
+        assert!(is_test("src/button.spec.tsx"));
+        assert!(is_test("src/main/java/FooTest.java"));
+        assert!(!is_test("src/main.rs"));
+        assert!(!is_test("src/testing.rs"));
+        assert!(!is_test("src/contest.py"));
+        assert!(is_test_file(&path("tests"), &path("tests/fixtures.rs")));
+
+        assert!(TestFilter::NoTests.allows(&root, &root.join("src/main.rs")));
+        assert!(!TestFilter::NoTests.allows(&root, &root.join("tests/cli.rs")));
+        assert!(!TestFilter::TestsOnly.allows(&root, &root.join("src/main.rs")));
+    }
+
+    #[test]
+    fn root_includes_only_apply_to_their_root() {
+        let mut includes = RootIncludes::default();
+        includes.add(path("src"), "*.rs").unwrap();
+        assert!(includes.allows(&path("src"), &path("src/lib.rs")));
+        assert!(!includes.allows(&path("src"), &path("src/notes.md")));
+        assert!(includes.allows(&path("docs"), &path("docs/notes.md")));
+    }
+
+    #[test]
+    fn matches_against_root_relative_paths() {
+        let matcher = CustomIgnore::new(
+            vec![
+                "src/**/*.rs".into(),
+                "nested/file.txt".into(),
+                "src/**/fixtures/*".into(),
+                "/target".into(),
+            ],
+            false,
+        )
+        .unwrap();
+        let root = path("./repo");
+        let ignored = |file: &str| matcher.should_ignore_file(&root, &root.join(file));
+
+        assert!(ignored("src/lib.rs"));
+        assert!(ignored("src/foo/mod.rs"));
+        assert!(ignored("nested/file.txt"));
+        assert!(!ignored("nested/file.md"));
+        assert!(!ignored("other/nested/file.txt"));
+        assert!(ignored("src/a/b/fixtures/data.json"));
+        assert!(!ignored("src/a/fixtures/deep/data.json"));
+        assert!(!ignored("test/fixtures/data.json"));
+        assert!(matcher.should_ignore_dir(&root, &root.join("src/a/fixtures/deep")));
+        assert!(matcher.should_ignore_dir(&root, &root.join("target")));
+        assert!(!matcher.should_ignore_dir(&root, &root.join("crates/target")));
+    }
+
+    #[test]
+    fn output_exclusions_match_templated_snapshots() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut exclusions = OutputExclusions::default();
+        exclusions.add(&temp_dir.path().join("ctx-{branch}-{date}.md"));
+
+        assert!(exclusions.excludes(&temp_dir.path().join("ctx-main-2024-01-01.md")));
+        assert!(exclusions.excludes(&temp_dir.path().join("ctx-main-2024-01-01.md.bak")));
+        assert!(!exclusions.excludes(&temp_dir.path().join("notes.md")));
+        assert!(!exclusions.excludes(&path("elsewhere/ctx-main-2024-01-01.md")));
+    }
+
+    #[test]
+    fn include_patterns_match_relative_paths_or_names() {
+        let mut includes = IncludePatterns::default();
+        assert!(includes.allows(&path("repo"), &path("repo/anything.txt")));
+
+        includes.add("src/**/*.rs").unwrap();
+        includes.add("/Cargo.toml").unwrap();
+        includes.add("*.proto").unwrap();
+        let root = path("repo");
+        let allows = |file: &str| includes.allows(&root, &root.join(file));
+
+        assert!(allows("src/main.rs"));
+        assert!(allows("src/output/xml.rs"));
+        assert!(allows("Cargo.toml"));
+        assert!(allows("api/v1/service.proto"));
+        assert!(!allows("tests/cli.rs"));
+        assert!(!allows("crates/core/Cargo.toml"));
+        assert!(!allows("src/README.md"));
+    }
+
+    #[test]
+    fn gitattributes_generated_and_export_ignore() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("api/gen")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(
+            root.join(".gitattributes"),
+            "# generated code\n*.pb.go linguist-generated=true\n/docs export-ignore\n*.lock -diff\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("api/.gitattributes"),
+            "gen/** linguist-generated\ngen/keep.go -linguist-generated\n",
+        )
+        .unwrap();
+        for file in [
+            "api/service.pb.go",
+            "api/service.go",
+            "api/gen/client.go",
+            "api/gen/keep.go",
+            "docs/guide.md",
+            "Cargo.lock",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let attributes = GitAttributes::default();
+        let skipped = |file: &str| attributes.is_skipped(&root.join(file));
+        assert!(skipped("api/service.pb.go"));
+        assert!(skipped("api/gen/client.go"));
+        assert!(skipped("docs/guide.md"));
+        assert!(!skipped("api/service.go"));
+        assert!(!skipped("api/gen/keep.go"));
+        assert!(!skipped("Cargo.lock"));
     }
 }
@@ -0,0 +1,96 @@
+//! End-of-bundle integrity footer (`--integrity`)
+//!
+//! The footer is the last line of the bundle, after a blank line:
+//!
+//! ```text
+//! fuse-integrity: files=12 bytes=48213 sha256=9f86d0...
+//! ```
+//!
+//! `bytes` and `sha256` cover everything before that blank line, so a bundle
+//! cut short by a chat UI or mail client no longer matches its footer.
+
+use crate::utils::sha256_hex;
+
+/// Marker that starts the footer line
+pub const FOOTER_PREFIX: &str = "fuse-integrity:";
+
+/// Parsed integrity footer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Footer {
+    pub files: usize,
+    pub bytes: usize,
+    pub sha256: String,
+}
+
+impl Footer {
+    /// Footer describing `content` with `files` files
+    pub fn new(content: &str, files: usize) -> Self {
+        Self {
+            files,
+            bytes: content.len(),
+            sha256: sha256_hex(content.as_bytes()),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        format!(
+            "{} files={} bytes={} sha256={}",
+            FOOTER_PREFIX, self.files, self.bytes, self.sha256
+        )
+    }
+
+    /// Parse a footer line, ignoring unknown fields
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields = line.trim().strip_prefix(FOOTER_PREFIX)?;
+        let (mut files, mut bytes, mut sha256) = (None, None, None);
+        for field in fields.split_whitespace() {
+            match field.split_once('=')? {
+                ("files", value) => files = value.parse().ok(),
+                ("bytes", value) => bytes = value.parse().ok(),
+                ("sha256", value) => sha256 = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Some(Self {
+            files: files?,
+            bytes: bytes?,
+            sha256: sha256?,
+        })
+    }
+}
+
+/// Append the footer for `files` files to `output`
+pub fn append_footer(output: String, files: usize) -> String {
+    let footer = Footer::new(&output, files).render();
+    format!("{}\n\n{}", output, footer)
+}
+
+/// Check a bundle against its footer: `None` without a footer, otherwise
+/// whether the content before it is intact. Trailing whitespace added in
+/// transit after the footer is tolerated.
+pub fn verify(bundle: &str) -> Option<bool> {
+    let (content, footer) = bundle.trim_end().rsplit_once("\n\n")?;
+    let footer = Footer::parse(footer)?;
+    Some(footer == Footer::new(content, footer.files))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_footer_round_trip() {
+        let bundle = append_footer("a.txt\n---\nhello\n---".to_string(), 1);
+        let footer = bundle.lines().last().unwrap();
+        assert!(footer.starts_with("fuse-integrity: files=1 bytes=19 sha256="));
+        assert_eq!(
+            Footer::parse(footer),
+            Some(Footer::new("a.txt\n---\nhello\n---", 1))
+        );
+
+        assert_eq!(verify(&bundle), Some(true));
+        assert_eq!(verify(&format!("{}\n", bundle)), Some(true));
+        assert_eq!(verify(&bundle.replacen("hello", "hell", 1)), Some(false));
+        assert_eq!(verify("no footer here"), None);
+    }
+}
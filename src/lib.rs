@@ -18,6 +18,12 @@ pub enum FilesToPromptError {
     #[error("Binary file detected: {path}")]
     BinaryFile { path: PathBuf },
 
+    #[error("Sensitive file detected: {path} (use --allow-sensitive to include it)")]
+    SensitiveFile { path: PathBuf },
+
+    #[error("Generated file detected: {path} ({reason})")]
+    GeneratedFile { path: PathBuf, reason: &'static str },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -26,6 +32,37 @@ pub enum FilesToPromptError {
 
     #[error("Pattern matching error: {0}")]
     PatternError(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Clipboard error: {0}")]
+    Clipboard(String),
+
+    #[error("Config error: {0}")]
+    Config(String),
+
+    #[error("Git error: {0}")]
+    Git(String),
+
+    #[error("Parquet error: {0}")]
+    Parquet(String),
+
+    #[error("Filter command error: {0}")]
+    FilterCommand(String),
+
+    #[error("Transform command error: {0}")]
+    TransformCommand(String),
+
+    #[error("Output is {tokens} tokens, over the {limit}-token context window of {model}")]
+    ContextWindowExceeded {
+        model: &'static str,
+        tokens: usize,
+        limit: usize,
+    },
+
+    #[error("fuse was built without the `{feature}` feature (reinstall with `cargo install fusefiles --features {feature}`)")]
+    FeatureDisabled { feature: &'static str },
 }
 
 /// Result type alias for the fusefiles application
@@ -43,15 +80,43 @@ pub enum TocMode {
 }
 
 // Public modules
+pub mod cache;
+pub mod chunks;
 pub mod cli;
+pub mod config;
+#[cfg(feature = "transcode")]
+pub mod encoding;
 pub mod extensions;
 pub mod file_processor;
+pub mod filter_cmd;
+pub mod git;
 pub mod ignore;
+pub mod integrity;
+pub mod llms_txt;
+pub mod models;
+pub mod ordering;
 pub mod output;
+pub mod report;
+pub mod sample;
+pub mod schema;
+#[cfg(feature = "signatures")]
+pub mod signatures;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
+pub mod testing;
+#[cfg(feature = "tokens")]
+pub mod tokens;
+pub mod transform;
+pub mod transform_cmd;
 pub mod tree;
+pub mod usage;
 pub mod utils;
 
 // Re-exports for convenience
-pub use file_processor::FileProcessor;
-pub use output::{DefaultFormatter, MarkdownFormatter, OutputFormatter, XmlFormatter};
+pub use file_processor::{Bundle, FileEntry, FileProcessor, FileProcessorBuilder};
+pub use output::{
+    DefaultFormatter, ManifestFormat, ManifestFormatter, MarkdownFormatter, OutputFormatter,
+    RawFormatter, XmlFormatter,
+};
 pub use tree::{TreeGenerator, TreeNode};
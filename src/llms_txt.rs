@@ -0,0 +1,219 @@
+//! llms.txt / llms-full.txt generation
+//!
+//! `llms.txt` is a small markdown index (title, summary and per-directory link
+//! lists) built from the same tree the table of contents uses. `llms-full.txt`
+//! carries the same header followed by every file's contents.
+
+use crate::output::MarkdownFormatter;
+use crate::tree::{TreeGenerator, TreeNode};
+use crate::utils::write_atomic;
+use crate::{FileProcessor, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// File name of the index
+pub const INDEX_FILE_NAME: &str = "llms.txt";
+/// File name of the full-content companion
+pub const FULL_FILE_NAME: &str = "llms-full.txt";
+
+/// Derive a title from the first input path (the directory or file name)
+pub fn default_title(paths: &[PathBuf]) -> String {
+    paths
+        .first()
+        .and_then(|p| fs::canonicalize(p).ok())
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "Project".to_string())
+}
+
+/// Use the first prose paragraph of a README in the first input directory as the summary
+pub fn default_summary(paths: &[PathBuf]) -> Option<String> {
+    let dir = paths.iter().find(|p| p.is_dir())?;
+    let readme = ["README.md", "README", "readme.md"]
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|p| p.is_file())?;
+    let content = fs::read_to_string(readme).ok()?;
+
+    content
+        .split("\n\n")
+        .map(str::trim)
+        .find(|block| {
+            !block.is_empty()
+                && !block.starts_with('#')
+                && !block.starts_with("[!")
+                && !block.starts_with("```")
+        })
+        .map(|block| block.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Render the header shared by both files
+fn render_header(title: &str, summary: Option<&str>) -> String {
+    match summary {
+        Some(summary) => format!("# {}\n\n> {}", title, summary),
+        None => format!("# {}", title),
+    }
+}
+
+/// Render the llms.txt index from generated trees, linking each file relative
+/// to `output_dir`, where the index is written
+pub fn render_index(
+    title: &str,
+    summary: Option<&str>,
+    trees: &[TreeNode],
+    output_dir: &Path,
+) -> String {
+    let mut sections: Vec<(String, Vec<&Path>)> = Vec::new();
+    let mut loose_files = Vec::new();
+
+    for tree in trees {
+        if tree.is_file {
+            loose_files.push(tree.path.as_path());
+        } else {
+            collect_sections(tree, &mut sections);
+        }
+    }
+    if !loose_files.is_empty() {
+        sections.insert(0, ("Files".to_string(), loose_files));
+    }
+
+    let mut output = vec![render_header(title, summary)];
+    for (heading, files) in sections {
+        let links = files
+            .iter()
+            .map(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                format!("- [{}]({})", name, relative_link(path, output_dir))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        output.push(format!("## {}\n\n{}", heading, links));
+    }
+
+    output.join("\n\n") + "\n"
+}
+
+/// `path` as a `/`-separated link relative to the directory `base`
+fn relative_link(path: &Path, base: &Path) -> String {
+    let (path, base) = (absolute(path), absolute(base));
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let ups = base.components().count() - common;
+    std::iter::repeat_n("..".to_string(), ups)
+        .chain(
+            path.components()
+                .skip(common)
+                .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `path` made absolute against the working directory, with `.` and `..`
+/// resolved lexically since the output directory may not exist yet
+fn absolute(path: &Path) -> PathBuf {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut absolute = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+/// Collect one section per directory that directly contains files
+fn collect_sections<'a>(node: &'a TreeNode, sections: &mut Vec<(String, Vec<&'a Path>)>) {
+    let files: Vec<&Path> = node
+        .children
+        .values()
+        .filter(|child| child.is_file)
+        .map(|child| child.path.as_path())
+        .collect();
+    if !files.is_empty() {
+        sections.push((node.path.display().to_string(), files));
+    }
+
+    for child in node.children.values().filter(|child| !child.is_file) {
+        collect_sections(child, sections);
+    }
+}
+
+/// Write llms.txt and llms-full.txt for the given paths into `output_dir`
+pub fn write_llms_txt(
+    processor: &FileProcessor,
+    paths: &[PathBuf],
+    output_dir: &Path,
+) -> Result<()> {
+    let title = default_title(paths);
+    let summary = default_summary(paths);
+
+    let files = processor.collect_files(paths)?;
+    let trees = TreeGenerator::new().generate_tree(paths, &files);
+    let index = render_index(&title, summary.as_deref(), &trees, output_dir);
+
+    let mut formatter = MarkdownFormatter::new();
+    let contents = processor.format_files(paths, &files, &mut formatter)?;
+    let full = format!(
+        "{}\n\n{}\n",
+        render_header(&title, summary.as_deref()),
+        contents
+    );
+
+    fs::create_dir_all(output_dir)?;
+    write_atomic(&output_dir.join(INDEX_FILE_NAME), index.as_bytes(), false)?;
+    write_atomic(&output_dir.join(FULL_FILE_NAME), full.as_bytes(), false)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render_index() {
+        let mut root = TreeNode::new("proj".to_string(), PathBuf::from("proj"), false);
+        let mut src = TreeNode::new("src".to_string(), PathBuf::from("proj/src"), false);
+        src.add_child(TreeNode::new(
+            "lib.rs".to_string(),
+            PathBuf::from("proj/src/lib.rs"),
+            true,
+        ));
+        root.add_child(src);
+        root.add_child(TreeNode::new(
+            "README.md".to_string(),
+            PathBuf::from("proj/README.md"),
+            true,
+        ));
+
+        let index = render_index("proj", Some("A demo."), &[root], Path::new("proj/docs"));
+        assert_eq!(
+            index,
+            "# proj\n\n> A demo.\n\n## proj\n\n- [README.md](../README.md)\n\n## proj/src\n\n- [lib.rs](../src/lib.rs)\n"
+        );
+        assert_eq!(
+            relative_link(Path::new("./a/b.rs"), Path::new("out/../.")),
+            "a/b.rs"
+        );
+    }
+
+    #[test]
+    fn test_default_summary_skips_headings() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("README.md"),
+            "# Title\n\n[![badge](x)](y)\n\nDoes useful\nthings.\n\nMore.",
+        )
+        .unwrap();
+
+        let summary = default_summary(&[temp_dir.path().to_path_buf()]);
+        assert_eq!(summary.as_deref(), Some("Does useful things."));
+    }
+}
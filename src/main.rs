@@ -1,9 +1,16 @@
 use fusefiles::cli;
+use fusefiles::FilesToPromptError;
+use std::io::ErrorKind;
 use std::process;
 
 fn main() {
-    if let Err(e) = cli::run() {
-        eprintln!("Error: {}", e);
-        process::exit(1);
+    match cli::run() {
+        // The reader went away early (`fuse src | head`), which is not a failure
+        Err(FilesToPromptError::Io(e)) if e.kind() == ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        Ok(()) => {}
     }
 }
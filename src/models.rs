@@ -0,0 +1,89 @@
+//! Context windows of well-known models, for `--model` fit checks
+
+use crate::utils::Tokenizer;
+
+/// Models whose context window `--model` knows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Model {
+    #[value(name = "gpt-4o")]
+    Gpt4o,
+    #[value(name = "gpt-4o-mini")]
+    Gpt4oMini,
+    #[value(name = "gpt-4.1")]
+    Gpt41,
+    #[value(name = "gpt-4-turbo")]
+    Gpt4Turbo,
+    #[value(name = "o3")]
+    O3,
+    #[value(name = "claude-opus")]
+    ClaudeOpus,
+    #[value(name = "claude-sonnet")]
+    ClaudeSonnet,
+    #[value(name = "claude-haiku")]
+    ClaudeHaiku,
+    #[value(name = "gemini-pro")]
+    GeminiPro,
+    #[value(name = "gemini-flash")]
+    GeminiFlash,
+    #[value(name = "llama3")]
+    Llama3,
+    #[value(name = "deepseek")]
+    DeepSeek,
+}
+
+impl Model {
+    /// Name as accepted on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Model::Gpt4o => "gpt-4o",
+            Model::Gpt4oMini => "gpt-4o-mini",
+            Model::Gpt41 => "gpt-4.1",
+            Model::Gpt4Turbo => "gpt-4-turbo",
+            Model::O3 => "o3",
+            Model::ClaudeOpus => "claude-opus",
+            Model::ClaudeSonnet => "claude-sonnet",
+            Model::ClaudeHaiku => "claude-haiku",
+            Model::GeminiPro => "gemini-pro",
+            Model::GeminiFlash => "gemini-flash",
+            Model::Llama3 => "llama3",
+            Model::DeepSeek => "deepseek",
+        }
+    }
+
+    /// Maximum input tokens the model accepts
+    pub fn context_window(self) -> usize {
+        match self {
+            Model::Gpt4o | Model::Gpt4oMini | Model::Gpt4Turbo => 128_000,
+            Model::Gpt41 => 1_047_576,
+            Model::O3 => 200_000,
+            Model::ClaudeOpus | Model::ClaudeSonnet | Model::ClaudeHaiku => 200_000,
+            Model::GeminiPro | Model::GeminiFlash => 1_048_576,
+            Model::Llama3 => 128_000,
+            Model::DeepSeek => 128_000,
+        }
+    }
+
+    /// Closest available tokenizer; models without a public one use o200k
+    pub fn tokenizer(self) -> Tokenizer {
+        match self {
+            Model::Gpt4Turbo => Tokenizer::Cl100k,
+            Model::Llama3 => Tokenizer::Llama3,
+            _ => Tokenizer::O200k,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_names_match_cli_values() {
+        for model in Model::value_variants() {
+            let value = model.to_possible_value().unwrap();
+            assert_eq!(value.get_name(), model.name());
+            assert!(model.context_window() >= 128_000);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Emission order of collected files
+
+use crate::git;
+use crate::FileEntry;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Decides the order in which collected files are emitted.
+///
+/// Files arrive in walk order (sorted by name within each directory, after any
+/// `--readme-first`/`--dir-preambles` ranking). With collections enabled the
+/// result is regrouped by input path afterwards, keeping the strategy's order
+/// within each group.
+pub trait OrderingStrategy {
+    /// Return the files in the order they should be emitted
+    fn order(&self, files: Vec<FileEntry>) -> Vec<FileEntry>;
+}
+
+/// Most frequently committed files first (`--sort git-churn`); ties and files
+/// without history keep their walk order
+#[derive(Debug, Clone, Default)]
+pub struct Churn {
+    counts: HashMap<PathBuf, usize>,
+}
+
+impl Churn {
+    /// Rank files by the commit counts of `git::commit_counts`
+    pub fn new(counts: HashMap<PathBuf, usize>) -> Self {
+        Self { counts }
+    }
+
+    /// Count commits within the churn window for the repositories containing `paths`
+    pub fn from_git(paths: &[PathBuf]) -> Self {
+        Self::new(git::commit_counts(paths, git::CHURN_WINDOW))
+    }
+
+    fn commits(&self, path: &Path) -> usize {
+        fs::canonicalize(path)
+            .ok()
+            .and_then(|path| self.counts.get(&path))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+impl OrderingStrategy for Churn {
+    fn order(&self, mut files: Vec<FileEntry>) -> Vec<FileEntry> {
+        files.sort_by_cached_key(|file| Reverse(self.commits(&file.path)));
+        files
+    }
+}
+
+/// Most recently committed files first (`--sort git-recency`); ties and files
+/// without history keep their walk order, after every committed file
+#[derive(Debug, Clone, Default)]
+pub struct Recency {
+    times: HashMap<PathBuf, i64>,
+}
+
+impl Recency {
+    /// Rank files by the last-commit times of `git::last_commit_times`
+    pub fn new(times: HashMap<PathBuf, i64>) -> Self {
+        Self { times }
+    }
+
+    /// Look up last-commit times in the repositories containing `paths`
+    pub fn from_git(paths: &[PathBuf]) -> Self {
+        Self::new(git::last_commit_times(paths))
+    }
+
+    fn last_commit(&self, path: &Path) -> Option<i64> {
+        fs::canonicalize(path)
+            .ok()
+            .and_then(|path| self.times.get(&path))
+            .copied()
+    }
+}
+
+impl OrderingStrategy for Recency {
+    fn order(&self, mut files: Vec<FileEntry>) -> Vec<FileEntry> {
+        // `None` sorts before `Some`, so reversing puts uncommitted files last
+        files.sort_by_cached_key(|file| Reverse(self.last_commit(&file.path)));
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A temp dir holding `a.txt`, `b.txt` and `c.txt`, with their paths in that order
+    fn fixture() -> (TempDir, Vec<PathBuf>) {
+        let temp_dir = TempDir::new().unwrap();
+        let paths = ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|name| {
+                let path = temp_dir.path().join(name);
+                fs::write(&path, *name).unwrap();
+                path
+            })
+            .collect();
+        (temp_dir, paths)
+    }
+
+    #[test]
+    fn test_churn_order() {
+        let (_temp_dir, paths) = fixture();
+        let canonical = |path: &PathBuf| fs::canonicalize(path).unwrap();
+        let churn = Churn::new(HashMap::from([
+            (canonical(&paths[1]), 5),
+            (canonical(&paths[2]), 2),
+        ]));
+
+        let files = paths
+            .iter()
+            .map(|path| FileEntry::new(path.clone(), String::new()))
+            .collect();
+        let ordered: Vec<_> = churn.order(files).into_iter().map(|f| f.path).collect();
+        assert_eq!(
+            ordered,
+            vec![paths[1].clone(), paths[2].clone(), paths[0].clone()]
+        );
+    }
+
+    #[test]
+    fn test_recency_order() {
+        let (_temp_dir, paths) = fixture();
+        let canonical = |path: &PathBuf| fs::canonicalize(path).unwrap();
+        let recency = Recency::new(HashMap::from([
+            (canonical(&paths[0]), 1_000),
+            (canonical(&paths[2]), 2_000),
+        ]));
+
+        let files = paths
+            .iter()
+            .map(|path| FileEntry::new(path.clone(), String::new()))
+            .collect();
+        let ordered: Vec<_> = recency.order(files).into_iter().map(|f| f.path).collect();
+        assert_eq!(
+            ordered,
+            vec![paths[2].clone(), paths[0].clone(), paths[1].clone()]
+        );
+    }
+}
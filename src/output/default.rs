@@ -1,6 +1,6 @@
 //! Default output formatter - simple format with path, separator, and content
 
-use crate::output::OutputFormatter;
+use crate::output::{preamble_dir, OutputFormatter};
 use crate::utils::add_line_numbers;
 use std::path::Path;
 
@@ -10,6 +10,7 @@ use std::path::Path;
 /// content
 ///
 /// ---
+#[derive(Debug, Clone)]
 pub struct DefaultFormatter;
 
 impl Default for DefaultFormatter {
@@ -35,6 +36,18 @@ impl OutputFormatter for DefaultFormatter {
         format!("{}\n---\n{}\n\n---", path.display(), content)
     }
 
+    fn start_collection(&mut self, name: &str) -> String {
+        format!("Project: {}\n===", name)
+    }
+
+    fn format_preamble(&mut self, path: &Path, content: &str) -> String {
+        format!(
+            "Context for {}\n---\n{}\n\n---",
+            preamble_dir(path),
+            content
+        )
+    }
+
     fn format_table_of_contents(&mut self, toc: &str) -> String {
         format!("Table of Contents\n---\n{}\n\n---", toc)
     }
@@ -73,6 +86,20 @@ mod tests {
         assert_eq!(result, "test.txt\n---\n1  line 1\n2  line 2\n\n---");
     }
 
+    #[test]
+    fn test_default_format_preamble() {
+        let mut formatter = DefaultFormatter::new();
+        let result = formatter.format_preamble(&PathBuf::from("src/PROMPT.md"), "Be nice");
+        assert_eq!(result, "Context for src/\n---\nBe nice\n\n---");
+    }
+
+    #[test]
+    fn test_default_collection() {
+        let mut formatter = DefaultFormatter::new();
+        assert_eq!(formatter.start_collection("api"), "Project: api\n===");
+        assert_eq!(formatter.end_collection("api"), "");
+    }
+
     #[test]
     fn test_start_end_output() {
         let mut formatter = DefaultFormatter::new();
@@ -0,0 +1,193 @@
+//! Manifest output formatter - one CSV/TSV row per file, without contents
+
+use crate::extensions::get_language_for_extension;
+use crate::output::OutputFormatter;
+use crate::utils::sha256_hex;
+use std::path::Path;
+
+/// Delimited format used by the manifest formatter
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ManifestFormat {
+    /// Comma-separated values
+    Csv,
+    /// Tab-separated values
+    Tsv,
+}
+
+impl ManifestFormat {
+    fn delimiter(self) -> char {
+        match self {
+            ManifestFormat::Csv => ',',
+            ManifestFormat::Tsv => '\t',
+        }
+    }
+}
+
+/// Manifest formatter that outputs an inventory of files instead of their contents:
+/// path,size,lines,language,sha256
+/// src/main.rs,120,9,,3f2a...
+#[derive(Debug, Clone)]
+pub struct ManifestFormatter {
+    format: ManifestFormat,
+    encodings: bool,
+}
+
+impl ManifestFormatter {
+    pub fn new(format: ManifestFormat) -> Self {
+        Self {
+            format,
+            encodings: false,
+        }
+    }
+
+    /// Add `encoding`, `line_endings` and `final_newline` columns describing each
+    /// file's exact bytes, so an unpacker can restore BOMs, CRLFs and final newlines
+    pub fn with_encodings(mut self) -> Self {
+        self.encodings = true;
+        self
+    }
+
+    fn row(&self, fields: &[&str]) -> String {
+        let delimiter = self.format.delimiter();
+        fields
+            .iter()
+            .map(|field| self.escape(field))
+            .collect::<Vec<_>>()
+            .join(&delimiter.to_string())
+    }
+
+    fn escape(&self, field: &str) -> String {
+        match self.format {
+            ManifestFormat::Csv => {
+                if field.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field.to_string()
+                }
+            }
+            // TSV has no quoting convention, so replace the characters that would break rows
+            ManifestFormat::Tsv => field.replace(['\t', '\n', '\r'], " "),
+        }
+    }
+}
+
+impl OutputFormatter for ManifestFormatter {
+    fn format_file(&mut self, path: &Path, content: &str, _line_numbers: bool) -> String {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let language = get_language_for_extension(extension);
+        let path = path.display().to_string();
+        let size = content.len().to_string();
+        let lines = content.lines().count().to_string();
+        let sha256 = sha256_hex(content.as_bytes());
+
+        if self.encodings {
+            let encoding = if content.starts_with('\u{feff}') {
+                "utf-8-bom"
+            } else {
+                "utf-8"
+            };
+            let final_newline = content.ends_with('\n').to_string();
+            self.row(&[
+                &path,
+                &size,
+                &lines,
+                language,
+                &sha256,
+                encoding,
+                line_endings(content),
+                &final_newline,
+            ])
+        } else {
+            self.row(&[&path, &size, &lines, language, &sha256])
+        }
+    }
+
+    fn format_table_of_contents(&mut self, _toc: &str) -> String {
+        // The manifest already lists every file, so a tree adds nothing
+        String::new()
+    }
+
+    fn start_output(&mut self) -> String {
+        if self.encodings {
+            self.row(&[
+                "path",
+                "size",
+                "lines",
+                "language",
+                "sha256",
+                "encoding",
+                "line_endings",
+                "final_newline",
+            ])
+        } else {
+            self.row(&["path", "size", "lines", "language", "sha256"])
+        }
+    }
+
+    fn end_output(&mut self) -> String {
+        String::new()
+    }
+}
+
+/// Classify a file's line terminators as `lf`, `crlf`, `mixed` or `none`
+fn line_endings(content: &str) -> &'static str {
+    let crlf = content.matches("\r\n").count();
+    let lf = content.matches('\n').count() - crlf;
+    match (lf, crlf) {
+        (0, 0) => "none",
+        (_, 0) => "lf",
+        (0, _) => "crlf",
+        _ => "mixed",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_csv_manifest_row() {
+        let mut formatter = ManifestFormatter::new(ManifestFormat::Csv);
+        let path = PathBuf::from("test.py");
+
+        let result = formatter.format_file(&path, "abc", false);
+        assert_eq!(
+            result,
+            "test.py,3,1,python,ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_csv_manifest_quotes_paths() {
+        let mut formatter = ManifestFormatter::new(ManifestFormat::Csv);
+        let path = PathBuf::from("a,\"b\".txt");
+
+        let result = formatter.format_file(&path, "", false);
+        assert!(result.starts_with("\"a,\"\"b\"\".txt\",0,0,,"));
+    }
+
+    #[test]
+    fn test_manifest_encodings() {
+        let mut formatter = ManifestFormatter::new(ManifestFormat::Csv).with_encodings();
+        assert!(formatter
+            .start_output()
+            .ends_with(",encoding,line_endings,final_newline"));
+
+        let path = PathBuf::from("win.txt");
+        let result = formatter.format_file(&path, "\u{feff}a\r\nb\r\n", false);
+        assert!(result.ends_with(",utf-8-bom,crlf,true"));
+        let result = formatter.format_file(&path, "a\nb\r\nc", false);
+        assert!(result.ends_with(",utf-8,mixed,false"));
+    }
+
+    #[test]
+    fn test_tsv_manifest_header() {
+        let mut formatter = ManifestFormatter::new(ManifestFormat::Tsv);
+        assert_eq!(
+            formatter.start_output(),
+            "path\tsize\tlines\tlanguage\tsha256"
+        );
+        assert_eq!(formatter.end_output(), "");
+    }
+}
@@ -1,8 +1,8 @@
 //! Markdown output formatter with fenced code blocks
 
 use crate::extensions::get_language_for_extension;
-use crate::output::OutputFormatter;
-use crate::utils::{add_line_numbers, determine_backtick_count};
+use crate::output::{preamble_dir, OutputFormatter};
+use crate::utils::{add_line_numbers, determine_backtick_count, sha256_hex};
 use std::path::Path;
 
 /// Markdown formatter that outputs files as fenced code blocks:
@@ -10,7 +10,11 @@ use std::path::Path;
 /// ```language
 /// content
 /// ```
-pub struct MarkdownFormatter;
+#[derive(Debug, Clone)]
+pub struct MarkdownFormatter {
+    headings: bool,
+    front_matter: bool,
+}
 
 impl Default for MarkdownFormatter {
     fn default() -> Self {
@@ -20,15 +24,53 @@ impl Default for MarkdownFormatter {
 
 impl MarkdownFormatter {
     pub fn new() -> Self {
-        Self
+        Self {
+            headings: false,
+            front_matter: false,
+        }
+    }
+
+    /// Put each file path in a `## path` heading instead of a bare line
+    pub fn with_headings(mut self) -> Self {
+        self.headings = true;
+        self
+    }
+
+    /// Precede each file with a YAML front-matter block (path, language, size, sha256)
+    /// so pipelines can split the markdown back into files with metadata
+    pub fn with_front_matter(mut self) -> Self {
+        self.front_matter = true;
+        self
     }
 }
 
+/// YAML front matter describing a file's original (un-numbered) content
+fn front_matter(path: &Path, language: &str, content: &str) -> String {
+    let path = path.display().to_string();
+    format!(
+        "---\npath: \"{}\"\nlanguage: {}\nsize: {}\nsha256: {}\n---\n",
+        path.replace('\\', "\\\\").replace('"', "\\\""),
+        if language.is_empty() {
+            "null"
+        } else {
+            language
+        },
+        content.len(),
+        sha256_hex(content.as_bytes())
+    )
+}
+
 impl OutputFormatter for MarkdownFormatter {
     fn format_file(&mut self, path: &Path, content: &str, line_numbers: bool) -> String {
         let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
         let language = get_language_for_extension(extension);
 
+        let metadata = if self.front_matter {
+            front_matter(path, language, content)
+        } else {
+            String::new()
+        };
+
         let content = if line_numbers {
             add_line_numbers(content)
         } else {
@@ -38,8 +80,12 @@ impl OutputFormatter for MarkdownFormatter {
         // Determine backtick count needed
         let backticks = determine_backtick_count(&content);
 
+        let heading = if self.headings { "## " } else { "" };
+
         format!(
-            "{}\n{}{}\n{}\n{}",
+            "{}{}{}\n{}{}\n{}\n{}",
+            metadata,
+            heading,
             path.display(),
             backticks,
             language,
@@ -48,6 +94,19 @@ impl OutputFormatter for MarkdownFormatter {
         )
     }
 
+    fn start_collection(&mut self, name: &str) -> String {
+        format!("# Project: {}\n", name)
+    }
+
+    fn format_preamble(&mut self, path: &Path, content: &str) -> String {
+        // Preambles are markdown already, so they are emitted as prose rather than fenced
+        format!(
+            "## Context for {}\n\n{}",
+            preamble_dir(path),
+            content.trim_end()
+        )
+    }
+
     fn format_table_of_contents(&mut self, toc: &str) -> String {
         format!("# Table of Contents\n\n```\n{}\n```", toc)
     }
@@ -111,6 +170,45 @@ mod tests {
         assert!(result.contains("1  line 1\n2  line 2"));
     }
 
+    #[test]
+    fn test_markdown_format_with_headings() {
+        let mut formatter = MarkdownFormatter::new().with_headings();
+        let path = PathBuf::from("test.py");
+
+        let result = formatter.format_file(&path, "print('hello')", false);
+        assert_eq!(result, "## test.py\n```python\nprint('hello')\n```");
+    }
+
+    #[test]
+    fn test_markdown_format_with_front_matter() {
+        let mut formatter = MarkdownFormatter::new().with_front_matter();
+        let path = PathBuf::from("test.py");
+        let content = "print('hello')";
+
+        let result = formatter.format_file(&path, content, true);
+        assert_eq!(
+            result,
+            format!(
+                "---\npath: \"test.py\"\nlanguage: python\nsize: 14\nsha256: {}\n---\ntest.py\n```python\n1  print('hello')\n```",
+                sha256_hex(content.as_bytes())
+            )
+        );
+    }
+
+    #[test]
+    fn test_markdown_format_preamble() {
+        let mut formatter = MarkdownFormatter::new();
+        let result = formatter.format_preamble(&PathBuf::from("src/CONTEXT.md"), "Be nice\n");
+        assert_eq!(result, "## Context for src/\n\nBe nice");
+    }
+
+    #[test]
+    fn test_markdown_collection() {
+        let mut formatter = MarkdownFormatter::new();
+        assert_eq!(formatter.start_collection("web"), "# Project: web\n");
+        assert_eq!(formatter.end_collection("web"), "");
+    }
+
     #[test]
     fn test_start_end_output() {
         let mut formatter = MarkdownFormatter::new();
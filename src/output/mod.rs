@@ -2,11 +2,38 @@
 
 use std::path::Path;
 
+/// Display the directory a preamble file describes
+pub(crate) fn preamble_dir(path: &Path) -> String {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => format!("{}/", parent.display()),
+        _ => "./".to_string(),
+    }
+}
+
 /// Trait for different output formatters
 pub trait OutputFormatter {
     /// Format a single file's content
     fn format_file(&mut self, path: &Path, content: &str, line_numbers: bool) -> String;
 
+    /// Format a directory preamble (`PROMPT.md`/`CONTEXT.md`) shown before that directory's files.
+    ///
+    /// Formatters without a dedicated block render it like any other file.
+    fn format_preamble(&mut self, path: &Path, content: &str) -> String {
+        self.format_file(path, content, false)
+    }
+
+    /// Open a collection grouping the files found under one input root (`--collections`).
+    ///
+    /// Formatters without collection support emit nothing.
+    fn start_collection(&mut self, _name: &str) -> String {
+        String::new()
+    }
+
+    /// Close the collection opened by `start_collection`
+    fn end_collection(&mut self, _name: &str) -> String {
+        String::new()
+    }
+
     /// Format the table of contents tree
     fn format_table_of_contents(&mut self, toc: &str) -> String;
 
@@ -18,9 +45,13 @@ pub trait OutputFormatter {
 }
 
 pub mod default;
+pub mod manifest;
 pub mod markdown;
+pub mod raw;
 pub mod xml;
 
 pub use default::DefaultFormatter;
+pub use manifest::{ManifestFormat, ManifestFormatter};
 pub use markdown::MarkdownFormatter;
+pub use raw::RawFormatter;
 pub use xml::XmlFormatter;
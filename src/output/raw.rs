@@ -0,0 +1,103 @@
+//! Raw output formatter - file contents only, without per-file headers
+
+use crate::output::OutputFormatter;
+use crate::utils::add_line_numbers;
+use std::path::Path;
+
+/// Raw formatter that concatenates file contents, optionally separated by a fixed line:
+/// content of first file
+/// <separator>
+/// content of second file
+#[derive(Debug, Clone)]
+pub struct RawFormatter {
+    separator: Option<String>,
+    index: usize,
+    exact: bool,
+}
+
+impl Default for RawFormatter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl RawFormatter {
+    pub fn new(separator: Option<String>) -> Self {
+        Self {
+            separator,
+            index: 0,
+            exact: false,
+        }
+    }
+
+    /// Keep each file's trailing newline instead of folding it into the join
+    pub fn with_exact(mut self) -> Self {
+        self.exact = true;
+        self
+    }
+}
+
+impl OutputFormatter for RawFormatter {
+    fn format_file(&mut self, _path: &Path, content: &str, line_numbers: bool) -> String {
+        let content = if line_numbers {
+            add_line_numbers(content)
+        } else if self.exact {
+            content.to_string()
+        } else {
+            // Files are joined with newlines, so drop one trailing newline to avoid doubling it
+            content.strip_suffix('\n').unwrap_or(content).to_string()
+        };
+
+        let output = match &self.separator {
+            Some(separator) if self.index > 0 => format!("{}\n{}", separator, content),
+            _ => content,
+        };
+
+        self.index += 1;
+        output
+    }
+
+    fn format_table_of_contents(&mut self, _toc: &str) -> String {
+        String::new()
+    }
+
+    fn start_output(&mut self) -> String {
+        String::new()
+    }
+
+    fn end_output(&mut self) -> String {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_raw_format_without_separator() {
+        let mut formatter = RawFormatter::new(None);
+        let first = formatter.format_file(&PathBuf::from("a.txt"), "alpha\n", false);
+        let second = formatter.format_file(&PathBuf::from("b.txt"), "beta", false);
+        assert_eq!(first, "alpha");
+        assert_eq!(second, "beta");
+    }
+
+    #[test]
+    fn test_raw_format_with_separator() {
+        let mut formatter = RawFormatter::new(Some("<|endoftext|>".to_string()));
+        let first = formatter.format_file(&PathBuf::from("a.txt"), "alpha\n", false);
+        let second = formatter.format_file(&PathBuf::from("b.txt"), "beta\n", false);
+        assert_eq!(first, "alpha");
+        assert_eq!(second, "<|endoftext|>\nbeta");
+    }
+
+    #[test]
+    fn test_start_end_output() {
+        let mut formatter = RawFormatter::default();
+        assert_eq!(formatter.start_output(), "");
+        assert_eq!(formatter.end_output(), "");
+        assert_eq!(formatter.format_table_of_contents("tree"), "");
+    }
+}
@@ -1,6 +1,6 @@
 //! XML output formatter for Claude's preferred format
 
-use crate::output::OutputFormatter;
+use crate::output::{preamble_dir, OutputFormatter};
 use crate::utils::add_line_numbers;
 use std::path::Path;
 
@@ -13,8 +13,10 @@ use std::path::Path;
 /// </document_content>
 /// </document>
 /// </documents>
+#[derive(Debug, Clone)]
 pub struct XmlFormatter {
     index: usize,
+    escape_closing_tags: bool,
 }
 
 impl Default for XmlFormatter {
@@ -25,7 +27,17 @@ impl Default for XmlFormatter {
 
 impl XmlFormatter {
     pub fn new() -> Self {
-        Self { index: 1 }
+        Self {
+            index: 1,
+            escape_closing_tags: false,
+        }
+    }
+
+    /// Escape `</document_content>`/`</document>` inside file content so a file
+    /// can't close its own wrapper early
+    pub fn with_escaped_closing_tags(mut self) -> Self {
+        self.escape_closing_tags = true;
+        self
     }
 }
 
@@ -36,6 +48,13 @@ impl OutputFormatter for XmlFormatter {
         } else {
             content.to_string()
         };
+        let content = if self.escape_closing_tags {
+            content
+                .replace("</document_content>", "&lt;/document_content>")
+                .replace("</document>", "&lt;/document>")
+        } else {
+            content
+        };
 
         let output = format!(
             r#"<document index="{}">
@@ -53,6 +72,24 @@ impl OutputFormatter for XmlFormatter {
         output
     }
 
+    fn start_collection(&mut self, name: &str) -> String {
+        format!(r#"<collection name="{}">"#, name)
+    }
+
+    fn end_collection(&mut self, _name: &str) -> String {
+        "</collection>".to_string()
+    }
+
+    fn format_preamble(&mut self, path: &Path, content: &str) -> String {
+        format!(
+            r#"<directory_context path="{}">
+{}
+</directory_context>"#,
+            preamble_dir(path),
+            content
+        )
+    }
+
     fn format_table_of_contents(&mut self, toc: &str) -> String {
         format!(
             r#"<table_of_contents>
@@ -115,6 +152,40 @@ Hello, world!
         assert!(result.contains("1  line 1\n2  line 2"));
     }
 
+    #[test]
+    fn test_xml_format_preamble() {
+        let mut formatter = XmlFormatter::new();
+        let result = formatter.format_preamble(&PathBuf::from("src/PROMPT.md"), "Be nice");
+        assert_eq!(
+            result,
+            "<directory_context path=\"src/\">\nBe nice\n</directory_context>"
+        );
+        // Preambles don't consume document indices
+        let file = formatter.format_file(&PathBuf::from("a.txt"), "a", false);
+        assert!(file.contains(r#"index="1""#));
+    }
+
+    #[test]
+    fn test_xml_collection() {
+        let mut formatter = XmlFormatter::new();
+        assert_eq!(
+            formatter.start_collection("backend"),
+            r#"<collection name="backend">"#
+        );
+        assert_eq!(formatter.end_collection("backend"), "</collection>");
+    }
+
+    #[test]
+    fn test_xml_escaped_closing_tags() {
+        let mut formatter = XmlFormatter::new().with_escaped_closing_tags();
+        let path = PathBuf::from("test.xml");
+        let content = "a</document_content></document>b";
+
+        let result = formatter.format_file(&path, content, false);
+        assert!(result.contains("a&lt;/document_content>&lt;/document>b"));
+        assert_eq!(result.matches("</document>").count(), 1);
+    }
+
     #[test]
     fn test_start_end_output() {
         let mut formatter = XmlFormatter::new();
@@ -0,0 +1,258 @@
+//! Warnings about files skipped while collecting
+//!
+//! A handful of skips are reported one line each. Past the warnings budget
+//! they are folded into a single summary line per kind (sensitive files,
+//! binaries, lockfiles, vendored directories), so a tree full of binaries doesn't bury everything
+//! else on stderr. Verbose mode always lists every file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Most per-file skip warnings printed before switching to a summary
+pub const WARNINGS_BUDGET: usize = 5;
+
+/// Skipped files gathered during one walk
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SkipReport {
+    verbose: bool,
+    /// Credential and key files left out without `--allow-sensitive`
+    sensitive: Vec<PathBuf>,
+    /// Binary files with their size in bytes
+    binary: Vec<(PathBuf, u64)>,
+    /// Lockfiles left out without `--include-lockfiles`
+    lockfiles: Vec<PathBuf>,
+    /// Vendored directories pruned without `--include-vendored`
+    vendored: Vec<PathBuf>,
+    /// Minified or generated-looking files with the reason, without `--include-generated`
+    generated: Vec<(PathBuf, &'static str)>,
+}
+
+impl SkipReport {
+    pub fn new(verbose: bool) -> Self {
+        Self {
+            verbose,
+            ..Self::default()
+        }
+    }
+
+    /// Record a sensitive file that was left out
+    pub fn skip_sensitive(&mut self, path: &Path) {
+        self.sensitive.push(path.to_path_buf());
+    }
+
+    /// Record a binary file that was left out
+    pub fn skip_binary(&mut self, path: &Path) {
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        self.binary.push((path.to_path_buf(), size));
+    }
+
+    /// Record a lockfile that was left out
+    pub fn skip_lockfile(&mut self, path: &Path) {
+        self.lockfiles.push(path.to_path_buf());
+    }
+
+    /// Record a vendored directory that was not descended into
+    pub fn skip_vendored(&mut self, dir: &Path) {
+        self.vendored.push(dir.to_path_buf());
+    }
+
+    /// Record a file that looks machine-generated, with what gave it away
+    pub fn skip_generated(&mut self, path: &Path, reason: &'static str) {
+        self.generated.push((path.to_path_buf(), reason));
+    }
+
+    /// Warning lines to print: one per file within the budget or in verbose mode,
+    /// otherwise a single summary for each kind of skip
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = self.listed(
+            self.sensitive
+                .iter()
+                .map(|path| {
+                    format!(
+                        "Warning: Skipping sensitive file {} (use --allow-sensitive to include it)",
+                        path.display()
+                    )
+                })
+                .collect(),
+            |count| format!("Warning: Skipped {} sensitive files; use -v to list or --allow-sensitive to include them", count),
+        );
+        warnings.extend(self.binary_warnings());
+        warnings.extend(self.listed(
+            self.lockfiles
+                .iter()
+                .map(|path| {
+                    format!(
+                        "Warning: Skipping lockfile {} (use --include-lockfiles to keep it)",
+                        path.display()
+                    )
+                })
+                .collect(),
+            |count| format!("Warning: Skipped {} lockfiles; use -v to list or --include-lockfiles to keep them", count),
+        ));
+        warnings.extend(self.listed(
+            self.vendored
+                .iter()
+                .map(|dir| {
+                    format!(
+                        "Warning: Skipping vendored directory {} (use --include-vendored to keep it)",
+                        dir.display()
+                    )
+                })
+                .collect(),
+            |count| format!("Warning: Skipped {} vendored directories; use -v to list or --include-vendored to keep them", count),
+        ));
+        warnings.extend(self.listed(
+            self.generated
+                .iter()
+                .map(|(path, reason)| {
+                    format!(
+                        "Warning: Skipping generated file {} ({}; use --include-generated to keep it)",
+                        path.display(),
+                        reason
+                    )
+                })
+                .collect(),
+            |count| format!("Warning: Skipped {} generated files; use -v to list or --include-generated to keep them", count),
+        ));
+        warnings
+    }
+
+    /// `lines` as they are within the budget or in verbose mode, else one summary line
+    fn listed(&self, lines: Vec<String>, summary: impl FnOnce(usize) -> String) -> Vec<String> {
+        if self.verbose || lines.len() <= WARNINGS_BUDGET {
+            lines
+        } else {
+            vec![summary(lines.len())]
+        }
+    }
+
+    fn binary_warnings(&self) -> Vec<String> {
+        if self.verbose || self.binary.len() <= WARNINGS_BUDGET {
+            return self
+                .binary
+                .iter()
+                .map(|(path, _)| format!("Warning: Skipping binary file {}", path.display()))
+                .collect();
+        }
+
+        let (largest, size) = self
+            .binary
+            .iter()
+            .max_by_key(|(_, size)| *size)
+            .expect("over budget, so not empty");
+        vec![format!(
+            "Warning: Skipped {} binary files (largest: {}, {}); use -v to list",
+            self.binary.len(),
+            largest.display(),
+            format_size(*size)
+        )]
+    }
+
+    /// Print the warnings to stderr
+    pub fn print(&self) {
+        for warning in self.warnings() {
+            eprintln!("{}", warning);
+        }
+    }
+}
+
+/// Human-readable size with binary multiples: `512 B`, `4 KB`, `120 MB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes;
+    let mut unit = 0;
+    while size >= 1024 && unit < UNITS.len() - 1 {
+        size /= 1024;
+        unit += 1;
+    }
+    format!("{} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(verbose: bool, count: usize) -> SkipReport {
+        let mut report = SkipReport::new(verbose);
+        for i in 0..count {
+            report
+                .binary
+                .push((PathBuf::from(format!("assets/{}.bin", i)), i as u64 * 1024));
+        }
+        report
+    }
+
+    #[test]
+    fn test_warnings_within_budget() {
+        let warnings = report(false, 2).warnings();
+        assert_eq!(
+            warnings,
+            vec![
+                "Warning: Skipping binary file assets/0.bin",
+                "Warning: Skipping binary file assets/1.bin"
+            ]
+        );
+        assert!(report(false, 0).warnings().is_empty());
+    }
+
+    #[test]
+    fn test_warnings_summarized_over_budget() {
+        assert_eq!(
+            report(false, 412).warnings(),
+            vec!["Warning: Skipped 412 binary files (largest: assets/411.bin, 411 KB); use -v to list"]
+        );
+        assert_eq!(report(true, 412).warnings().len(), 412);
+    }
+
+    #[test]
+    fn test_lockfile_and_vendored_warnings() {
+        let mut report = SkipReport::new(false);
+        report.skip_lockfile(Path::new("Cargo.lock"));
+        report.skip_vendored(Path::new("go/vendor"));
+        assert_eq!(
+            report.warnings(),
+            vec![
+                "Warning: Skipping lockfile Cargo.lock (use --include-lockfiles to keep it)",
+                "Warning: Skipping vendored directory go/vendor (use --include-vendored to keep it)"
+            ]
+        );
+
+        for i in 0..WARNINGS_BUDGET {
+            report.skip_lockfile(&PathBuf::from(format!("pkg{}/yarn.lock", i)));
+        }
+        assert_eq!(
+            report.warnings()[0],
+            "Warning: Skipped 6 lockfiles; use -v to list or --include-lockfiles to keep them"
+        );
+    }
+
+    #[test]
+    fn test_generated_warnings() {
+        let mut report = SkipReport::new(false);
+        report.skip_generated(Path::new("dist/app.min.js"), "minified file name");
+        assert_eq!(
+            report.warnings(),
+            vec!["Warning: Skipping generated file dist/app.min.js (minified file name; use --include-generated to keep it)"]
+        );
+    }
+
+    #[test]
+    fn test_sensitive_warnings() {
+        let mut report = SkipReport::new(false);
+        report.skip_sensitive(Path::new(".env"));
+        report.skip_binary(Path::new("logo.png"));
+        assert_eq!(
+            report.warnings(),
+            vec![
+                "Warning: Skipping sensitive file .env (use --allow-sensitive to include it)",
+                "Warning: Skipping binary file logo.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(120 * 1024 * 1024), "120 MB");
+    }
+}
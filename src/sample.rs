@@ -0,0 +1,169 @@
+//! Tabular data sampling (`--sample-rows`)
+//!
+//! CSV and TSV files are cut down to their header and first rows. Parquet
+//! files, which are otherwise skipped as binary, are rendered as their schema
+//! followed by the first rows (requires the `parquet` feature).
+
+use crate::transform::ContentTransform;
+use crate::utils::format_thousands;
+use std::path::Path;
+
+/// Extensions of delimited text data, with whether fields may be quoted
+const DELIMITED_EXTENSIONS: &[(&str, bool)] = &[("csv", true), ("tsv", false)];
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// Is this a Parquet file?
+pub fn is_parquet(path: &Path) -> bool {
+    extension(path).is_some_and(|e| e == "parquet")
+}
+
+/// `[... 9,990 more rows ...]`
+fn more_rows_marker(more: usize) -> String {
+    format!(
+        "[... {} more {} ...]\n",
+        format_thousands(more),
+        if more == 1 { "row" } else { "rows" }
+    )
+}
+
+/// Keep the header and first rows of CSV/TSV files
+#[derive(Debug, Clone)]
+pub struct SampleRows {
+    rows: usize,
+}
+
+impl SampleRows {
+    pub fn new(rows: usize) -> Self {
+        Self { rows }
+    }
+}
+
+impl ContentTransform for SampleRows {
+    fn apply(&self, path: &Path, content: &str) -> String {
+        let Some(&(_, quoted)) = extension(path)
+            .and_then(|e| DELIMITED_EXTENSIONS.iter().find(|(known, _)| *known == e))
+        else {
+            return content.to_string();
+        };
+
+        let records = records(content, quoted);
+        if records.len() <= self.rows + 1 {
+            return content.to_string();
+        }
+        let mut sample = records[..=self.rows].concat();
+        if !sample.ends_with('\n') {
+            sample.push('\n');
+        }
+        sample.push_str(&more_rows_marker(records.len() - self.rows - 1));
+        sample
+    }
+}
+
+/// Split into records, each with its line ending. With `quoted`, newlines
+/// inside double-quoted fields don't end a record.
+fn records(content: &str, quoted: bool) -> Vec<&str> {
+    let mut records = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, byte) in content.bytes().enumerate() {
+        match byte {
+            b'"' if quoted => in_quotes = !in_quotes,
+            b'\n' if !in_quotes => {
+                records.push(&content[start..=i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < content.len() {
+        records.push(&content[start..]);
+    }
+    records
+}
+
+/// Schema and first `rows` rows of a Parquet file
+#[cfg(feature = "parquet")]
+pub fn sample_parquet(path: &Path, rows: usize) -> crate::Result<String> {
+    use ::parquet::file::reader::{FileReader, SerializedFileReader};
+    use ::parquet::schema::printer::print_schema;
+
+    let parquet_error =
+        |e: ::parquet::errors::ParquetError| crate::FilesToPromptError::Parquet(e.to_string());
+    let reader = SerializedFileReader::new(std::fs::File::open(path)?).map_err(parquet_error)?;
+    let metadata = reader.metadata().file_metadata();
+    let total = usize::try_from(metadata.num_rows()).unwrap_or(0);
+
+    let mut schema = Vec::new();
+    print_schema(&mut schema, metadata.schema());
+    let mut sample = String::from_utf8_lossy(&schema).into_owned();
+    sample.push('\n');
+    for row in reader.get_row_iter(None).map_err(parquet_error)?.take(rows) {
+        sample.push_str(&row.map_err(parquet_error)?.to_string());
+        sample.push('\n');
+    }
+    if total > rows {
+        sample.push_str(&more_rows_marker(total - rows));
+    }
+    Ok(sample)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_csv() {
+        let sample = |path: &str, rows: usize, content: &str| {
+            SampleRows::new(rows).apply(Path::new(path), content)
+        };
+        let csv = "id,note\n1,\"two\nlines\"\n2,b\n3,c\n";
+        assert_eq!(
+            sample("data.csv", 1, csv),
+            "id,note\n1,\"two\nlines\"\n[... 2 more rows ...]\n"
+        );
+        assert_eq!(sample("data.csv", 3, csv), csv);
+        assert_eq!(
+            sample("data.tsv", 1, "a\tb\n\"x\t1\n\"y\t2"),
+            "a\tb\n\"x\t1\n[... 1 more row ...]\n"
+        );
+        assert_eq!(sample("notes.txt", 1, "a\nb\nc\n"), "a\nb\nc\n");
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_sample_parquet() {
+        use ::parquet::data_type::Int64Type;
+        use ::parquet::file::writer::SerializedFileWriter;
+        use ::parquet::schema::parser::parse_message_type;
+        use std::sync::Arc;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("events.parquet");
+        let schema = Arc::new(parse_message_type("message events { REQUIRED INT64 id; }").unwrap());
+        let mut writer = SerializedFileWriter::new(
+            std::fs::File::create(&path).unwrap(),
+            schema,
+            Arc::default(),
+        )
+        .unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column
+            .typed::<Int64Type>()
+            .write_batch(&[10, 20, 30], None, None)
+            .unwrap();
+        column.close().unwrap();
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        assert_eq!(
+            sample_parquet(&path, 2).unwrap(),
+            "message events {\n  REQUIRED INT64 id;\n}\n\n{id: 10}\n{id: 20}\n[... 1 more row ...]\n"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! JSON Schemas for machine-readable outputs
+//!
+//! Each schema carries a versioned `$id`; the version is bumped whenever a
+//! field is removed or changes meaning, so CI can pin the shape it validates.
+
+/// Version embedded in every schema `$id`
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// One `--manifest` row; CSV/TSV columns appear in the order of `properties`,
+/// with the encoding columns present only under `--exact`
+pub const MANIFEST_SCHEMA: &str = r#"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "$id": "https://github.com/finnatsea/fusefiles/schemas/v1/manifest.json",
+  "title": "fuse --manifest row",
+  "type": "object",
+  "properties": {
+    "path": { "type": "string", "description": "File path as it appears in the bundle" },
+    "size": { "type": "integer", "minimum": 0, "description": "Content size in bytes" },
+    "lines": { "type": "integer", "minimum": 0 },
+    "language": { "type": "string", "description": "Fence language, empty when unknown" },
+    "sha256": { "type": "string", "pattern": "^[0-9a-f]{64}$" },
+    "encoding": { "enum": ["utf-8", "utf-8-bom"] },
+    "line_endings": { "enum": ["lf", "crlf", "mixed", "none"] },
+    "final_newline": { "enum": ["true", "false"] }
+  },
+  "required": ["path", "size", "lines", "language", "sha256"],
+  "additionalProperties": false
+}"#;
+
+/// One `--trace` JSONL record
+pub const TRACE_SCHEMA: &str = r#"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "$id": "https://github.com/finnatsea/fusefiles/schemas/v1/trace.json",
+  "title": "fuse --trace record",
+  "type": "object",
+  "properties": {
+    "path": { "type": "string" },
+    "decision": { "enum": ["included", "excluded", "pruned"] },
+    "rule": {
+      "enum": [
+        null,
+        "extension",
+        "hidden",
+        "gitignore",
+        "ignore_pattern",
+        "include_pattern",
+        "path_pattern",
+        "previous_output",
+        "binary",
+        "content",
+        "submodule",
+        "generated",
+        "default_ignore",
+        "lockfile",
+        "sensitive",
+        "vendored",
+        "empty",
+        "tests",
+        "filter_cmd"
+      ]
+    },
+    "elapsed_us": { "type": "integer", "minimum": 0 }
+  },
+  "required": ["path", "decision", "rule", "elapsed_us"],
+  "additionalProperties": false
+}"#;
+
+/// One `--chunks` JSONL record
+pub const CHUNK_SCHEMA: &str = r#"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "$id": "https://github.com/finnatsea/fusefiles/schemas/v1/chunk.json",
+  "title": "fuse --chunks record",
+  "type": "object",
+  "properties": {
+    "path": { "type": "string" },
+    "chunk_index": { "type": "integer", "minimum": 0, "description": "Position of the chunk within its file" },
+    "start_line": { "type": "integer", "minimum": 1 },
+    "end_line": { "type": "integer", "minimum": 1, "description": "Last line, inclusive" },
+    "content": { "type": "string" }
+  },
+  "required": ["path", "chunk_index", "start_line", "end_line", "content"],
+  "additionalProperties": false
+}"#;
+
+/// Names accepted by `fuse schema <NAME>`, paired with their schema
+pub const SCHEMAS: &[(&str, &str)] = &[
+    ("manifest", MANIFEST_SCHEMA),
+    ("trace", TRACE_SCHEMA),
+    ("chunk", CHUNK_SCHEMA),
+];
+
+/// Look up a schema by name
+pub fn schema(name: &str) -> Option<&'static str> {
+    SCHEMAS
+        .iter()
+        .find(|(schema_name, _)| *schema_name == name)
+        .map(|(_, schema)| *schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processor::FilterReason;
+
+    #[test]
+    fn test_schema_ids_are_versioned() {
+        for (name, schema) in SCHEMAS {
+            assert!(schema.contains(&format!("/schemas/v{}/{}.json", SCHEMA_VERSION, name)));
+        }
+        assert!(schema("manifest").is_some());
+        assert!(schema("nope").is_none());
+    }
+
+    #[test]
+    fn test_trace_schema_lists_every_rule() {
+        for reason in [
+            FilterReason::Extension,
+            FilterReason::Hidden,
+            FilterReason::Gitignore,
+            FilterReason::IgnorePattern,
+            FilterReason::IncludePattern,
+            FilterReason::PathPattern,
+            FilterReason::PreviousOutput,
+            FilterReason::Binary,
+            FilterReason::Content,
+            FilterReason::Submodule,
+            FilterReason::Generated,
+            FilterReason::DefaultIgnore,
+            FilterReason::Lockfile,
+            FilterReason::Sensitive,
+            FilterReason::Vendored,
+            FilterReason::Empty,
+            FilterReason::Tests,
+            FilterReason::FilterCmd,
+        ] {
+            assert!(TRACE_SCHEMA.contains(&format!("\"{}\"", reason.as_str())));
+        }
+    }
+}
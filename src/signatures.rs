@@ -0,0 +1,468 @@
+//! Signatures-only code map (`--signatures`, requires the `signatures` feature)
+//!
+//! Each supported source file is parsed with tree-sitter and reduced to its
+//! declarations: functions and methods keep their signature with the body
+//! elided, types keep their full definition, and classes, impls, traits and
+//! interfaces keep the signatures of their public members. Doc comments and
+//! attributes directly above a declaration are kept with it. Files in other
+//! languages pass through unchanged.
+
+use crate::transform::ContentTransform;
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser};
+
+/// How a language delimits bodies
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bodies {
+    /// `{ ... }`
+    Braces,
+    /// An indented block after a `:`
+    Indented,
+}
+
+/// How a language marks members as hidden from outside callers
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Privacy {
+    /// Inherent impl items need `pub`; trait items are public with the trait
+    RustPub,
+    /// A `private` modifier or a `#name`
+    Modifier,
+    /// A leading underscore, except dunder methods
+    Underscore,
+}
+
+/// Which node kinds make up a language's declarations
+struct Grammar {
+    language: fn() -> Language,
+    bodies: Bodies,
+    privacy: Privacy,
+    /// Declarations whose body is replaced by a placeholder
+    functions: &'static [&'static str],
+    /// Declarations whose members are listed in turn
+    containers: &'static [&'static str],
+    /// Declarations kept in full (type definitions, bodiless signatures)
+    whole: &'static [&'static str],
+    /// Nodes wrapping a declaration, with the field holding it
+    wrappers: &'static [(&'static str, &'static str)],
+    /// Comments and attributes that belong to the declaration below them
+    leading: &'static [&'static str],
+}
+
+const RUST: Grammar = Grammar {
+    language: || tree_sitter_rust::LANGUAGE.into(),
+    bodies: Bodies::Braces,
+    privacy: Privacy::RustPub,
+    functions: &["function_item"],
+    containers: &["impl_item", "trait_item", "mod_item"],
+    whole: &[
+        "function_signature_item",
+        "struct_item",
+        "enum_item",
+        "union_item",
+        "type_item",
+        "const_item",
+        "static_item",
+        "associated_type",
+    ],
+    wrappers: &[],
+    leading: &["line_comment", "block_comment", "attribute_item"],
+};
+
+const PYTHON: Grammar = Grammar {
+    language: || tree_sitter_python::LANGUAGE.into(),
+    bodies: Bodies::Indented,
+    privacy: Privacy::Underscore,
+    functions: &["function_definition"],
+    containers: &["class_definition"],
+    whole: &[],
+    wrappers: &[("decorated_definition", "definition")],
+    leading: &["comment"],
+};
+
+const JAVASCRIPT: Grammar = Grammar {
+    language: || tree_sitter_javascript::LANGUAGE.into(),
+    bodies: Bodies::Braces,
+    privacy: Privacy::Modifier,
+    functions: &[
+        "function_declaration",
+        "generator_function_declaration",
+        "method_definition",
+    ],
+    containers: &["class_declaration"],
+    whole: &[],
+    wrappers: &[("export_statement", "declaration")],
+    leading: &["comment"],
+};
+
+const TYPESCRIPT_FUNCTIONS: &[&str] = &[
+    "function_declaration",
+    "generator_function_declaration",
+    "method_definition",
+];
+const TYPESCRIPT_CONTAINERS: &[&str] = &[
+    "class_declaration",
+    "abstract_class_declaration",
+    "internal_module",
+];
+const TYPESCRIPT_WHOLE: &[&str] = &[
+    "interface_declaration",
+    "type_alias_declaration",
+    "enum_declaration",
+    "function_signature",
+    "method_signature",
+    "abstract_method_signature",
+    "property_signature",
+];
+
+const TYPESCRIPT: Grammar = Grammar {
+    language: || tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+    bodies: Bodies::Braces,
+    privacy: Privacy::Modifier,
+    functions: TYPESCRIPT_FUNCTIONS,
+    containers: TYPESCRIPT_CONTAINERS,
+    whole: TYPESCRIPT_WHOLE,
+    wrappers: &[("export_statement", "declaration")],
+    leading: &["comment"],
+};
+
+const TSX: Grammar = Grammar {
+    language: || tree_sitter_typescript::LANGUAGE_TSX.into(),
+    ..TYPESCRIPT
+};
+
+const GO: Grammar = Grammar {
+    language: || tree_sitter_go::LANGUAGE.into(),
+    bodies: Bodies::Braces,
+    privacy: Privacy::Modifier,
+    functions: &["function_declaration", "method_declaration"],
+    containers: &[],
+    whole: &["type_declaration"],
+    wrappers: &[],
+    leading: &["comment"],
+};
+
+const JAVA: Grammar = Grammar {
+    language: || tree_sitter_java::LANGUAGE.into(),
+    bodies: Bodies::Braces,
+    privacy: Privacy::Modifier,
+    functions: &["method_declaration", "constructor_declaration"],
+    containers: &[
+        "class_declaration",
+        "interface_declaration",
+        "enum_declaration",
+        "record_declaration",
+    ],
+    whole: &[],
+    wrappers: &[],
+    leading: &["line_comment", "block_comment"],
+};
+
+/// Grammar for a file extension
+fn grammar_for(path: &Path) -> Option<&'static Grammar> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "rs" => &RUST,
+        "py" | "pyi" => &PYTHON,
+        "js" | "mjs" | "cjs" | "jsx" => &JAVASCRIPT,
+        "ts" | "mts" | "cts" => &TYPESCRIPT,
+        "tsx" => &TSX,
+        "go" => &GO,
+        "java" => &JAVA,
+        _ => return None,
+    })
+}
+
+/// Replaces each supported file with its declarations
+#[derive(Debug, Clone, Default)]
+pub struct Signatures;
+
+impl ContentTransform for Signatures {
+    fn apply(&self, path: &Path, content: &str) -> String {
+        let Some(grammar) = grammar_for(path) else {
+            return content.to_string();
+        };
+        let mut parser = Parser::new();
+        if parser.set_language(&(grammar.language)()).is_err() {
+            return content.to_string();
+        }
+        let Some(tree) = parser.parse(content, None) else {
+            return content.to_string();
+        };
+
+        let map = CodeMap {
+            grammar,
+            source: content,
+        };
+        let mut items = Vec::new();
+        map.collect(tree.root_node(), false, &mut items);
+        let mut output = items.join("\n\n");
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output
+    }
+}
+
+struct CodeMap<'a> {
+    grammar: &'static Grammar,
+    source: &'a str,
+}
+
+impl CodeMap<'_> {
+    /// Render the declarations among `parent`'s children, skipping private
+    /// members when `members` is set
+    fn collect(&self, parent: Node, members: bool, items: &mut Vec<String>) {
+        let mut cursor = parent.walk();
+        for child in parent.named_children(&mut cursor) {
+            let Some(declaration) = self.unwrap(child) else {
+                continue;
+            };
+            if members && self.is_private(parent, declaration) {
+                continue;
+            }
+            items.push(self.render(child, declaration));
+        }
+    }
+
+    /// The declaration `node` is or wraps, if any
+    fn unwrap<'tree>(&self, node: Node<'tree>) -> Option<Node<'tree>> {
+        let kind = node.kind();
+        if let Some((_, field)) = self.grammar.wrappers.iter().find(|(w, _)| *w == kind) {
+            return node
+                .child_by_field_name(field)
+                .and_then(|inner| self.unwrap(inner));
+        }
+        let grammar = self.grammar;
+        [grammar.functions, grammar.containers, grammar.whole]
+            .iter()
+            .any(|kinds| kinds.contains(&kind))
+            .then_some(node)
+    }
+
+    /// `outer` spans the declaration with any wrapper (export, decorators)
+    fn render(&self, outer: Node, declaration: Node) -> String {
+        let start = self.leading_start(outer);
+        let indent = self.indent(outer);
+        let kind = declaration.kind();
+        let body = declaration.child_by_field_name("body");
+
+        let Some(body) = body.filter(|_| !self.grammar.whole.contains(&kind)) else {
+            return self.text(start, outer.end_byte());
+        };
+        let header = self.text(start, body.start_byte()).trim_end().to_string();
+
+        if self.grammar.functions.contains(&kind) {
+            return match self.grammar.bodies {
+                Bodies::Braces => format!("{} {{ ... }}", header),
+                Bodies::Indented => match self.docstring(body) {
+                    Some(docstring) => self.text(start, docstring.end_byte()),
+                    None => format!("{}\n{}    ...", header, indent),
+                },
+            };
+        }
+
+        let mut members = Vec::new();
+        self.collect(body, true, &mut members);
+        let members = members.join("\n");
+        match self.grammar.bodies {
+            Bodies::Braces if members.is_empty() => format!("{} {{ ... }}", header),
+            Bodies::Braces => format!("{} {{\n{}\n{}}}", header, members, indent),
+            Bodies::Indented => {
+                let head = match self.docstring(body) {
+                    Some(docstring) => self.text(start, docstring.end_byte()),
+                    None => header,
+                };
+                if members.is_empty() {
+                    format!("{}\n{}    ...", head, indent)
+                } else {
+                    format!("{}\n{}", head, members)
+                }
+            }
+        }
+    }
+
+    /// Start of the comments and attributes directly above `node`, from the
+    /// beginning of their line
+    fn leading_start(&self, node: Node) -> usize {
+        let mut first = node;
+        while let Some(previous) = first.prev_named_sibling() {
+            let adjacent = previous.end_position().row + 1 >= first.start_position().row;
+            if !adjacent || !self.grammar.leading.contains(&previous.kind()) {
+                break;
+            }
+            first = previous;
+        }
+        self.line_start(first.start_byte())
+    }
+
+    /// A Python docstring: a string as the first statement of `body`
+    fn docstring<'tree>(&self, body: Node<'tree>) -> Option<Node<'tree>> {
+        let first = body.named_child(0)?;
+        (first.kind() == "expression_statement"
+            && first.named_child(0).is_some_and(|e| e.kind() == "string"))
+        .then_some(first)
+    }
+
+    /// Is `member` of `container` hidden from outside callers?
+    fn is_private(&self, container: Node, member: Node) -> bool {
+        let name = member
+            .child_by_field_name("name")
+            .map(|name| self.text(name.start_byte(), name.end_byte()));
+        let has_child = |kind: &str, text: &str| {
+            let mut cursor = member.walk();
+            let found = member.children(&mut cursor).any(|c| {
+                c.kind() == kind && self.text(c.start_byte(), c.end_byte()).contains(text)
+            });
+            found
+        };
+        match self.grammar.privacy {
+            Privacy::RustPub => {
+                let impl_item = container.parent().filter(|p| p.kind() == "impl_item");
+                impl_item.is_some_and(|i| i.child_by_field_name("trait").is_none())
+                    && !has_child("visibility_modifier", "pub")
+            }
+            Privacy::Modifier => {
+                has_child("modifiers", "private")
+                    || has_child("accessibility_modifier", "private")
+                    || name.is_some_and(|name| name.starts_with('#'))
+            }
+            Privacy::Underscore => name.is_some_and(|name| {
+                name.starts_with('_') && !(name.starts_with("__") && name.ends_with("__"))
+            }),
+        }
+    }
+
+    /// Leading whitespace of the line `node` starts on
+    fn indent(&self, node: Node) -> String {
+        let line = self.text(self.line_start(node.start_byte()), node.start_byte());
+        line.chars().take_while(|c| c.is_whitespace()).collect()
+    }
+
+    fn line_start(&self, byte: usize) -> usize {
+        self.source[..byte].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn text(&self, start: usize, end: usize) -> String {
+        self.source[start..end].to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signatures(path: &str, content: &str) -> String {
+        Signatures.apply(Path::new(path), content)
+    }
+
+    #[test]
+    fn test_rust_signatures() {
+        let source = r#"use std::fmt;
+
+/// A point
+#[derive(Debug)]
+pub struct Point {
+    pub x: i32,
+}
+
+impl Point {
+    /// Make one
+    pub fn new(x: i32) -> Self {
+        Self { x }
+    }
+
+    fn helper(&self) -> i32 {
+        self.x * 2
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.x)
+    }
+}
+
+fn main() {
+    println!("{}", Point::new(1));
+}
+"#;
+        assert_eq!(
+            signatures("lib.rs", source),
+            r#"/// A point
+#[derive(Debug)]
+pub struct Point {
+    pub x: i32,
+}
+
+impl Point {
+    /// Make one
+    pub fn new(x: i32) -> Self { ... }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { ... }
+}
+
+fn main() { ... }
+"#
+        );
+    }
+
+    #[test]
+    fn test_python_signatures() {
+        let source = r#"import os
+
+class Store(Base):
+    """Key-value store."""
+
+    def __init__(self, path):
+        self.path = path
+
+    @property
+    def size(self) -> int:
+        """Number of keys."""
+        return len(self._keys())
+
+    def _keys(self):
+        return os.listdir(self.path)
+
+
+def open_store(path):
+    return Store(path)
+"#;
+        assert_eq!(
+            signatures("store.py", source),
+            r#"class Store(Base):
+    """Key-value store."""
+    def __init__(self, path):
+        ...
+    @property
+    def size(self) -> int:
+        """Number of keys."""
+
+def open_store(path):
+    ...
+"#
+        );
+    }
+
+    #[test]
+    fn test_typescript_and_go_signatures() {
+        let source = "export interface Props {\n  name: string;\n}\n\nexport class Widget {\n  private cache = 1;\n  render(): string {\n    return '';\n  }\n  private reset() {}\n}\n";
+        assert_eq!(
+            signatures("widget.ts", source),
+            "export interface Props {\n  name: string;\n}\n\nexport class Widget {\n  render(): string { ... }\n}\n"
+        );
+
+        let source = "package main\n\n// Run starts it\nfunc Run(n int) error {\n\treturn nil\n}\n";
+        assert_eq!(
+            signatures("main.go", source),
+            "// Run starts it\nfunc Run(n int) error { ... }\n"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_language_unchanged() {
+        assert_eq!(signatures("notes.txt", "fn main() {}"), "fn main() {}");
+    }
+}
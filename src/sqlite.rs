@@ -0,0 +1,101 @@
+//! SQLite database output (requires the `sqlite` feature)
+
+use crate::extensions::get_language_for_extension;
+use crate::utils::sha256_hex;
+use crate::{FileEntry, FilesToPromptError, Result};
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+const SCHEMA: &str = "
+    DROP TABLE IF EXISTS files;
+    CREATE TABLE files (
+        path TEXT PRIMARY KEY,
+        content TEXT NOT NULL,
+        language TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        sha256 TEXT NOT NULL
+    );";
+
+fn db_error(err: rusqlite::Error) -> FilesToPromptError {
+    FilesToPromptError::Database(err.to_string())
+}
+
+/// Write collected files into a `files(path, content, language, size, sha256)` table.
+///
+/// Any existing `files` table in the database is replaced.
+pub fn write_sqlite(db_path: &Path, files: &[FileEntry]) -> Result<()> {
+    let mut conn = Connection::open(db_path).map_err(db_error)?;
+    let tx = conn.transaction().map_err(db_error)?;
+    tx.execute_batch(SCHEMA).map_err(db_error)?;
+
+    {
+        let mut insert = tx
+            .prepare(
+                "INSERT OR REPLACE INTO files (path, content, language, size, sha256)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .map_err(db_error)?;
+
+        for file in files {
+            let extension = file.path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            insert
+                .execute(params![
+                    file.path.display().to_string(),
+                    file.content,
+                    get_language_for_extension(extension),
+                    file.content.len() as i64,
+                    sha256_hex(file.content.as_bytes()),
+                ])
+                .map_err(db_error)?;
+        }
+    }
+
+    tx.commit().map_err(db_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_sqlite() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("bundle.db");
+        let files = vec![FileEntry::new(
+            PathBuf::from("src/app.py"),
+            "abc".to_string(),
+        )];
+
+        // Writing twice replaces the table rather than failing on duplicates
+        write_sqlite(&db_path, &files).unwrap();
+        write_sqlite(&db_path, &files).unwrap();
+
+        let conn = Connection::open(&db_path).unwrap();
+        let row: (String, String, String, i64, String) = conn
+            .query_row(
+                "SELECT path, content, language, size, sha256 FROM files",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .unwrap();
+
+        assert_eq!(row.0, "src/app.py");
+        assert_eq!(row.1, "abc");
+        assert_eq!(row.2, "python");
+        assert_eq!(row.3, 3);
+        assert_eq!(
+            row.4,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Size reports over the collected files (`--stats`)
+
+use crate::utils::{format_thousands, Tokenizer};
+use crate::FileEntry;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Token counts per file and per directory, each sorted descending
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenBreakdown {
+    pub files: Vec<(PathBuf, usize)>,
+    /// Every ancestor directory of a file, with the sum of the files below it
+    pub dirs: Vec<(PathBuf, usize)>,
+}
+
+impl TokenBreakdown {
+    /// Count the tokens of each file's content and aggregate them by directory
+    pub fn new(files: &[FileEntry], tokenizer: Tokenizer) -> Self {
+        Self::with_counter(files, |file| tokenizer.count(&file.content))
+    }
+
+    /// Like `new`, with the per-file counts coming from `count`
+    pub fn with_counter(files: &[FileEntry], count: impl Fn(&FileEntry) -> usize) -> Self {
+        let mut per_file = Vec::new();
+        let mut per_dir: BTreeMap<&Path, usize> = BTreeMap::new();
+
+        for file in files {
+            let tokens = count(file);
+            per_file.push((file.path.clone(), tokens));
+            for dir in file.path.ancestors().skip(1) {
+                // Skip the empty relative root and the filesystem root
+                if !dir.as_os_str().is_empty() && dir.parent().is_some() {
+                    *per_dir.entry(dir).or_default() += tokens;
+                }
+            }
+        }
+
+        let mut dirs: Vec<_> = per_dir
+            .into_iter()
+            .map(|(dir, tokens)| (dir.to_path_buf(), tokens))
+            .collect();
+        sort_descending(&mut per_file);
+        sort_descending(&mut dirs);
+
+        Self {
+            files: per_file,
+            dirs,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.files.iter().map(|(_, tokens)| tokens).sum()
+    }
+
+    /// Render the breakdown as two right-aligned tables
+    pub fn render(&self) -> String {
+        let width = self.total().to_string().len();
+        let mut output = vec![format!("Total tokens: {}", self.total())];
+
+        output.push(String::new());
+        output.push("Tokens by directory:".to_string());
+        for (dir, tokens) in &self.dirs {
+            output.push(format!(
+                "  {:>width$}  {}/",
+                tokens,
+                dir.display(),
+                width = width
+            ));
+        }
+
+        output.push(String::new());
+        output.push("Tokens by file:".to_string());
+        for (path, tokens) in &self.files {
+            output.push(format!(
+                "  {:>width$}  {}",
+                tokens,
+                path.display(),
+                width = width
+            ));
+        }
+
+        output.join("\n")
+    }
+}
+
+/// `wc`-style totals over an emitted bundle
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextCounts {
+    pub files: usize,
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub bytes: usize,
+}
+
+impl TextCounts {
+    /// Count `output`, which contains `files` files
+    pub fn new(output: &str, files: usize) -> Self {
+        Self {
+            files,
+            lines: output.lines().count(),
+            words: output.split_whitespace().count(),
+            chars: output.chars().count(),
+            bytes: output.len(),
+        }
+    }
+
+    /// Render the counts on one line
+    pub fn render(&self) -> String {
+        format!(
+            "Emitted {} {}: {} lines, {} words, {} chars, {} bytes",
+            format_thousands(self.files),
+            if self.files == 1 { "file" } else { "files" },
+            format_thousands(self.lines),
+            format_thousands(self.words),
+            format_thousands(self.chars),
+            format_thousands(self.bytes)
+        )
+    }
+}
+
+fn sort_descending(rows: &mut [(PathBuf, usize)]) {
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_breakdown() {
+        let files = vec![
+            FileEntry::new(PathBuf::from("src/lib.rs"), "a ".repeat(40)),
+            FileEntry::new(PathBuf::from("src/output/xml.rs"), "b ".repeat(200)),
+            FileEntry::new(PathBuf::from("README.md"), "c ".repeat(10)),
+        ];
+        let breakdown = TokenBreakdown::new(&files, Tokenizer::Approx);
+
+        let file_order: Vec<_> = breakdown.files.iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(
+            file_order,
+            vec![
+                PathBuf::from("src/output/xml.rs"),
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from("README.md")
+            ]
+        );
+
+        let dir_order: Vec<_> = breakdown.dirs.iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(
+            dir_order,
+            vec![PathBuf::from("src"), PathBuf::from("src/output")]
+        );
+        assert_eq!(
+            breakdown.dirs[0].1,
+            breakdown.files[0].1 + breakdown.files[1].1
+        );
+        assert_eq!(
+            breakdown.total(),
+            breakdown.dirs[0].1 + breakdown.files[2].1
+        );
+
+        let table = breakdown.render();
+        assert!(table.starts_with(&format!("Total tokens: {}", breakdown.total())));
+        assert!(table.contains("  src/output/\n"));
+        assert!(table.ends_with("  README.md"));
+    }
+
+    #[test]
+    fn test_text_counts() {
+        let counts = TextCounts::new("a.txt\n---\nhéllo wörld\n---\n", 1);
+        assert_eq!(
+            counts,
+            TextCounts {
+                files: 1,
+                lines: 4,
+                words: 5,
+                chars: 26,
+                bytes: 28,
+            }
+        );
+        assert_eq!(
+            counts.render(),
+            "Emitted 1 file: 4 lines, 5 words, 26 chars, 28 bytes"
+        );
+    }
+}
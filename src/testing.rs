@@ -0,0 +1,309 @@
+//! Test-support helpers for output formatters
+//!
+//! Provides golden-output helpers and a conformance suite that any
+//! [`OutputFormatter`] implementation can run, so third-party and built-in
+//! formatters behave consistently on edge cases such as empty files, trailing
+//! newlines and content containing backticks.
+//!
+//! ```
+//! use fusefiles::testing::ConformanceSuite;
+//! use fusefiles::DefaultFormatter;
+//!
+//! ConformanceSuite::new().assert_passes(DefaultFormatter::new);
+//! ```
+
+use crate::output::OutputFormatter;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that rewrites golden files instead of comparing against them
+pub const BLESS_ENV_VAR: &str = "FUSE_BLESS";
+
+/// Render in-memory files through a formatter the same way `FileProcessor` joins output
+pub fn render<F: OutputFormatter>(
+    formatter: &mut F,
+    files: &[(&str, &str)],
+    line_numbers: bool,
+) -> String {
+    let mut output = Vec::new();
+
+    let start = formatter.start_output();
+    if !start.is_empty() {
+        output.push(start);
+    }
+
+    for (path, content) in files {
+        output.push(formatter.format_file(Path::new(path), content, line_numbers));
+    }
+
+    let end = formatter.end_output();
+    if !end.is_empty() {
+        output.push(end);
+    }
+
+    output.join("\n")
+}
+
+/// Compare `actual` against the contents of a golden file.
+///
+/// When the `FUSE_BLESS` environment variable is set, the golden file is
+/// (re)written instead, which is how new golden files are created.
+///
+/// # Panics
+///
+/// Panics when the golden file is missing or its contents differ from `actual`.
+pub fn assert_golden(golden_path: &Path, actual: &str) {
+    if std::env::var_os(BLESS_ENV_VAR).is_some() {
+        if let Some(parent) = golden_path.parent() {
+            fs::create_dir_all(parent).expect("failed to create golden directory");
+        }
+        fs::write(golden_path, actual).expect("failed to write golden file");
+        return;
+    }
+
+    let expected = fs::read_to_string(golden_path).unwrap_or_else(|_| {
+        panic!(
+            "Golden file {} is missing; rerun with {}=1 to create it",
+            golden_path.display(),
+            BLESS_ENV_VAR
+        )
+    });
+
+    if expected != actual {
+        panic!(
+            "Output does not match golden file {}\n--- expected ---\n{}\n--- actual ---\n{}",
+            golden_path.display(),
+            expected,
+            actual
+        );
+    }
+}
+
+/// A single input exercised by the conformance suite
+#[derive(Debug, Clone)]
+pub struct ConformanceCase {
+    pub name: &'static str,
+    pub path: PathBuf,
+    pub content: &'static str,
+}
+
+/// Built-in edge cases every formatter should handle
+pub fn conformance_cases() -> Vec<ConformanceCase> {
+    vec![
+        ConformanceCase {
+            name: "plain",
+            path: PathBuf::from("src/plain.txt"),
+            content: "Hello, world!",
+        },
+        ConformanceCase {
+            name: "empty file",
+            path: PathBuf::from("empty.py"),
+            content: "",
+        },
+        ConformanceCase {
+            name: "trailing newline",
+            path: PathBuf::from("trailing.rs"),
+            content: "fn main() {}\n",
+        },
+        ConformanceCase {
+            name: "backtick content",
+            path: PathBuf::from("README.md"),
+            content: "Example:\n```rust\nlet x = 1;\n```\n````\nnested\n````",
+        },
+        ConformanceCase {
+            name: "unicode",
+            path: PathBuf::from("docs/ünïcode.txt"),
+            content: "naïve café — ✓",
+        },
+    ]
+}
+
+/// A check that failed for a particular conformance case
+#[derive(Debug, Clone)]
+pub struct ConformanceFailure {
+    pub case: &'static str,
+    pub check: &'static str,
+    pub output: String,
+}
+
+impl fmt::Display for ConformanceFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} failed; output was:\n{}",
+            self.case, self.check, self.output
+        )
+    }
+}
+
+/// Conformance suite for `OutputFormatter` implementations
+#[derive(Debug, Clone)]
+pub struct ConformanceSuite {
+    embeds_content: bool,
+    embeds_paths: bool,
+}
+
+impl Default for ConformanceSuite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConformanceSuite {
+    pub fn new() -> Self {
+        Self {
+            embeds_content: true,
+            embeds_paths: true,
+        }
+    }
+
+    /// Skip content checks for formatters that intentionally omit file contents
+    pub fn without_content(mut self) -> Self {
+        self.embeds_content = false;
+        self
+    }
+
+    /// Skip path checks for formatters that intentionally omit file paths
+    pub fn without_paths(mut self) -> Self {
+        self.embeds_paths = false;
+        self
+    }
+
+    /// Run every check against fresh formatters created by `make`
+    pub fn run<F, M>(&self, make: M) -> Vec<ConformanceFailure>
+    where
+        F: OutputFormatter,
+        M: Fn() -> F,
+    {
+        let mut failures = Vec::new();
+
+        for case in conformance_cases() {
+            let output = make().format_file(&case.path, case.content, false);
+            let mut check = |passed: bool, name: &'static str| {
+                if !passed {
+                    failures.push(ConformanceFailure {
+                        case: case.name,
+                        check: name,
+                        output: output.clone(),
+                    });
+                }
+            };
+
+            if self.embeds_paths {
+                check(
+                    output.contains(&case.path.display().to_string()),
+                    "path is included",
+                );
+            }
+            check(
+                make().format_file(&case.path, case.content, false) == output,
+                "output is deterministic",
+            );
+
+            if self.embeds_content {
+                // Blocks are joined with newlines, so a single trailing newline may be folded
+                let content = case.content.strip_suffix('\n').unwrap_or(case.content);
+                check(output.contains(content), "content is preserved");
+                if self.embeds_paths {
+                    check(
+                        !case.content.is_empty() || !output.trim().is_empty(),
+                        "empty file still produces a block",
+                    );
+                }
+
+                if let Some(first_line) = case.content.lines().next() {
+                    let numbered = make().format_file(&case.path, case.content, true);
+                    check(
+                        numbered.contains(&format!("1  {}", first_line)),
+                        "line numbers are applied",
+                    );
+                }
+            }
+        }
+
+        failures
+    }
+
+    /// Run the suite and panic with a readable report if any check fails
+    pub fn assert_passes<F, M>(&self, make: M)
+    where
+        F: OutputFormatter,
+        M: Fn() -> F,
+    {
+        let failures = self.run(make);
+        if !failures.is_empty() {
+            let report = failures
+                .iter()
+                .map(|failure| failure.to_string())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            panic!(
+                "{} conformance check(s) failed:\n\n{}",
+                failures.len(),
+                report
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{
+        DefaultFormatter, ManifestFormat, ManifestFormatter, MarkdownFormatter, RawFormatter,
+        XmlFormatter,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_builtin_formatters_conform() {
+        ConformanceSuite::new().assert_passes(DefaultFormatter::new);
+        ConformanceSuite::new().assert_passes(MarkdownFormatter::new);
+        ConformanceSuite::new().assert_passes(XmlFormatter::new);
+        ConformanceSuite::new()
+            .without_paths()
+            .assert_passes(|| RawFormatter::new(Some("----".to_string())));
+        ConformanceSuite::new()
+            .without_content()
+            .assert_passes(|| ManifestFormatter::new(ManifestFormat::Csv));
+    }
+
+    #[test]
+    fn test_suite_reports_failures() {
+        struct PathOnly;
+        impl OutputFormatter for PathOnly {
+            fn format_file(&mut self, path: &Path, _content: &str, _line_numbers: bool) -> String {
+                path.display().to_string()
+            }
+            fn format_table_of_contents(&mut self, toc: &str) -> String {
+                toc.to_string()
+            }
+            fn start_output(&mut self) -> String {
+                String::new()
+            }
+            fn end_output(&mut self) -> String {
+                String::new()
+            }
+        }
+
+        let failures = ConformanceSuite::new().run(|| PathOnly);
+        assert!(failures.iter().any(|f| f.check == "content is preserved"));
+        assert!(ConformanceSuite::new()
+            .without_content()
+            .run(|| PathOnly)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_render_and_golden() {
+        let temp_dir = TempDir::new().unwrap();
+        let golden = temp_dir.path().join("xml.golden");
+        let output = render(&mut XmlFormatter::new(), &[("a.txt", "A")], false);
+        fs::write(&golden, &output).unwrap();
+
+        assert_golden(&golden, &output);
+        assert!(output.starts_with("<documents>\n<document index=\"1\">"));
+        assert!(output.ends_with("</document>\n</documents>"));
+    }
+}
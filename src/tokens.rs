@@ -0,0 +1,42 @@
+//! Exact token counting with a tiktoken-compatible tokenizer (requires the `tokens` feature)
+//!
+//! Counts default to the `o200k_base` encoding (GPT-4o and later); `cl100k_base`
+//! (GPT-4, GPT-3.5) is available for older models. Other model families tokenize
+//! differently, but either is far closer than a byte estimate.
+
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+
+fn o200k_encoder() -> &'static CoreBPE {
+    static ENCODER: OnceLock<CoreBPE> = OnceLock::new();
+    ENCODER.get_or_init(|| tiktoken_rs::o200k_base().expect("o200k_base encoding is bundled"))
+}
+
+fn cl100k_encoder() -> &'static CoreBPE {
+    static ENCODER: OnceLock<CoreBPE> = OnceLock::new();
+    ENCODER.get_or_init(|| tiktoken_rs::cl100k_base().expect("cl100k_base encoding is bundled"))
+}
+
+/// Count the tokens in `text` with `o200k_base`
+pub fn count_tokens(text: &str) -> usize {
+    o200k_encoder().encode_with_special_tokens(text).len()
+}
+
+/// Count the tokens in `text` with `cl100k_base`
+pub fn count_cl100k_tokens(text: &str) -> usize {
+    cl100k_encoder().encode_with_special_tokens(text).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_tokens() {
+        assert_eq!(count_tokens(""), 0);
+        assert_eq!(count_tokens("hello world"), 2);
+        assert_eq!(count_cl100k_tokens("hello world"), 2);
+        assert_eq!(count_tokens("こんにちは世界"), 2);
+        assert_eq!(count_cl100k_tokens("こんにちは世界"), 4);
+    }
+}
@@ -0,0 +1,91 @@
+//! External content transform (`--transform-cmd`)
+//!
+//! The command runs once per file through `sh -c`, reading the file's content
+//! on stdin, with the path in [`PATH_ENV_VAR`]; what it prints replaces the
+//! content. A command that fails aborts the run rather than letting the
+//! untransformed content through, since it may be there to scrub it.
+
+use crate::{FilesToPromptError, Result};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Environment variable holding the path of the file being transformed
+pub const PATH_ENV_VAR: &str = "FUSE_PATH";
+
+/// Shell command that rewrites each file's content
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformCmd {
+    command: String,
+}
+
+impl TransformCmd {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+        }
+    }
+
+    /// Pipe `content` through the command
+    pub fn run(&self, path: &Path, content: &str) -> Result<String> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env(PATH_ENV_VAR, path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.error(path, format!("could not run it: {}", e)))?;
+
+        let input = content.to_string();
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        // As in `FilterCmd`, written from a separate thread so a command that
+        // prints before reading everything can't deadlock against a full pipe
+        let writer = std::thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+        let output = child
+            .wait_with_output()
+            .map_err(|e| self.error(path, e.to_string()))?;
+        let _ = writer.join();
+
+        if !output.status.success() {
+            return Err(self.error(path, format!("exited with {}", output.status)));
+        }
+        String::from_utf8(output.stdout)
+            .map_err(|_| self.error(path, "printed invalid UTF-8".into()))
+    }
+
+    fn error(&self, path: &Path, message: String) -> FilesToPromptError {
+        FilesToPromptError::TransformCommand(format!(
+            "`{}` on {}: {}",
+            self.command,
+            path.display(),
+            message
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_pipes_content_and_path() {
+        let path = Path::new("src/it's.rs");
+        let upper = TransformCmd::new("tr a-z A-Z");
+        assert_eq!(upper.run(path, "fn main() {}\n").unwrap(), "FN MAIN() {}\n");
+
+        let named = TransformCmd::new("cat; printf '%s' \"$FUSE_PATH\"");
+        assert_eq!(named.run(path, "x\n").unwrap(), "x\nsrc/it's.rs");
+    }
+
+    #[test]
+    fn test_failing_command_is_an_error() {
+        let result = TransformCmd::new("cat >/dev/null; exit 2").run(Path::new("a.rs"), "x");
+        assert!(matches!(
+            result,
+            Err(FilesToPromptError::TransformCommand(_))
+        ));
+    }
+}
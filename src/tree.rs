@@ -1,9 +1,8 @@
 //! Tree generation for directory structure visualization
 
-use crate::ignore::CustomIgnore;
-use crate::{Result, TocMode};
-use ignore::WalkBuilder;
-use std::collections::BTreeMap;
+use crate::file_processor::FileEntry;
+use crate::TocMode;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
 /// Represents a node in the directory tree
@@ -50,6 +49,17 @@ impl TreeNode {
                 .sum::<usize>()
     }
 
+    /// Tokens of this file, or of every file below this directory
+    pub fn count_tokens(&self, token_counts: &HashMap<PathBuf, usize>) -> usize {
+        if self.is_file {
+            return token_counts.get(&self.path).copied().unwrap_or(0);
+        }
+        self.children
+            .values()
+            .map(|child| child.count_tokens(token_counts))
+            .sum()
+    }
+
     /// Estimate the number of lines this tree would take to render
     pub fn estimate_render_lines(&self, show_files: bool) -> usize {
         if !show_files && self.is_file {
@@ -64,187 +74,50 @@ impl TreeNode {
     }
 }
 
-/// Handles tree generation for directory structures
+/// Builds the table of contents tree from the files a run collected
+#[derive(Debug, Default)]
 pub struct TreeGenerator {
-    extensions: Vec<String>,
-    include_hidden: bool,
-    ignore_gitignore: bool,
-    custom_ignore: CustomIgnore,
+    token_counts: Option<HashMap<PathBuf, usize>>,
 }
 
 impl TreeGenerator {
-    pub fn new(
-        extensions: Vec<String>,
-        include_hidden: bool,
-        ignore_gitignore: bool,
-        custom_ignore: CustomIgnore,
-    ) -> Self {
-        Self {
-            extensions,
-            include_hidden,
-            ignore_gitignore,
-            custom_ignore,
-        }
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Generate a tree structure for the given paths
-    pub fn generate_tree(&self, paths: &[PathBuf]) -> Result<Vec<TreeNode>> {
-        let mut trees = Vec::new();
-
-        for path in paths {
-            if path.is_file() {
-                if self.should_include_file(path) {
-                    let name = path
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("?")
-                        .to_string();
-                    trees.push(TreeNode::new(name, path.clone(), true));
-                }
-            } else if path.is_dir() {
-                if let Some(tree) = self.generate_directory_tree(path)? {
-                    trees.push(tree);
-                }
-            }
-        }
-
-        Ok(trees)
+    /// Annotate each entry with its token count
+    pub fn with_token_counts(mut self, token_counts: HashMap<PathBuf, usize>) -> Self {
+        self.token_counts = Some(token_counts);
+        self
     }
 
-    /// Generate tree for a single directory
-    fn generate_directory_tree(&self, dir_path: &Path) -> Result<Option<TreeNode>> {
-        let dir_name = dir_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("?")
-            .to_string();
-
-        let mut root = TreeNode::new(dir_name, dir_path.to_path_buf(), false);
-
-        let walker = self.build_walker(dir_path)?;
-
-        for result in walker {
-            let entry = match result {
-                Ok(entry) => entry,
-                Err(err) => return Err(map_walk_error(err)),
-            };
-
-            if entry.depth() == 0 {
-                continue;
-            }
-
-            let entry_path = entry.path();
-            #[cfg(test)]
-            println!("Processing path: {:?}", entry_path);
-
-            let is_dir = entry
-                .file_type()
-                .map(|ft| ft.is_dir())
-                .unwrap_or_else(|| entry_path.is_dir());
-
-            if !self.include_hidden
-                && entry_path
-                    .file_name()
-                    .and_then(|name| name.to_str())
-                    .map(|name| name.starts_with('.'))
-                    .unwrap_or(false)
-            {
-                #[cfg(test)]
-                println!("Hidden entry skipped: {:?}", entry_path);
-                continue;
-            }
-
-            if is_dir && self.custom_ignore.should_ignore_dir(entry_path) {
-                #[cfg(test)]
-                println!("Path ignored: {:?}", entry_path);
-                continue;
-            }
-
-            if !is_dir && self.custom_ignore.should_ignore_file(entry_path) {
-                #[cfg(test)]
-                println!("File ignored by custom rule: {:?}", entry_path);
-                continue;
-            }
+    /// Generate one tree per input path holding the `files` collected under it,
+    /// so the tree lists exactly the files the bundle contains
+    pub fn generate_tree(&self, paths: &[PathBuf], files: &[FileEntry]) -> Vec<TreeNode> {
+        let mut trees: Vec<Option<TreeNode>> = paths
+            .iter()
+            .map(|path| {
+                path.is_dir()
+                    .then(|| TreeNode::new(node_name(path), path.clone(), false))
+            })
+            .collect();
 
-            if !is_dir && !self.should_include_file(entry_path) {
-                #[cfg(test)]
-                println!("File criteria not met: {:?}", entry_path);
+        for file in files {
+            let Some(path) = paths.get(file.root) else {
                 continue;
+            };
+            match &mut trees[file.root] {
+                Some(root) if !root.is_file => self.add_path_to_tree(root, path, &file.path),
+                Some(_) => {}
+                None => trees[file.root] = Some(TreeNode::new(node_name(path), path.clone(), true)),
             }
-
-            #[cfg(test)]
-            println!("Adding to tree: {:?} (is_file: {})", entry_path, !is_dir);
-
-            self.add_path_to_tree(&mut root, dir_path, entry_path, !is_dir);
         }
 
-        // Always return the root, even if empty, so tests can see the structure
-        Ok(Some(root))
+        trees.into_iter().flatten().collect()
     }
 
-    fn build_walker(&self, dir_path: &Path) -> Result<ignore::Walk> {
-        let mut builder = WalkBuilder::new(dir_path);
-        builder.sort_by_file_name(|a, b| a.cmp(b));
-        builder.follow_links(false);
-        if self.include_hidden {
-            builder.hidden(false);
-        }
-
-        if self.ignore_gitignore {
-            builder.git_ignore(false);
-            builder.git_global(false);
-            builder.git_exclude(false);
-            builder.ignore(false);
-            builder.parents(false);
-        } else {
-            builder.git_ignore(true);
-            builder.git_global(true);
-            builder.git_exclude(true);
-            builder.ignore(true);
-            builder.parents(true);
-            builder.require_git(false);
-        }
-
-        let root = dir_path.to_path_buf();
-        let custom_for_dirs = self.custom_ignore.clone();
-        let include_hidden = self.include_hidden;
-        builder.filter_entry(move |entry| {
-            if entry.path() == root {
-                return true;
-            }
-
-            let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-
-            if !include_hidden
-                && is_dir
-                && entry
-                    .path()
-                    .file_name()
-                    .and_then(|name| name.to_str())
-                    .map(|name| name.starts_with('.'))
-                    .unwrap_or(false)
-            {
-                return false;
-            }
-
-            if is_dir && custom_for_dirs.should_ignore_dir(entry.path()) {
-                return false;
-            }
-
-            true
-        });
-
-        Ok(builder.build())
-    }
-
-    /// Add a path to the tree structure
-    fn add_path_to_tree(
-        &self,
-        root: &mut TreeNode,
-        base_path: &Path,
-        full_path: &Path,
-        is_file: bool,
-    ) {
+    /// Add a file to the tree structure, creating its parent directories
+    fn add_path_to_tree(&self, root: &mut TreeNode, base_path: &Path, full_path: &Path) {
         // Get relative path from base
         let relative_path = match full_path.strip_prefix(base_path) {
             Ok(rel) => rel,
@@ -256,8 +129,7 @@ impl TreeGenerator {
 
         for (i, component) in components.iter().enumerate() {
             let name = component.as_os_str().to_str().unwrap_or("?").to_string();
-            let is_last = i == components.len() - 1;
-            let node_is_file = is_last && is_file;
+            let node_is_file = i == components.len() - 1;
 
             if !current.children.contains_key(&name) {
                 let node_path =
@@ -270,22 +142,6 @@ impl TreeGenerator {
         }
     }
 
-    /// Check if a file should be included based on extension filters
-    fn should_include_file(&self, path: &Path) -> bool {
-        if self.extensions.is_empty() {
-            return true;
-        }
-
-        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
-            self.extensions.iter().any(|ext| {
-                let ext = ext.strip_prefix('.').unwrap_or(ext);
-                extension == ext
-            })
-        } else {
-            false
-        }
-    }
-
     /// Render tree to string format
     pub fn render_tree(&self, trees: &[TreeNode], mode: TocMode) -> String {
         if trees.is_empty() {
@@ -310,7 +166,7 @@ impl TreeGenerator {
 
         for (i, tree) in trees.iter().enumerate() {
             let is_last = i == trees.len() - 1;
-            Self::render_node(tree, "", is_last, show_files, &mut output);
+            self.render_node(tree, "", is_last, show_files, &mut output);
         }
 
         output.join("\n")
@@ -318,6 +174,7 @@ impl TreeGenerator {
 
     /// Render a single tree node with proper indentation and tree characters
     fn render_node(
+        &self,
         node: &TreeNode,
         prefix: &str,
         is_last: bool,
@@ -333,11 +190,15 @@ impl TreeGenerator {
         let connector = if is_last { "└── " } else { "├── " };
 
         // Add file/directory indicator
-        let name = if node.is_file {
+        let mut name = if node.is_file {
             node.name.clone()
         } else {
             format!("{}/", node.name)
         };
+        if let Some(token_counts) = &self.token_counts {
+            let tokens = format_token_count(node.count_tokens(token_counts));
+            name.push_str(&format!(" ({} tok)", tokens));
+        }
 
         output.push(format!("{}{}{}", prefix, connector, name));
 
@@ -351,21 +212,30 @@ impl TreeGenerator {
                 format!("{}│   ", prefix)
             };
 
-            Self::render_node(child, &child_prefix, child_is_last, show_files, output);
+            self.render_node(child, &child_prefix, child_is_last, show_files, output);
         }
     }
 }
 
-fn map_walk_error(err: ignore::Error) -> crate::FilesToPromptError {
-    use std::io;
-
-    if let Some(io_err) = err.io_error() {
-        crate::FilesToPromptError::Io(io::Error::new(io_err.kind(), io_err.to_string()))
+/// Compact count for tree annotations: `850`, `12.4k`, `1.2M`
+fn format_token_count(tokens: usize) -> String {
+    if tokens < 1_000 {
+        tokens.to_string()
+    } else if tokens < 1_000_000 {
+        format!("{:.1}k", tokens as f64 / 1_000.0)
     } else {
-        crate::FilesToPromptError::Io(io::Error::other(err.to_string()))
+        format!("{:.1}M", tokens as f64 / 1_000_000.0)
     }
 }
 
+/// Last component of `path`, used as its node name
+fn node_name(path: &Path) -> String {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("?")
+        .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -399,45 +269,29 @@ mod tests {
     fn test_basic_tree_generation() {
         let temp_dir = TempDir::new().unwrap();
         let base_path = temp_dir.path();
-
-        // Create test structure
         fs::create_dir_all(base_path.join("subdir")).unwrap();
+        fs::create_dir_all(base_path.join("empty")).unwrap();
         fs::write(base_path.join("file1.txt"), "content1").unwrap();
-        fs::write(base_path.join("subdir/file2.txt"), "content2").unwrap();
-
-        let generator = TreeGenerator::new(
-            vec![],
-            false,
-            true, // ignore gitignore
-            CustomIgnore::new(vec![], false).unwrap(),
-        );
-
-        let trees = generator.generate_tree(&[base_path.to_path_buf()]).unwrap();
-
-        // Debug output
-        println!("Generated trees: {}", trees.len());
-        for (i, tree) in trees.iter().enumerate() {
-            println!(
-                "Tree {}: {} (children: {})",
-                i,
-                tree.name,
-                tree.children.len()
-            );
-        }
-
-        assert!(
-            !trees.is_empty(),
-            "Expected at least 1 tree, got {}",
-            trees.len()
-        );
 
+        let single = base_path.join("file1.txt");
+        let paths = [base_path.to_path_buf(), single.clone()];
+        let files = [
+            FileEntry::new(base_path.join("subdir/file2.txt"), String::new()),
+            FileEntry {
+                root: 1,
+                ..FileEntry::new(single, String::new())
+            },
+        ];
+        let trees = TreeGenerator::new().generate_tree(&paths, &files);
+
+        assert_eq!(trees.len(), 2);
         let tree = &trees[0];
         assert!(!tree.is_file);
-        assert!(
-            !tree.children.is_empty(),
-            "Expected at least 1 child, got {}",
-            tree.children.len()
-        );
+        // Only directories holding collected files appear
+        assert_eq!(tree.children.keys().collect::<Vec<_>>(), ["subdir"]);
+        assert!(tree.children["subdir"].children["file2.txt"].is_file);
+        assert!(trees[1].is_file);
+        assert_eq!(trees[1].name, "file1.txt");
     }
 
     #[test]
@@ -459,12 +313,7 @@ mod tests {
         root.add_child(file1);
         root.add_child(subdir);
 
-        let generator = TreeGenerator::new(
-            vec![],
-            false,
-            true,
-            CustomIgnore::new(vec![], false).unwrap(),
-        );
+        let generator = TreeGenerator::new();
         let output = generator.render_tree(&[root], TocMode::FilesAndDirs);
 
         assert!(output.contains("root/"));
@@ -473,6 +322,34 @@ mod tests {
         assert!(output.contains("    └── file2.txt"));
     }
 
+    #[test]
+    fn test_tree_rendering_with_token_counts() {
+        let mut root = TreeNode::new("root".to_string(), PathBuf::from("/root"), false);
+        let mut subdir = TreeNode::new("subdir".to_string(), PathBuf::from("/root/subdir"), false);
+        subdir.add_child(TreeNode::new(
+            "big.txt".to_string(),
+            PathBuf::from("/root/subdir/big.txt"),
+            true,
+        ));
+        root.add_child(TreeNode::new(
+            "small.txt".to_string(),
+            PathBuf::from("/root/small.txt"),
+            true,
+        ));
+        root.add_child(subdir);
+
+        let generator = TreeGenerator::new().with_token_counts(HashMap::from([
+            (PathBuf::from("/root/subdir/big.txt"), 12_400),
+            (PathBuf::from("/root/small.txt"), 850),
+        ]));
+        let output = generator.render_tree(&[root], TocMode::FilesAndDirs);
+
+        assert!(output.contains("└── root/ (13.2k tok)"));
+        assert!(output.contains("├── small.txt (850 tok)"));
+        assert!(output.contains("└── subdir/ (12.4k tok)"));
+        assert!(output.contains("└── big.txt (12.4k tok)"));
+    }
+
     #[test]
     fn test_auto_mode_line_estimation() {
         let mut root = TreeNode::new("root".to_string(), PathBuf::from("/root"), false);
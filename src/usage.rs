@@ -0,0 +1,212 @@
+//! Local, opt-in usage history and the `fuse usage` report
+//!
+//! Nothing is recorded unless the `FUSE_HISTORY` environment variable points at a
+//! log file. Each run appends one tab-separated line to that file and nothing ever
+//! leaves the machine.
+
+use crate::utils::{estimate_tokens_for_bytes, utc_datetime};
+use crate::Result;
+use chrono::{Datelike, Days, NaiveDate};
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable naming the history log file
+pub const HISTORY_ENV_VAR: &str = "FUSE_HISTORY";
+
+/// A single recorded run
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageRecord {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Size of the generated output in bytes
+    pub bytes: usize,
+    /// Estimated token count of the generated output
+    pub tokens: usize,
+    /// Absolute paths passed to the run
+    pub paths: Vec<PathBuf>,
+}
+
+impl UsageRecord {
+    fn to_line(&self) -> String {
+        let mut fields = vec![
+            self.timestamp.to_string(),
+            self.bytes.to_string(),
+            self.tokens.to_string(),
+        ];
+        fields.extend(self.paths.iter().map(|p| p.display().to_string()));
+        fields.join("\t")
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let timestamp = fields.next()?.parse().ok()?;
+        let bytes = fields.next()?.parse().ok()?;
+        let tokens = fields.next()?.parse().ok()?;
+        let paths = fields.map(PathBuf::from).collect();
+        Some(Self {
+            timestamp,
+            bytes,
+            tokens,
+            paths,
+        })
+    }
+}
+
+/// Returns the history log path if recording has been opted into
+pub fn history_path() -> Option<PathBuf> {
+    std::env::var_os(HISTORY_ENV_VAR)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Append a record for a completed run that produced `bytes` of output to the history log.
+///
+/// Only the size is needed, so streamed output can be recorded without keeping it.
+pub fn record_run(log_path: &Path, paths: &[PathBuf], bytes: usize) -> Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let record = UsageRecord {
+        timestamp,
+        bytes,
+        tokens: estimate_tokens_for_bytes(bytes),
+        paths: paths
+            .iter()
+            .map(|p| fs::canonicalize(p).unwrap_or_else(|_| p.clone()))
+            .collect(),
+    };
+
+    if let Some(parent) = log_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+    writeln!(file, "{}", record.to_line())?;
+    Ok(())
+}
+
+/// Read every well-formed record from the history log
+pub fn read_history(log_path: &Path) -> Result<Vec<UsageRecord>> {
+    if !log_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(log_path)?;
+    Ok(content.lines().filter_map(UsageRecord::from_line).collect())
+}
+
+/// Render the aggregated usage report for the given records
+pub fn render_report(records: &[UsageRecord]) -> String {
+    if records.is_empty() {
+        return "No runs recorded yet.".to_string();
+    }
+
+    let mut output = Vec::new();
+
+    let total_tokens: usize = records.iter().map(|r| r.tokens).sum();
+    output.push(format!("Runs recorded: {}", records.len()));
+    output.push(format!(
+        "Average tokens per bundle: {}",
+        total_tokens / records.len()
+    ));
+
+    let mut per_week: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for record in records {
+        *per_week.entry(week_start(record.timestamp)).or_default() += 1;
+    }
+
+    output.push(String::new());
+    output.push("Bundles per week:".to_string());
+    for (week, count) in &per_week {
+        output.push(format!("  {}  {}", week.format("%Y-%m-%d"), count));
+    }
+
+    let mut per_dir: HashMap<&Path, usize> = HashMap::new();
+    for record in records {
+        for path in &record.paths {
+            *per_dir.entry(path.as_path()).or_default() += 1;
+        }
+    }
+    let mut per_dir: Vec<_> = per_dir.into_iter().collect();
+    per_dir.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    output.push(String::new());
+    output.push("Most-fused paths:".to_string());
+    for (path, count) in per_dir.iter().take(10) {
+        output.push(format!("  {:>4}  {}", count, path.display()));
+    }
+
+    output.join("\n")
+}
+
+/// The Monday starting the UTC week of `timestamp`
+fn week_start(timestamp: u64) -> NaiveDate {
+    let date = utc_datetime(timestamp).date_naive();
+    let since_monday = date.weekday().num_days_from_monday();
+    date - Days::new(u64::from(since_monday))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_round_trip() {
+        let record = UsageRecord {
+            timestamp: 1_700_000_000,
+            bytes: 400,
+            tokens: 100,
+            paths: vec![PathBuf::from("/repo/src"), PathBuf::from("/repo/tests")],
+        };
+        assert_eq!(UsageRecord::from_line(&record.to_line()), Some(record));
+        assert_eq!(UsageRecord::from_line("garbage"), None);
+    }
+
+    #[test]
+    fn test_render_report() {
+        let records = vec![
+            UsageRecord {
+                timestamp: 0,
+                bytes: 40,
+                tokens: 10,
+                paths: vec![PathBuf::from("/repo/src")],
+            },
+            UsageRecord {
+                timestamp: 60,
+                bytes: 120,
+                tokens: 30,
+                paths: vec![PathBuf::from("/repo/src"), PathBuf::from("/repo/docs")],
+            },
+        ];
+
+        let report = render_report(&records);
+        assert!(report.contains("Runs recorded: 2"));
+        assert!(report.contains("Average tokens per bundle: 20"));
+        // The epoch was a Thursday, in the week starting Monday 1969-12-29
+        assert!(report.contains("1969-12-29  2"));
+        assert!(report.contains("   2  /repo/src"));
+    }
+
+    #[test]
+    fn test_week_start() {
+        // 2023-11-14 was a Tuesday
+        assert_eq!(week_start(1_700_000_000).to_string(), "2023-11-13");
+        assert_eq!(week_start(1_699_833_600).to_string(), "2023-11-13");
+    }
+
+    #[test]
+    fn test_render_empty_report() {
+        assert_eq!(render_report(&[]), "No runs recorded yet.");
+    }
+}
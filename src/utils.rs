@@ -1,6 +1,10 @@
 //! Utility functions for file processing and input/output
 
-use std::io::{self, Read};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::OnceLock;
 
 /// Add line numbers to content with proper padding
 pub fn add_line_numbers(content: &str) -> String {
@@ -53,6 +57,388 @@ pub fn determine_backtick_count(content: &str) -> String {
     backticks
 }
 
+/// Compute the lowercase hex SHA-256 digest of the given bytes
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Encode a string as a quoted JSON string literal
+pub fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Re-indent JSON with two spaces per level, one member or element per line.
+/// Only whitespace outside strings changes; `None` if brackets don't balance.
+pub fn pretty_json(json: &str) -> Option<String> {
+    let mut pretty = String::with_capacity(json.len() * 2);
+    let mut open = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    let newline = |pretty: &mut String, depth: usize| {
+        pretty.push('\n');
+        pretty.extend(std::iter::repeat_n(' ', depth * 2));
+    };
+
+    let mut chars = json.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            pretty.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                pretty.push(c);
+            }
+            '{' | '[' => {
+                let close = if c == '{' { '}' } else { ']' };
+                pretty.push(c);
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                // Empty containers stay on one line
+                if chars.next_if_eq(&close).is_some() {
+                    pretty.push(close);
+                } else {
+                    open.push(close);
+                    newline(&mut pretty, open.len());
+                }
+            }
+            '}' | ']' => {
+                if open.pop()? != c {
+                    return None;
+                }
+                newline(&mut pretty, open.len());
+                pretty.push(c);
+            }
+            ',' => {
+                pretty.push(c);
+                newline(&mut pretty, open.len());
+            }
+            ':' => pretty.push_str(": "),
+            c if c.is_whitespace() => {}
+            c => pretty.push(c),
+        }
+    }
+    if !open.is_empty() || in_string {
+        return None;
+    }
+    if json.ends_with('\n') {
+        pretty.push('\n');
+    }
+    Some(pretty)
+}
+
+/// Format a count with thousands separators: `4312` → `4,312`
+pub fn format_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// `hexdump -C` style dump of the leading `bytes` of a `total`-byte file: offset,
+/// sixteen hex bytes and their printable ASCII, with a note when bytes were left out
+pub fn hex_dump(bytes: &[u8], total: u64) -> String {
+    let mut dump = String::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:08x}  {:<47}  |{}|\n",
+            i * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    let shown = bytes.len() as u64;
+    if total > shown {
+        dump.push_str(&format!("... {} more bytes\n", total - shown));
+    }
+    dump
+}
+
+/// Remove ANSI terminal escape sequences: CSI (`\x1b[31m`, cursor moves),
+/// OSC (`\x1b]0;title\x07`, hyperlinks) and short escapes such as `\x1b(B`
+pub fn strip_ansi(bytes: &[u8]) -> Vec<u8> {
+    static ANSI: OnceLock<regex::bytes::Regex> = OnceLock::new();
+    let ansi = ANSI.get_or_init(|| {
+        regex::bytes::Regex::new(
+            r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(\x07|\x1b\\)|\x1b[ -/]*[0-~]",
+        )
+        .expect("valid ANSI pattern")
+    });
+    ansi.replace_all(bytes, &b""[..]).into_owned()
+}
+
+/// Rough token estimate for LLM prompts (about four bytes per token)
+pub fn estimate_tokens(content: &str) -> usize {
+    estimate_tokens_for_bytes(content.len())
+}
+
+/// `estimate_tokens` for content of `bytes` length that is no longer at hand
+pub fn estimate_tokens_for_bytes(bytes: usize) -> usize {
+    bytes.div_ceil(4)
+}
+
+/// Tokenizer used for `--tokens`, `--max-tokens` and token reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Tokenizer {
+    /// cl100k_base (GPT-4, GPT-3.5)
+    #[value(name = "cl100k")]
+    Cl100k,
+    /// o200k_base (GPT-4o and later)
+    #[value(name = "o200k")]
+    O200k,
+    /// Llama 3, counted with cl100k_base, whose vocabulary Llama 3 extends (slightly high)
+    #[value(name = "llama3")]
+    Llama3,
+    /// About four bytes per token, no tokenizer needed
+    #[value(name = "approx")]
+    Approx,
+}
+
+impl Default for Tokenizer {
+    /// o200k with the `tokens` feature, otherwise the byte estimate
+    fn default() -> Self {
+        if cfg!(feature = "tokens") {
+            Tokenizer::O200k
+        } else {
+            Tokenizer::Approx
+        }
+    }
+}
+
+impl Tokenizer {
+    /// Whether counting needs the `tokens` feature to be exact
+    pub fn is_exact(self) -> bool {
+        self != Tokenizer::Approx
+    }
+
+    /// Count the tokens in `content`; exact tokenizers fall back to the
+    /// estimate when built without the `tokens` feature
+    pub fn count(self, content: &str) -> usize {
+        match self {
+            #[cfg(feature = "tokens")]
+            Tokenizer::O200k => crate::tokens::count_tokens(content),
+            #[cfg(feature = "tokens")]
+            Tokenizer::Cl100k | Tokenizer::Llama3 => crate::tokens::count_cl100k_tokens(content),
+            _ => estimate_tokens(content),
+        }
+    }
+}
+
+/// Token count with the default tokenizer: exact with the `tokens` feature, otherwise estimated
+pub fn token_count(content: &str) -> usize {
+    Tokenizer::default().count(content)
+}
+
+/// The UTC date and time `timestamp` seconds after the Unix epoch
+pub fn utc_datetime(timestamp: u64) -> chrono::DateTime<chrono::Utc> {
+    i64::try_from(timestamp)
+        .ok()
+        .and_then(|seconds| chrono::DateTime::from_timestamp(seconds, 0))
+        .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC)
+}
+
+/// Format seconds since the Unix epoch as a `YYYY-MM-DD` date (UTC)
+pub fn format_date(timestamp: u64) -> String {
+    utc_datetime(timestamp).format("%Y-%m-%d").to_string()
+}
+
+/// Format seconds since the Unix epoch as a `YYYY-MM-DDTHH:MM:SSZ` timestamp (UTC)
+pub fn format_timestamp(timestamp: u64) -> String {
+    utc_datetime(timestamp)
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string()
+}
+
+/// Time zone for timestamps written into bundles.
+///
+/// UTC is the default so bundles generated on different machines diff cleanly;
+/// `--local-time` switches to the machine's offset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeZone {
+    #[default]
+    Utc,
+    Local,
+}
+
+impl TimeZone {
+    /// Offset from UTC in seconds at `timestamp`
+    fn offset_seconds(self, timestamp: u64) -> i64 {
+        match self {
+            TimeZone::Utc => 0,
+            TimeZone::Local => {
+                use chrono::{Offset, TimeZone as _};
+                chrono::Local
+                    .timestamp_opt(timestamp as i64, 0)
+                    .single()
+                    .map_or(0, |time| time.offset().fix().local_minus_utc() as i64)
+            }
+        }
+    }
+
+    /// RFC 3339 timestamp: `2023-11-14T22:13:20Z`, or `2023-11-15T00:13:20+02:00` in local time
+    pub fn format_timestamp(self, timestamp: u64) -> String {
+        let offset = self.offset_seconds(timestamp);
+        if offset == 0 {
+            return format_timestamp(timestamp);
+        }
+
+        let local = format_timestamp(timestamp.saturating_add_signed(offset));
+        let sign = if offset < 0 { '-' } else { '+' };
+        let minutes = offset.unsigned_abs() / 60;
+        format!(
+            "{}{}{:02}:{:02}",
+            local.trim_end_matches('Z'),
+            sign,
+            minutes / 60,
+            minutes % 60
+        )
+    }
+
+    /// `YYYY-MM-DD` date in this time zone
+    pub fn format_date(self, timestamp: u64) -> String {
+        format_date(timestamp.saturating_add_signed(self.offset_seconds(timestamp)))
+    }
+}
+
+/// Write `contents` to `path` so concurrent runs never interleave or tear output.
+///
+/// The data goes to a temporary file in the same directory that is then renamed
+/// over the destination, so readers only ever see a complete file. Writers
+/// serialize on an advisory lock held on a hidden `.NAME.lock` sidecar, which is
+/// removed again once the write is done.
+/// With `backup`, an existing destination is first copied to `NAME.bak`.
+pub fn write_atomic(path: &Path, contents: &[u8], backup: bool) -> io::Result<()> {
+    write_atomic_with(path, backup, |file| {
+        file.write_all(contents)?;
+        Ok::<_, io::Error>(true)
+    })?;
+    Ok(())
+}
+
+/// Like `write_atomic`, but `write` produces the contents straight into the
+/// temporary file, so they never have to be held in memory at once.
+///
+/// If `write` returns `false` or fails, the temporary file is discarded and the
+/// destination left as it was. Returns what `write` returned.
+pub fn write_atomic_with<E: From<io::Error>>(
+    path: &Path,
+    backup: bool,
+    write: impl FnOnce(&mut dyn Write) -> Result<bool, E>,
+) -> Result<bool, E> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "output path has no file name"))?
+        .to_string_lossy();
+
+    let lock_path = dir.join(format!(".{}.lock", name));
+    let lock = lock_sidecar(&lock_path)?;
+
+    let backup_path = backup.then(|| dir.join(format!("{}.bak", name)));
+    let temp_path = dir.join(format!(".{}.{}.tmp", name, std::process::id()));
+    let result = write_and_rename(&temp_path, path, backup_path.as_deref(), write);
+    if !matches!(result, Ok(true)) {
+        let _ = fs::remove_file(&temp_path);
+    }
+    // Still holding the lock, so a waiting writer sees the sidecar is gone and retries
+    let _ = fs::remove_file(&lock_path);
+    drop(lock);
+    result
+}
+
+/// Create and lock the sidecar at `lock_path`, waiting for other writers.
+///
+/// A writer that was waiting on a sidecar its holder has since removed starts over
+/// with a new one, so two writers never hold locks on different files at once.
+fn lock_sidecar(lock_path: &Path) -> io::Result<File> {
+    loop {
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lock_path)?;
+        lock.lock()?;
+        if is_same_file(&lock, lock_path) {
+            return Ok(lock);
+        }
+    }
+}
+
+/// Whether `path` still names the open `file`
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(named)) => open.dev() == named.dev() && open.ino() == named.ino(),
+        _ => false,
+    }
+}
+
+/// Whether `path` still names the open `file`; open files can't be removed here,
+/// so it's enough that it exists
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+fn write_and_rename<E: From<io::Error>>(
+    temp_path: &Path,
+    path: &Path,
+    backup_path: Option<&Path>,
+    write: impl FnOnce(&mut dyn Write) -> Result<bool, E>,
+) -> Result<bool, E> {
+    let mut file = BufWriter::new(File::create(temp_path)?);
+    if !write(&mut file)? {
+        return Ok(false);
+    }
+    let file = file.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    if let Some(backup_path) = backup_path.filter(|_| path.is_file()) {
+        fs::copy(path, backup_path)?;
+    }
+    fs::rename(temp_path, path)?;
+    Ok(true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,9 +476,124 @@ mod tests {
         assert_eq!(determine_backtick_count(content), "`````");
     }
 
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(Tokenizer::Approx.count("abcde"), 2);
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi(b"\x1b[32mINFO\x1b[0m ready \x1b[1;31m\xe2\x9c\x97\x1b[m\n"),
+            "INFO ready ✗\n".as_bytes()
+        );
+        assert_eq!(
+            strip_ansi(b"\x1b]8;;https://x.dev\x1b\\link\x1b]8;;\x1b\\ \x1b[2K\x1b(Bdone"),
+            b"link done"
+        );
+        assert_eq!(strip_ansi(b"plain [not] escaped"), b"plain [not] escaped");
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(
+            hex_dump(b"\x89PNG\r\n", 6),
+            "00000000  89 50 4e 47 0d 0a                                |.PNG..|\n"
+        );
+        let dump = hex_dump(&[0u8; 32], 40);
+        assert_eq!(dump.lines().count(), 3);
+        assert!(dump.starts_with("00000000  00 00"));
+        assert!(dump.contains("\n00000010  00"));
+        assert!(dump.ends_with("... 8 more bytes\n"));
+    }
+
+    #[test]
+    fn test_format_thousands() {
+        assert_eq!(format_thousands(0), "0");
+        assert_eq!(format_thousands(999), "999");
+        assert_eq!(format_thousands(4312), "4,312");
+        assert_eq!(format_thousands(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("plain"), r#""plain""#);
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+    }
+
+    #[test]
+    fn test_pretty_json() {
+        assert_eq!(
+            pretty_json(r#"{"a":[1,2,{}],"b":{"c":"x, {y}: \"z\""},"d":[ ]}"#).as_deref(),
+            Some(
+                r#"{
+  "a": [
+    1,
+    2,
+    {}
+  ],
+  "b": {
+    "c": "x, {y}: \"z\""
+  },
+  "d": []
+}"#
+            )
+        );
+        assert_eq!(pretty_json("[1]\n").as_deref(), Some("[\n  1\n]\n"));
+        assert_eq!(pretty_json(r#"{"a":[1}"#), None);
+        assert_eq!(pretty_json(r#"{"a":"b"#), None);
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_700_000_000), "2023-11-14");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(
+            TimeZone::Utc.format_timestamp(1_700_000_000),
+            "2023-11-14T22:13:20Z"
+        );
+        assert_eq!(TimeZone::Utc.format_date(1_700_000_000), "2023-11-14");
+    }
+
     #[test]
     fn test_empty_content() {
         assert_eq!(add_line_numbers(""), "");
         assert_eq!(determine_backtick_count(""), "```");
     }
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.txt");
+        fs::write(&path, "old contents that are longer").unwrap();
+
+        write_atomic(&path, b"new", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("out.txt.bak")).unwrap(),
+            "old contents that are longer"
+        );
+        // Neither the temporary file nor the lock sidecar is left behind
+        let mut names: Vec<_> = temp_dir
+            .path()
+            .read_dir()
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["out.txt", "out.txt.bak"]);
+    }
 }